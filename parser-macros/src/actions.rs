@@ -0,0 +1,122 @@
+use std::{fmt, vec::Drain};
+
+use crate::{NonTerminal, ParseError, Parser, Span, Token, TreeBuilder};
+
+impl Parser {
+    /// Parses `input` into a value of the caller's own instead of a tree,
+    /// computing it bottom up as the parse goes: `leaf` for each token
+    /// shifted, and `tree` for each production reduced, given its head and
+    /// the values of its right hand side in source order.
+    ///
+    /// Either action can reject the input by returning an error message,
+    /// for constraints the grammar itself cannot express, such as a number
+    /// being out of range. The parse then fails with
+    /// [`ParseError::Semantic`] spanning the tokens the rejected value was
+    /// built from. A rejection comes before any syntax error later in the
+    /// input.
+    pub fn parse_with_actions<V, L, T>(
+        &self,
+        input: &str,
+        leaf: L,
+        tree: T,
+    ) -> Result<V, ParseError>
+    where
+        V: fmt::Debug,
+        L: FnMut(&Token) -> Result<V, String>,
+        T: FnMut(&NonTerminal, Vec<V>) -> Result<V, String>,
+    {
+        let tables = self.tables();
+        let mut actions = Actions {
+            leaf,
+            tree,
+            error: None,
+        };
+
+        let tokens = self.lexer.lex(input)?.into_iter();
+        let shifted = self.shift_all(tables, &mut actions, tokens, None);
+        if let Some(error) = actions.error.take() {
+            return Err(error);
+        }
+        let mut state = shifted?;
+
+        let reduced = state.reduce(tables, &mut actions, None);
+        if let Some(error) = actions.error {
+            return Err(error);
+        }
+        reduced?;
+
+        if state.stack.is_empty() {
+            return Err(ParseError::UnexpectedEnd {
+                expected: state.expected(tables),
+            });
+        }
+
+        match state.accept(&self.grammar.starting_symbol) {
+            Ok(value) => Ok(value.expect("No action failed").0),
+            Err(_) => Err(self
+                .parse(input)
+                .expect_err("The same input fails to parse into a tree")),
+        }
+    }
+}
+
+/// Runs the actions of [`Parser::parse_with_actions`]. Values carry the
+/// span of their tokens, for errors. Once an action fails the first error
+/// is kept and every later value is `None`.
+struct Actions<L, T> {
+    leaf: L,
+    tree: T,
+    error: Option<ParseError>,
+}
+
+impl<V, L, T> TreeBuilder for Actions<L, T>
+where
+    L: FnMut(&Token) -> Result<V, String>,
+    T: FnMut(&NonTerminal, Vec<V>) -> Result<V, String>,
+{
+    type Value = Option<(V, Option<Span>)>;
+
+    fn leaf(&mut self, token: Token) -> Self::Value {
+        if self.error.is_some() {
+            return None;
+        }
+
+        let result = (self.leaf)(&token);
+        self.finish(result, Some(token.span))
+    }
+
+    fn tree(&mut self, head: &NonTerminal, values: Drain<'_, Self::Value>) -> Self::Value {
+        if self.error.is_some() {
+            return None;
+        }
+
+        let mut span: Option<Span> = None;
+        let mut children = vec![];
+        for (value, child_span) in values.map(|value| value.expect("No action failed")) {
+            children.push(value);
+            span = match (span, child_span) {
+                (Some(span), Some(child)) => Some(span.merge(child)),
+                (span, child) => span.or(child),
+            };
+        }
+
+        let result = (self.tree)(head, children);
+        self.finish(result, span)
+    }
+}
+
+impl<L, T> Actions<L, T> {
+    fn finish<V>(
+        &mut self,
+        result: Result<V, String>,
+        span: Option<Span>,
+    ) -> Option<(V, Option<Span>)> {
+        match result {
+            Ok(value) => Some((value, span)),
+            Err(message) => {
+                self.error = Some(ParseError::Semantic { message, span });
+                None
+            }
+        }
+    }
+}
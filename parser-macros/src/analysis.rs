@@ -0,0 +1,770 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Expression, Grammar, NonTerminal, Parser, Terminal, Tokenizer};
+
+impl Grammar {
+    /// Nonterminals that can derive the empty string.
+    ///
+    /// Only grammars with epsilon productions (an empty right hand side) have
+    /// any. This is a fixed point over `rules`, so the result is the same
+    /// regardless of the map's iteration order.
+    pub fn nullable_nonterminals(&self) -> HashSet<NonTerminal> {
+        let mut nullable = HashSet::new();
+
+        loop {
+            let mut changed = false;
+
+            for (non_terminal, productions) in &self.rules {
+                if nullable.contains(non_terminal) {
+                    continue;
+                }
+
+                let derives_empty = productions.iter().any(|production| {
+                    production.iter().all(|expr| match expr {
+                        Expression::NonTerminal(nt) => nullable.contains(nt),
+                        Expression::Terminal(_) => false,
+                    })
+                });
+
+                if derives_empty {
+                    nullable.insert(non_terminal.clone());
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                return nullable;
+            }
+        }
+    }
+
+    /// Terminals that can begin a string derived from each nonterminal.
+    pub(crate) fn first_sets(&self) -> HashMap<NonTerminal, HashSet<Terminal>> {
+        let nullable = self.nullable_nonterminals();
+        let mut first: HashMap<NonTerminal, HashSet<Terminal>> = self
+            .rules
+            .keys()
+            .map(|nt| (nt.clone(), HashSet::new()))
+            .collect();
+
+        loop {
+            let mut changed = false;
+
+            for (non_terminal, productions) in &self.rules {
+                for production in productions {
+                    let (terminals, _) = first_of_sequence(production, &first, &nullable);
+                    let entry = first.entry(non_terminal.clone()).or_default();
+                    for terminal in terminals {
+                        changed |= entry.insert(terminal);
+                    }
+                }
+            }
+
+            if !changed {
+                return first;
+            }
+        }
+    }
+
+    /// Terminals that can directly follow each nonterminal, with `None`
+    /// standing for the end of input.
+    pub(crate) fn follow_sets(&self) -> HashMap<NonTerminal, HashSet<Option<Terminal>>> {
+        let nullable = self.nullable_nonterminals();
+        let first = self.first_sets();
+        let mut follow: HashMap<NonTerminal, HashSet<Option<Terminal>>> = self
+            .rules
+            .keys()
+            .map(|nt| (nt.clone(), HashSet::new()))
+            .collect();
+
+        follow
+            .entry(self.starting_symbol.clone())
+            .or_default()
+            .insert(None);
+
+        loop {
+            let mut changed = false;
+
+            for (non_terminal, productions) in &self.rules {
+                for production in productions {
+                    for (index, expr) in production.iter().enumerate() {
+                        let Expression::NonTerminal(nt) = expr else {
+                            continue;
+                        };
+
+                        let rest = &production[index + 1..];
+                        let (terminals, rest_nullable) = first_of_sequence(rest, &first, &nullable);
+
+                        let mut additions: Vec<Option<Terminal>> =
+                            terminals.into_iter().map(Some).collect();
+
+                        if rest_nullable {
+                            additions.extend(follow.get(non_terminal).cloned().unwrap_or_default());
+                        }
+
+                        let entry = follow.entry(nt.clone()).or_default();
+                        for addition in additions {
+                            changed |= entry.insert(addition);
+                        }
+                    }
+                }
+            }
+
+            if !changed {
+                return follow;
+            }
+        }
+    }
+}
+
+/// FIRST set of a sequence of symbols, and whether the whole sequence is
+/// nullable.
+pub(crate) fn first_of_sequence(
+    sequence: &[Expression],
+    first: &HashMap<NonTerminal, HashSet<Terminal>>,
+    nullable: &HashSet<NonTerminal>,
+) -> (HashSet<Terminal>, bool) {
+    let mut terminals = HashSet::new();
+
+    for expr in sequence {
+        match expr {
+            Expression::Terminal(t) => {
+                terminals.insert(t.kind());
+                return (terminals, false);
+            }
+            Expression::NonTerminal(nt) => {
+                if let Some(set) = first.get(nt) {
+                    terminals.extend(set.iter().cloned());
+                }
+
+                if !nullable.contains(nt) {
+                    return (terminals, false);
+                }
+            }
+        }
+    }
+
+    (terminals, true)
+}
+
+impl Grammar {
+    /// Nonterminals and terminals in a stable order: the starting symbol
+    /// first, then in the order productions reach them, alternatives in
+    /// order and each left to right. Nonterminals with rules that are never
+    /// reached come last, sorted by name, each followed by what it reaches.
+    /// Terminals are listed by [`Terminal::kind`].
+    ///
+    /// The order only depends on the rules, not on how
+    /// [`Grammar::rules`] happens to hash, so tables, dumps and encodings
+    /// built in this order come out the same every time.
+    pub fn symbols_in_order(&self) -> (Vec<NonTerminal>, Vec<Terminal>) {
+        let mut non_terminals = vec![self.starting_symbol.clone()];
+        let mut terminals = vec![];
+        let mut index = 0;
+
+        loop {
+            while let Some(non_terminal) = non_terminals.get(index).cloned() {
+                index += 1;
+
+                for production in self.rules.get(&non_terminal).into_iter().flatten() {
+                    for expr in production {
+                        match expr {
+                            Expression::Terminal(t) if !terminals.contains(&t.kind()) => {
+                                terminals.push(t.kind())
+                            }
+                            Expression::NonTerminal(nt) if !non_terminals.contains(nt) => {
+                                non_terminals.push(nt.clone())
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+
+            let mut unreached: Vec<&NonTerminal> = self
+                .rules
+                .keys()
+                .filter(|nt| !non_terminals.contains(nt))
+                .collect();
+            unreached.sort_by_key(|nt| nt.to_string());
+
+            let Some(next) = unreached.first() else {
+                return (non_terminals, terminals);
+            };
+
+            non_terminals.push((*next).clone());
+        }
+    }
+
+    /// Terminal kinds that [`Tokenizer::new`] can produce but no rule
+    /// uses, sorted. Input holding one never parses, so a long list can
+    /// mean a rule was forgotten. For a parser with a lexer of its own, see
+    /// [`Parser::unused_terminals`].
+    pub fn unused_terminals(&self) -> Vec<Terminal> {
+        self.unused_in(Tokenizer::new().terminals())
+    }
+
+    /// Terminal kinds that rules use but [`Tokenizer::new`] never
+    /// produces, sorted. They need another lexer, such as a
+    /// [`CharLexer`](crate::CharLexer) for [`Terminal::Char`], or they are
+    /// never matched. For a parser with a lexer of its own, see
+    /// [`Parser::undeclared_terminals`].
+    pub fn undeclared_terminals(&self) -> Vec<Terminal> {
+        self.undeclared_in(Tokenizer::new().terminals())
+    }
+
+    /// The terminal kinds in `declared` that no rule uses, sorted.
+    fn unused_in(&self, declared: Vec<Terminal>) -> Vec<Terminal> {
+        let used = self.symbols_in_order().1;
+
+        let mut unused: Vec<Terminal> = declared
+            .into_iter()
+            .filter(|terminal| !used.contains(terminal))
+            .collect();
+        unused.sort();
+        unused
+    }
+
+    /// The terminal kinds rules use that are not in `declared`, sorted.
+    fn undeclared_in(&self, declared: Vec<Terminal>) -> Vec<Terminal> {
+        let mut undeclared: Vec<Terminal> = self
+            .symbols_in_order()
+            .1
+            .into_iter()
+            .filter(|terminal| !declared.contains(terminal))
+            .collect();
+        undeclared.sort();
+        undeclared
+    }
+}
+
+impl Parser {
+    /// Terminal kinds that the parser's lexer can produce but no rule
+    /// uses, sorted, as [`Grammar::unused_terminals`] finds them for the
+    /// default [`Tokenizer`]. `None` if the lexer cannot list its terminals,
+    /// as [`Lexer::terminal_kinds`](crate::Lexer::terminal_kinds) says of
+    /// [`CharLexer`](crate::CharLexer), which produces a terminal for every
+    /// character: there is then nothing to check the grammar against.
+    pub fn unused_terminals(&self) -> Option<Vec<Terminal>> {
+        Some(self.grammar.unused_in(self.lexer.terminal_kinds()?))
+    }
+
+    /// Terminal kinds that rules use but the parser's lexer never
+    /// produces, sorted, as [`Grammar::undeclared_terminals`] finds them
+    /// for the default [`Tokenizer`]. `None` if the lexer cannot list its
+    /// terminals, as for [`Parser::unused_terminals`].
+    pub fn undeclared_terminals(&self) -> Option<Vec<Terminal>> {
+        Some(self.grammar.undeclared_in(self.lexer.terminal_kinds()?))
+    }
+}
+
+impl Grammar {
+    /// The shortest terminal string each nonterminal can derive.
+    ///
+    /// Nonterminals that cannot derive any terminal string are absent, so
+    /// the keys are exactly the productive nonterminals. Ties between
+    /// alternatives go to the earliest one, so the result does not depend on
+    /// the iteration order of `rules`.
+    pub fn shortest_sentences(&self) -> HashMap<NonTerminal, Vec<Terminal>> {
+        let (order, _) = self.symbols_in_order();
+        let mut shortest: HashMap<NonTerminal, Vec<Terminal>> = HashMap::new();
+        let mut chosen: HashMap<NonTerminal, usize> = HashMap::new();
+
+        loop {
+            let mut changed = false;
+
+            for non_terminal in &order {
+                for (alternative, production) in self
+                    .rules
+                    .get(non_terminal)
+                    .into_iter()
+                    .flatten()
+                    .enumerate()
+                {
+                    let candidate: Option<Vec<Terminal>> =
+                        production.iter().try_fold(vec![], |mut sentence, expr| {
+                            match expr {
+                                Expression::Terminal(t) => sentence.push(t.clone()),
+                                Expression::NonTerminal(nt) => {
+                                    sentence.extend(shortest.get(nt)?.iter().cloned())
+                                }
+                            }
+                            Some(sentence)
+                        });
+
+                    let Some(candidate) = candidate else {
+                        continue;
+                    };
+
+                    let better = match (shortest.get(non_terminal), chosen.get(non_terminal)) {
+                        (Some(current), Some(current_alternative)) => {
+                            (candidate.len(), alternative) <= (current.len(), *current_alternative)
+                                && candidate != *current
+                        }
+                        _ => true,
+                    };
+
+                    if better {
+                        shortest.insert(non_terminal.clone(), candidate);
+                        chosen.insert(non_terminal.clone(), alternative);
+                        changed = true;
+                    }
+                }
+            }
+
+            if !changed {
+                return shortest;
+            }
+        }
+    }
+}
+
+impl Grammar {
+    /// Checks that `self` and `other` accept exactly the same sentences of
+    /// at most `max_len` terminals, returning a sentence only one of them
+    /// accepts otherwise. When there are several the shortest is returned.
+    ///
+    /// This is a bounded check, not a decision procedure: grammars that
+    /// agree up to `max_len` may still differ on longer sentences.
+    /// Terminals are compared by [`Terminal::kind`].
+    pub fn equivalent_up_to(&self, other: &Grammar, max_len: usize) -> Result<(), Vec<Terminal>> {
+        // Whether the other grammar accepts a sentence is decided by the
+        // memoized derivation count the other ambiguity checks share.
+        let ours = self.sentences_up_to(max_len);
+        let theirs = other.sentences_up_to(max_len);
+
+        let mut witnesses: Vec<&Vec<Terminal>> = ours
+            .iter()
+            .filter(|sentence| !other.derives(sentence))
+            .chain(theirs.iter().filter(|sentence| !self.derives(sentence)))
+            .collect();
+        witnesses.sort_by_cached_key(|sentence| {
+            (
+                sentence.len(),
+                sentence
+                    .iter()
+                    .map(Terminal::to_string)
+                    .collect::<Vec<String>>(),
+            )
+        });
+
+        match witnesses.first() {
+            Some(witness) => Err((*witness).clone()),
+            None => Ok(()),
+        }
+    }
+
+    /// Every sentence of at most `max_len` terminals the starting symbol
+    /// derives.
+    pub(crate) fn sentences_up_to(&self, max_len: usize) -> HashSet<Vec<Terminal>> {
+        let mut sentences: HashMap<NonTerminal, HashSet<Vec<Terminal>>> = HashMap::new();
+
+        loop {
+            let mut changed = false;
+
+            for (non_terminal, productions) in &self.rules {
+                for production in productions {
+                    let mut partial: HashSet<Vec<Terminal>> = HashSet::from([vec![]]);
+
+                    for expr in production {
+                        let options: Vec<Vec<Terminal>> = match expr {
+                            Expression::Terminal(t) => vec![vec![t.kind()]],
+                            Expression::NonTerminal(nt) => sentences
+                                .get(nt)
+                                .map(|set| set.iter().cloned().collect())
+                                .unwrap_or_default(),
+                        };
+
+                        partial = partial
+                            .iter()
+                            .flat_map(|prefix| {
+                                options
+                                    .iter()
+                                    .filter(move |option| prefix.len() + option.len() <= max_len)
+                                    .map(move |option| [prefix.as_slice(), option].concat())
+                            })
+                            .collect();
+                    }
+
+                    let entry = sentences.entry(non_terminal.clone()).or_default();
+                    for sentence in partial {
+                        changed |= entry.insert(sentence);
+                    }
+                }
+            }
+
+            if !changed {
+                return sentences.remove(&self.starting_symbol).unwrap_or_default();
+            }
+        }
+    }
+}
+
+impl Grammar {
+    /// Whether some nonterminal can derive itself through unit productions
+    /// alone (`a -> b`, `b -> a`). The reduction loop could rewrite such a
+    /// cycle forever without consuming input.
+    pub fn has_cycle(&self) -> bool {
+        !self.unit_cycles().is_empty()
+    }
+
+    /// Every cycle of unit productions, each listed from its nonterminal
+    /// that the rules reach first from the starting symbol, so `[a, b]`
+    /// stands for `a -> b -> a`. Cycles are ordered by that first
+    /// nonterminal, then by the order of the alternatives they follow.
+    pub fn unit_cycles(&self) -> Vec<Vec<NonTerminal>> {
+        let (order, _) = self.symbols_in_order();
+        let index = |nt: &NonTerminal| order.iter().position(|other| other == nt);
+
+        let units = |nt: &NonTerminal| -> Vec<NonTerminal> {
+            let mut targets: Vec<NonTerminal> = vec![];
+            for production in self.rules.get(nt).into_iter().flatten() {
+                if let [Expression::NonTerminal(target)] = production.as_slice()
+                    && !targets.contains(target)
+                {
+                    targets.push(target.clone());
+                }
+            }
+            targets
+        };
+
+        let mut cycles = vec![];
+
+        for start in &order {
+            let start_index = index(start);
+
+            // Depth first search over simple paths through nonterminals that
+            // come after `start`, so each cycle is found once.
+            let mut path = vec![start.clone()];
+            let mut pending = vec![units(start)];
+
+            while let Some(targets) = pending.last_mut() {
+                let Some(next) = targets.first().cloned() else {
+                    pending.pop();
+                    path.pop();
+                    continue;
+                };
+                targets.remove(0);
+
+                if next == *start {
+                    cycles.push(path.clone());
+                } else if index(&next) > start_index && !path.contains(&next) {
+                    pending.push(units(&next));
+                    path.push(next);
+                }
+            }
+        }
+
+        cycles
+    }
+}
+
+impl Grammar {
+    /// Whether the grammar is regular by its shape alone: either every
+    /// production is right-linear, with at most one nonterminal and only as
+    /// its last symbol (`a -> '0' '+' b`), or every production is
+    /// left-linear, with at most one nonterminal and only as its first
+    /// symbol (`a -> b '+' '0'`). Productions with no nonterminal, including
+    /// empty ones, count as both. Mixing the two forms is not accepted even
+    /// where the language happens to be regular, so `false` only means the
+    /// check could not tell.
+    pub fn is_regular(&self) -> bool {
+        let productions = || self.rules.values().flatten();
+        let non_terminals = |production: &[Expression]| {
+            production
+                .iter()
+                .filter(|expr| matches!(expr, Expression::NonTerminal(_)))
+                .count()
+        };
+
+        let right_linear = productions().all(|production| match production.split_last() {
+            Some((_, init)) => non_terminals(init) == 0,
+            None => true,
+        });
+        let left_linear = productions().all(|production| match production.split_first() {
+            Some((_, tail)) => non_terminals(tail) == 0,
+            None => true,
+        });
+
+        right_linear || left_linear
+    }
+}
+
+impl Grammar {
+    /// Leading symbol sequences shared by two or more alternatives of the
+    /// same nonterminal, which give those alternatives overlapping FIRST
+    /// sets and would need left factoring for an LL(1) parser.
+    ///
+    /// Alternatives are grouped by their first symbol, and each group of at
+    /// least two reports the longest prefix all its members share.
+    /// Terminals compare by [`Terminal::kind`]. Results follow
+    /// [`Grammar::symbols_in_order`], then the order of the first alternative
+    /// in each group.
+    pub fn common_prefixes(&self) -> Vec<(NonTerminal, Vec<Expression>)> {
+        let kind = |expr: &Expression| match expr {
+            Expression::Terminal(t) => Expression::Terminal(t.kind()),
+            Expression::NonTerminal(_) => expr.clone(),
+        };
+
+        let mut prefixes = vec![];
+
+        for non_terminal in self.symbols_in_order().0 {
+            let mut groups: Vec<Vec<Vec<Expression>>> = vec![];
+
+            for alternative in self.rules.get(&non_terminal).into_iter().flatten() {
+                let alternative: Vec<Expression> = alternative.iter().map(kind).collect();
+                let Some(first) = alternative.first() else {
+                    continue;
+                };
+
+                match groups.iter_mut().find(|group| group[0][0] == *first) {
+                    Some(group) => group.push(alternative),
+                    None => groups.push(vec![alternative]),
+                }
+            }
+
+            for group in groups.into_iter().filter(|group| group.len() > 1) {
+                let shared = (1..)
+                    .take_while(|len| {
+                        group
+                            .iter()
+                            .all(|alternative| alternative.get(..*len) == group[0].get(..*len))
+                    })
+                    .last()
+                    .unwrap_or(1);
+
+                prefixes.push((non_terminal.clone(), group[0][..shared].to_vec()));
+            }
+        }
+
+        prefixes
+    }
+}
+
+impl Grammar {
+    /// The first `limit` sentences of the language, shortest first and
+    /// sentences of the same length ordered by the Display strings of their
+    /// terminals, so the output is stable. Terminals are given by
+    /// [`Terminal::kind`]. Fewer come back only if the language has fewer.
+    ///
+    /// Sentences are generated one length at a time, so recursive rules
+    /// never get expanded past the length being collected.
+    pub fn enumerate(&self, limit: usize) -> Vec<Vec<Terminal>> {
+        let longest = self.longest_sentence_len();
+        let mut found = vec![];
+        let mut len = 0;
+
+        while found.len() < limit && longest.is_none_or(|longest| len <= longest) {
+            let mut sentences: Vec<Vec<Terminal>> = self
+                .sentences_up_to(len)
+                .into_iter()
+                .filter(|sentence| sentence.len() == len)
+                .collect();
+            sentences.sort_by_cached_key(|sentence| {
+                sentence
+                    .iter()
+                    .map(Terminal::to_string)
+                    .collect::<Vec<String>>()
+            });
+
+            found.extend(sentences.into_iter().take(limit - found.len()));
+            len += 1;
+        }
+
+        found
+    }
+
+    /// The length of the longest sentence, or `None` if sentences can be
+    /// arbitrarily long. Only the productions that can take part in a
+    /// derivation of a sentence from the starting symbol count.
+    fn longest_sentence_len(&self) -> Option<usize> {
+        let productive = self.shortest_sentences();
+        if !productive.contains_key(&self.starting_symbol) {
+            return Some(0);
+        }
+
+        let usable = |production: &Vec<Expression>| {
+            production.iter().all(|expr| match expr {
+                Expression::Terminal(_) => true,
+                Expression::NonTerminal(nt) => productive.contains_key(nt),
+            })
+        };
+        let productions = |nt: &NonTerminal| {
+            self.rules
+                .get(nt)
+                .into_iter()
+                .flatten()
+                .filter(move |production| usable(production))
+        };
+
+        let mut reached = vec![self.starting_symbol.clone()];
+        let mut index = 0;
+        while let Some(non_terminal) = reached.get(index).cloned() {
+            index += 1;
+            for expr in productions(&non_terminal).flatten() {
+                if let Expression::NonTerminal(nt) = expr
+                    && !reached.contains(nt)
+                {
+                    reached.push(nt.clone());
+                }
+            }
+        }
+
+        // Nonterminals that can derive at least one terminal.
+        let mut nonempty: HashSet<NonTerminal> = HashSet::new();
+        loop {
+            let before = nonempty.len();
+            for non_terminal in &reached {
+                if productions(non_terminal).flatten().any(|expr| match expr {
+                    Expression::Terminal(_) => true,
+                    Expression::NonTerminal(nt) => nonempty.contains(nt),
+                }) {
+                    nonempty.insert(non_terminal.clone());
+                }
+            }
+            if nonempty.len() == before {
+                break;
+            }
+        }
+
+        let reaches = |from: &NonTerminal, to: &NonTerminal| {
+            let mut seen = vec![from.clone()];
+            let mut index = 0;
+            while let Some(non_terminal) = seen.get(index).cloned() {
+                index += 1;
+                for expr in productions(&non_terminal).flatten() {
+                    if let Expression::NonTerminal(nt) = expr
+                        && !seen.contains(nt)
+                    {
+                        seen.push(nt.clone());
+                    }
+                }
+            }
+            seen.contains(to)
+        };
+
+        // Sentences are unbounded exactly when a nonterminal can derive
+        // itself alongside something that yields a terminal.
+        for non_terminal in &reached {
+            for production in productions(non_terminal) {
+                for (position, expr) in production.iter().enumerate() {
+                    let Expression::NonTerminal(nt) = expr else {
+                        continue;
+                    };
+
+                    let grows = production.iter().enumerate().any(|(other, expr)| {
+                        other != position
+                            && match expr {
+                                Expression::Terminal(_) => true,
+                                Expression::NonTerminal(nt) => nonempty.contains(nt),
+                            }
+                    });
+
+                    if grows && reaches(nt, non_terminal) {
+                        return None;
+                    }
+                }
+            }
+        }
+
+        let mut longest: HashMap<NonTerminal, usize> = HashMap::new();
+        loop {
+            let mut changed = false;
+            for non_terminal in &reached {
+                for production in productions(non_terminal) {
+                    let len = production
+                        .iter()
+                        .map(|expr| match expr {
+                            Expression::Terminal(_) => 1,
+                            Expression::NonTerminal(nt) => longest.get(nt).copied().unwrap_or(0),
+                        })
+                        .sum();
+
+                    let current = longest.entry(non_terminal.clone()).or_default();
+                    if len > *current {
+                        *current = len;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                return longest.get(&self.starting_symbol).copied();
+            }
+        }
+    }
+}
+
+impl Grammar {
+    /// The largest `k` [`Grammar::first_k`] accepts.
+    pub const MAX_FIRST_K: usize = 8;
+
+    /// FIRST_k sets: every string of up to `k` terminals that can begin a
+    /// string derived from each nonterminal. Strings shorter than `k` are
+    /// whole derivations, so an empty string means the nonterminal is
+    /// nullable. `first_k(1)` is the usual FIRST set plus that empty string.
+    ///
+    /// A set can hold up to |T|^k strings for |T| terminals, and the fixed
+    /// point revisits every production until no set grows, so the cost is
+    /// exponential in `k`.
+    ///
+    /// # Panics
+    ///
+    /// If `k` is larger than [`Grammar::MAX_FIRST_K`].
+    pub fn first_k(&self, k: usize) -> HashMap<NonTerminal, HashSet<Vec<Terminal>>> {
+        assert!(
+            k <= Self::MAX_FIRST_K,
+            "first_k supports k up to {}, got {k}",
+            Self::MAX_FIRST_K
+        );
+
+        let mut first: HashMap<NonTerminal, HashSet<Vec<Terminal>>> = self
+            .rules
+            .keys()
+            .map(|nt| (nt.clone(), HashSet::new()))
+            .collect();
+
+        loop {
+            let mut changed = false;
+
+            for (non_terminal, productions) in &self.rules {
+                for production in productions {
+                    // Prefixes of the production so far, truncated to `k`.
+                    let mut prefixes: HashSet<Vec<Terminal>> = HashSet::from([vec![]]);
+
+                    for expr in production {
+                        let options: Vec<Vec<Terminal>> = match expr {
+                            Expression::Terminal(t) => vec![vec![t.kind()]],
+                            Expression::NonTerminal(nt) => first
+                                .get(nt)
+                                .map(|set| set.iter().cloned().collect())
+                                .unwrap_or_default(),
+                        };
+
+                        prefixes = prefixes
+                            .iter()
+                            .flat_map(|prefix| {
+                                options.iter().map(move |option| {
+                                    if prefix.len() >= k {
+                                        return prefix.clone();
+                                    }
+                                    let mut joined = prefix.clone();
+                                    joined.extend(option.iter().take(k - prefix.len()).cloned());
+                                    joined
+                                })
+                            })
+                            .collect();
+                    }
+
+                    let entry = first.entry(non_terminal.clone()).or_default();
+                    for prefix in prefixes {
+                        changed |= entry.insert(prefix);
+                    }
+                }
+            }
+
+            if !changed {
+                return first;
+            }
+        }
+    }
+}
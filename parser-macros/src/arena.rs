@@ -0,0 +1,145 @@
+use std::{ops::Index, vec::Drain};
+
+use crate::{NonTerminal, ParseError, Parser, StackValue, Token, TreeBuilder};
+
+/// Index of a [`Node`] in an [`Arena`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone)]
+pub enum Node {
+    /// A reduced production. Its children are listed by
+    /// [`Arena::children`], in source order.
+    Tree {
+        head: NonTerminal,
+        children: (usize, usize),
+    },
+    Terminal(Token),
+}
+
+/// A parse tree stored flat, as the arena-backed alternative to owned
+/// [`StackValue`] trees.
+///
+/// Nodes live in one `Vec` and refer to their children by [`NodeId`]. All
+/// child lists share a second `Vec`, so building a tree allocates nothing
+/// per node once the two have grown.
+#[derive(Debug, Clone, Default)]
+pub struct Arena {
+    nodes: Vec<Node>,
+    children: Vec<NodeId>,
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Arena::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.0)
+    }
+
+    /// Children of `id`, in source order. Empty for a terminal.
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        match &self[id] {
+            Node::Tree {
+                head: _,
+                children: (start, end),
+            } => &self.children[*start..*end],
+            Node::Terminal(_) => &[],
+        }
+    }
+
+    /// Copies the tree below `root` out into an owned [`StackValue`].
+    pub fn to_tree(&self, root: NodeId) -> StackValue {
+        // Post-order walk, finishing each tree once its children are built.
+        let mut pending = vec![(root, false)];
+        let mut built: Vec<StackValue> = vec![];
+
+        while let Some((id, expanded)) = pending.pop() {
+            match &self[id] {
+                Node::Terminal(token) => built.push(StackValue::Terminal(token.clone())),
+                Node::Tree { head, children: _ } if expanded => {
+                    let values = built.split_off(built.len() - self.children(id).len());
+                    built.push(StackValue::Tree {
+                        head: head.clone(),
+                        values,
+                    });
+                }
+                Node::Tree { .. } => {
+                    pending.push((id, true));
+                    pending.extend(self.children(id).iter().rev().map(|child| (*child, false)));
+                }
+            }
+        }
+
+        built.pop().expect("The root is always built")
+    }
+
+    fn push(&mut self, node: Node) -> NodeId {
+        self.nodes.push(node);
+        NodeId(self.nodes.len() - 1)
+    }
+}
+
+impl Index<NodeId> for Arena {
+    type Output = Node;
+
+    fn index(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+}
+
+impl TreeBuilder for Arena {
+    type Value = NodeId;
+
+    fn leaf(&mut self, token: Token) -> NodeId {
+        self.push(Node::Terminal(token))
+    }
+
+    fn tree(&mut self, head: &NonTerminal, children: Drain<'_, NodeId>) -> NodeId {
+        let start = self.children.len();
+        self.children.extend(children);
+        let end = self.children.len();
+
+        self.push(Node::Tree {
+            head: head.clone(),
+            children: (start, end),
+        })
+    }
+}
+
+impl Parser {
+    /// Parses `input` into an [`Arena`], returning it with the root node.
+    /// Produces the same tree as [`Parser::parse`], which is the convenience
+    /// form for owned trees.
+    pub fn parse_into_arena(&self, input: &str) -> Result<(Arena, NodeId), ParseError> {
+        let tables = self.tables();
+        let mut arena = Arena::new();
+
+        let mut state =
+            self.shift_all(tables, &mut arena, self.lexer.lex(input)?.into_iter(), None)?;
+        state.reduce(tables, &mut arena, None)?;
+
+        if state.stack.is_empty() {
+            return Err(ParseError::UnexpectedEnd {
+                expected: state.expected(tables),
+            });
+        }
+
+        match state.accept(&self.grammar.starting_symbol) {
+            Ok(root) => Ok((arena, root)),
+            Err(stack) => Err(ParseError::BadStack {
+                stack: stack.iter().map(|id| arena.to_tree(*id)).collect(),
+                next: None,
+            }),
+        }
+    }
+}
@@ -0,0 +1,665 @@
+//! A compact binary encoding of grammars, for embedding precompiled
+//! grammars that are loaded often, and of parses in progress, for
+//! checkpointing them.
+//!
+//! The encoding starts with a version byte. Lengths and indices are LEB128
+//! varints, symbols are one tag byte (char and byte terminals add theirs),
+//! and string payloads are a length followed by UTF-8. Rules are written
+//! sorted by nonterminal, so equal grammars encode to equal bytes.
+
+use std::{collections::HashSet, fmt};
+
+use crate::{
+    CharClass, DebugState, Expression, FloatLiteral, Grammar, NonTerminal, ParseMetrics,
+    ParseState, Span, StackValue, Terminal, Token, Trivia, opens,
+};
+
+const VERSION: u8 = 2;
+
+/// Why [`Grammar::from_bytes`] or [`DebugState::from_bytes`] could not
+/// decode its input. Offsets count
+/// bytes from the start of the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input was written by an incompatible version of the encoding.
+    UnsupportedVersion(u8),
+    /// The input ended in the middle of a value.
+    UnexpectedEnd,
+    /// A symbol tag that names no symbol.
+    InvalidTag { tag: u8, offset: usize },
+    /// A length or index too large for this platform.
+    InvalidLength { offset: usize },
+    /// A string payload that is not UTF-8.
+    InvalidUtf8 { offset: usize },
+    /// Bytes left over after the grammar.
+    TrailingBytes { offset: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported grammar encoding version {version}")
+            }
+            DecodeError::UnexpectedEnd => write!(f, "Encoded grammar ends too early"),
+            DecodeError::InvalidTag { tag, offset } => {
+                write!(f, "Invalid symbol tag {tag} at byte {offset}")
+            }
+            DecodeError::InvalidLength { offset } => {
+                write!(f, "Length at byte {offset} is too large")
+            }
+            DecodeError::InvalidUtf8 { offset } => {
+                write!(f, "String at byte {offset} is not UTF-8")
+            }
+            DecodeError::TrailingBytes { offset } => {
+                write!(f, "Unexpected bytes after the grammar at byte {offset}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Grammar {
+    /// Encodes the grammar, rules, lookahead restrictions and labels
+    /// included.
+    /// [`Grammar::from_bytes`] reads it back.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![VERSION];
+
+        write_non_terminal(&mut out, &self.starting_symbol);
+
+        let mut rules: Vec<_> = self.rules.iter().collect();
+        rules.sort_by_cached_key(|(nt, _)| non_terminal_bytes(nt));
+        write_len(&mut out, rules.len());
+        for (non_terminal, alternatives) in rules {
+            write_non_terminal(&mut out, non_terminal);
+            write_len(&mut out, alternatives.len());
+            for alternative in alternatives {
+                write_len(&mut out, alternative.len());
+                for expr in alternative {
+                    match expr {
+                        Expression::Terminal(t) => {
+                            out.push(0);
+                            write_terminal(&mut out, t);
+                        }
+                        Expression::NonTerminal(nt) => {
+                            out.push(1);
+                            write_non_terminal(&mut out, nt);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut restrictions: Vec<_> = self.reduce_lookahead.iter().collect();
+        restrictions
+            .sort_by_cached_key(|((nt, alternative), _)| (non_terminal_bytes(nt), *alternative));
+        write_len(&mut out, restrictions.len());
+        for ((non_terminal, alternative), lookahead) in restrictions {
+            write_non_terminal(&mut out, non_terminal);
+            write_len(&mut out, *alternative);
+
+            let mut terminals: Vec<Vec<u8>> = lookahead
+                .iter()
+                .map(|t| {
+                    let mut bytes = vec![];
+                    write_terminal(&mut bytes, t);
+                    bytes
+                })
+                .collect();
+            terminals.sort();
+            write_len(&mut out, terminals.len());
+            for bytes in terminals {
+                out.extend(bytes);
+            }
+        }
+
+        let mut labels: Vec<_> = self.labels.iter().collect();
+        labels.sort_by_cached_key(|((nt, alternative), _)| (non_terminal_bytes(nt), *alternative));
+        write_len(&mut out, labels.len());
+        for ((non_terminal, alternative), label) in labels {
+            write_non_terminal(&mut out, non_terminal);
+            write_len(&mut out, *alternative);
+            write_str(&mut out, label);
+        }
+
+        out
+    }
+
+    /// Decodes a grammar written by [`Grammar::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Grammar, DecodeError> {
+        let mut reader = Reader { bytes, offset: 0 };
+
+        let version = reader.byte()?;
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+
+        let starting_symbol = reader.non_terminal()?;
+
+        let mut rules = vec![];
+        for _ in 0..reader.len()? {
+            let non_terminal = reader.non_terminal()?;
+            let mut alternatives = vec![];
+            for _ in 0..reader.len()? {
+                let mut alternative = vec![];
+                for _ in 0..reader.len()? {
+                    let offset = reader.offset;
+                    alternative.push(match reader.byte()? {
+                        0 => Expression::Terminal(reader.terminal()?),
+                        1 => Expression::NonTerminal(reader.non_terminal()?),
+                        tag => return Err(DecodeError::InvalidTag { tag, offset }),
+                    });
+                }
+                alternatives.push(alternative);
+            }
+            rules.push((non_terminal, alternatives));
+        }
+
+        let mut grammar = Grammar::from_rules(starting_symbol, rules);
+
+        for _ in 0..reader.len()? {
+            let non_terminal = reader.non_terminal()?;
+            let alternative = reader.len()?;
+            let mut lookahead = HashSet::new();
+            for _ in 0..reader.len()? {
+                lookahead.insert(reader.terminal()?);
+            }
+            grammar
+                .reduce_lookahead
+                .insert((non_terminal, alternative), lookahead);
+        }
+
+        for _ in 0..reader.len()? {
+            let non_terminal = reader.non_terminal()?;
+            let alternative = reader.len()?;
+            grammar
+                .labels
+                .insert((non_terminal, alternative), reader.string()?);
+        }
+
+        if reader.offset != bytes.len() {
+            return Err(DecodeError::TrailingBytes {
+                offset: reader.offset,
+            });
+        }
+
+        Ok(grammar)
+    }
+}
+
+fn write_non_terminal(out: &mut Vec<u8>, non_terminal: &NonTerminal) {
+    let tag = match non_terminal {
+        NonTerminal::Sum => 0,
+        NonTerminal::Sub => 1,
+        NonTerminal::Mult => 2,
+        NonTerminal::Atom => 3,
+        NonTerminal::Number => 4,
+        NonTerminal::List => 5,
+        NonTerminal::Elements => 6,
+        NonTerminal::Expr => 7,
+        NonTerminal::Generated(_) => 8,
+    };
+
+    out.push(tag);
+    if let NonTerminal::Generated(index) = non_terminal {
+        write_len(out, *index);
+    }
+}
+
+impl DebugState {
+    /// Encodes the parse so far: the stack, the input still to parse and
+    /// how far it has got, for [`DebugState::from_bytes`] to resume later,
+    /// in another process if need be. Whether the parse had finished is not
+    /// kept, so stepping a restored parse that had redoes its last step,
+    /// which gives the same result unless the input did not tokenize.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![VERSION];
+
+        write_len(&mut out, self.tokens.len());
+        for token in &self.tokens {
+            write_token(&mut out, token);
+        }
+        write_len(&mut out, self.index);
+
+        let state = &self.state;
+        write_len(&mut out, state.stack.len());
+        for value in &state.stack {
+            write_stack_value(&mut out, value);
+        }
+        write_len(&mut out, state.states.len());
+        for target in &state.states {
+            write_len(&mut out, *target);
+        }
+        let metrics = state.metrics;
+        for count in [
+            metrics.shifts,
+            metrics.reductions,
+            metrics.peak_stack_depth,
+            metrics.tokens,
+        ] {
+            write_len(&mut out, count);
+        }
+
+        out
+    }
+
+    /// Decodes a parse written by [`DebugState::to_bytes`]. It can only be
+    /// stepped by a parser for the same grammar as the one that started it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<DebugState, DecodeError> {
+        let mut reader = Reader { bytes, offset: 0 };
+
+        let version = reader.byte()?;
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+
+        let mut tokens = vec![];
+        for _ in 0..reader.len()? {
+            tokens.push(reader.token()?);
+        }
+        let index = reader.len()?;
+
+        let mut state = ParseState::new();
+        state.states.clear();
+        for _ in 0..reader.len()? {
+            let value = reader.stack_value()?;
+            if let StackValue::Terminal(token) = &value
+                && opens(&token.terminal)
+            {
+                state.open.push((state.stack.len(), token.span));
+            }
+            state.symbols.push(match &value {
+                StackValue::Terminal(token) => Expression::Terminal(token.terminal.kind()),
+                StackValue::Tree { head, .. } => Expression::NonTerminal(head.clone()),
+            });
+            state.stack.push(value);
+        }
+        let offset = reader.offset;
+        let states = reader.len()?;
+        if states != state.stack.len() + 1 {
+            return Err(DecodeError::InvalidLength { offset });
+        }
+        for _ in 0..states {
+            state.states.push(reader.len()?);
+        }
+        state.metrics = ParseMetrics {
+            shifts: reader.len()?,
+            reductions: reader.len()?,
+            peak_stack_depth: reader.len()?,
+            tokens: reader.len()?,
+        };
+
+        if reader.offset != bytes.len() {
+            return Err(DecodeError::TrailingBytes {
+                offset: reader.offset,
+            });
+        }
+
+        Ok(DebugState {
+            tokens,
+            index,
+            state,
+            finished: None,
+        })
+    }
+}
+
+fn write_stack_value(out: &mut Vec<u8>, value: &StackValue) {
+    // Pre-order, with each tree's child count before its children, so
+    // deep trees do not recurse.
+    let mut pending = vec![value];
+    while let Some(value) = pending.pop() {
+        match value {
+            StackValue::Terminal(token) => {
+                out.push(0);
+                write_token(out, token);
+            }
+            StackValue::Tree { head, values } => {
+                out.push(1);
+                write_non_terminal(out, head);
+                write_len(out, values.len());
+                pending.extend(values.iter().rev());
+            }
+        }
+    }
+}
+
+fn write_token(out: &mut Vec<u8>, token: &Token) {
+    write_terminal(out, &token.terminal);
+    write_len(out, token.span.start);
+    write_len(out, token.span.end);
+    for trivia in [&token.leading_trivia, &token.trailing_trivia] {
+        write_len(out, trivia.len());
+        for piece in trivia {
+            let (tag, text) = match piece {
+                Trivia::Whitespace(text) => (0, text),
+                Trivia::Comment(text) => (1, text),
+            };
+            out.push(tag);
+            write_str(out, text);
+        }
+    }
+}
+
+fn non_terminal_bytes(non_terminal: &NonTerminal) -> Vec<u8> {
+    let mut bytes = vec![];
+    write_non_terminal(&mut bytes, non_terminal);
+    bytes
+}
+
+fn write_terminal(out: &mut Vec<u8>, terminal: &Terminal) {
+    let (tag, payload) = match terminal {
+        Terminal::Plus => (0, None),
+        Terminal::Minus => (1, None),
+        Terminal::Star => (2, None),
+        Terminal::LeftParen => (3, None),
+        Terminal::RightParen => (4, None),
+        Terminal::LeftBracket => (5, None),
+        Terminal::RightBracket => (6, None),
+        Terminal::Comma => (7, None),
+        Terminal::Zero => (8, None),
+        Terminal::Equal => (9, None),
+        Terminal::DoubleEqual => (10, None),
+        Terminal::Less => (11, None),
+        Terminal::LessEqual => (12, None),
+        Terminal::Greater => (13, None),
+        Terminal::GreaterEqual => (14, None),
+        Terminal::Arrow => (15, None),
+        Terminal::StringLiteral(content) => (16, Some(content)),
+        Terminal::Identifier(name) => (17, Some(name)),
+        Terminal::Whitespace(text) => (18, Some(text)),
+        Terminal::Error(text) => (24, Some(text)),
+        Terminal::Semicolon => (25, None),
+        Terminal::Char(class, c) => {
+            let class = match class {
+                CharClass::Letter => 0,
+                CharClass::Digit => 1,
+                CharClass::Whitespace => 2,
+                CharClass::Other => 3,
+            };
+            out.extend([19, class]);
+            write_str(out, &c.map(String::from).unwrap_or_default());
+            return;
+        }
+        Terminal::Byte { low, high, value } => {
+            out.extend([20, *low, *high]);
+            match value {
+                Some(value) => out.extend([1, *value]),
+                None => out.push(0),
+            }
+            return;
+        }
+        Terminal::Int(value) => {
+            out.push(22);
+            write_option_bytes(out, value.map(i64::to_le_bytes));
+            return;
+        }
+        Terminal::Float(value) => {
+            out.push(23);
+            write_option_bytes(out, value.map(|FloatLiteral(value)| value.to_le_bytes()));
+            return;
+        }
+        Terminal::Class(name, terminal) => {
+            out.push(21);
+            write_str(out, name);
+            match terminal {
+                Some(terminal) => {
+                    out.push(1);
+                    write_terminal(out, terminal);
+                }
+                None => out.push(0),
+            }
+            return;
+        }
+    };
+
+    out.push(tag);
+    if let Some(payload) = payload {
+        write_str(out, payload);
+    }
+}
+
+fn write_option_bytes(out: &mut Vec<u8>, bytes: Option<[u8; 8]>) {
+    match bytes {
+        Some(bytes) => {
+            out.push(1);
+            out.extend(bytes);
+        }
+        None => out.push(0),
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_len(out, s.len());
+    out.extend(s.as_bytes());
+}
+
+fn write_len(out: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let byte = (len & 0x7f) as u8;
+        len >>= 7;
+        if len == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let byte = *self
+            .bytes
+            .get(self.offset)
+            .ok_or(DecodeError::UnexpectedEnd)?;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    fn len(&mut self) -> Result<usize, DecodeError> {
+        let mut len = 0usize;
+        let mut shift = 0;
+
+        loop {
+            let offset = self.offset;
+            let byte = self.byte()?;
+            let bits = usize::from(byte & 0x7f);
+            if shift >= usize::BITS || (bits << shift) >> shift != bits {
+                return Err(DecodeError::InvalidLength { offset });
+            }
+            len |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(len);
+            }
+            shift += 7;
+        }
+    }
+
+    fn non_terminal(&mut self) -> Result<NonTerminal, DecodeError> {
+        let offset = self.offset;
+        Ok(match self.byte()? {
+            0 => NonTerminal::Sum,
+            1 => NonTerminal::Sub,
+            2 => NonTerminal::Mult,
+            3 => NonTerminal::Atom,
+            4 => NonTerminal::Number,
+            5 => NonTerminal::List,
+            6 => NonTerminal::Elements,
+            7 => NonTerminal::Expr,
+            8 => NonTerminal::Generated(self.len()?),
+            tag => return Err(DecodeError::InvalidTag { tag, offset }),
+        })
+    }
+
+    fn terminal(&mut self) -> Result<Terminal, DecodeError> {
+        let offset = self.offset;
+        Ok(match self.byte()? {
+            0 => Terminal::Plus,
+            1 => Terminal::Minus,
+            2 => Terminal::Star,
+            3 => Terminal::LeftParen,
+            4 => Terminal::RightParen,
+            5 => Terminal::LeftBracket,
+            6 => Terminal::RightBracket,
+            7 => Terminal::Comma,
+            8 => Terminal::Zero,
+            9 => Terminal::Equal,
+            10 => Terminal::DoubleEqual,
+            11 => Terminal::Less,
+            12 => Terminal::LessEqual,
+            13 => Terminal::Greater,
+            14 => Terminal::GreaterEqual,
+            15 => Terminal::Arrow,
+            16 => Terminal::StringLiteral(self.string()?),
+            17 => Terminal::Identifier(self.string()?),
+            18 => Terminal::Whitespace(self.string()?),
+            24 => Terminal::Error(self.string()?),
+            25 => Terminal::Semicolon,
+            19 => {
+                let offset = self.offset;
+                let class = match self.byte()? {
+                    0 => CharClass::Letter,
+                    1 => CharClass::Digit,
+                    2 => CharClass::Whitespace,
+                    3 => CharClass::Other,
+                    tag => return Err(DecodeError::InvalidTag { tag, offset }),
+                };
+                let offset = self.offset;
+                let text = self.string()?;
+                let mut chars = text.chars();
+                let c = chars.next();
+                if chars.next().is_some() {
+                    return Err(DecodeError::InvalidLength { offset });
+                }
+                Terminal::Char(class, c)
+            }
+            20 => {
+                let low = self.byte()?;
+                let high = self.byte()?;
+                let offset = self.offset;
+                let value = match self.byte()? {
+                    0 => None,
+                    1 => Some(self.byte()?),
+                    tag => return Err(DecodeError::InvalidTag { tag, offset }),
+                };
+                Terminal::Byte { low, high, value }
+            }
+            22 => Terminal::Int(self.option_bytes()?.map(i64::from_le_bytes)),
+            23 => Terminal::Float(
+                self.option_bytes()?
+                    .map(|bytes| FloatLiteral(f64::from_le_bytes(bytes))),
+            ),
+            21 => {
+                let name = self.string()?;
+                let offset = self.offset;
+                let terminal = match self.byte()? {
+                    0 => None,
+                    1 => Some(Box::new(self.terminal()?)),
+                    tag => return Err(DecodeError::InvalidTag { tag, offset }),
+                };
+                Terminal::Class(name, terminal)
+            }
+            tag => return Err(DecodeError::InvalidTag { tag, offset }),
+        })
+    }
+
+    fn stack_value(&mut self) -> Result<StackValue, DecodeError> {
+        // Trees still waiting for children, with how many they need.
+        let mut open: Vec<(NonTerminal, usize, Vec<StackValue>)> = vec![];
+
+        loop {
+            let offset = self.offset;
+            let mut value = match self.byte()? {
+                0 => StackValue::Terminal(self.token()?),
+                1 => {
+                    let head = self.non_terminal()?;
+                    let len = self.len()?;
+                    if len > 0 {
+                        open.push((head, len, vec![]));
+                        continue;
+                    }
+                    StackValue::Tree {
+                        head,
+                        values: vec![],
+                    }
+                }
+                tag => return Err(DecodeError::InvalidTag { tag, offset }),
+            };
+
+            // Close every tree this value completes.
+            loop {
+                let Some((_, len, values)) = open.last_mut() else {
+                    return Ok(value);
+                };
+                values.push(value);
+                if values.len() < *len {
+                    break;
+                }
+                let (head, _, values) = open.pop().expect("Just looked at it");
+                value = StackValue::Tree { head, values };
+            }
+        }
+    }
+
+    fn token(&mut self) -> Result<Token, DecodeError> {
+        let terminal = self.terminal()?;
+        let span = Span {
+            start: self.len()?,
+            end: self.len()?,
+        };
+        let mut trivia = [vec![], vec![]];
+        for pieces in &mut trivia {
+            for _ in 0..self.len()? {
+                let offset = self.offset;
+                pieces.push(match self.byte()? {
+                    0 => Trivia::Whitespace(self.string()?),
+                    1 => Trivia::Comment(self.string()?),
+                    tag => return Err(DecodeError::InvalidTag { tag, offset }),
+                });
+            }
+        }
+        let [leading_trivia, trailing_trivia] = trivia;
+
+        Ok(Token {
+            terminal,
+            span,
+            leading_trivia,
+            trailing_trivia,
+        })
+    }
+
+    fn option_bytes(&mut self) -> Result<Option<[u8; 8]>, DecodeError> {
+        let offset = self.offset;
+        match self.byte()? {
+            0 => Ok(None),
+            1 => {
+                let mut bytes = [0; 8];
+                for byte in &mut bytes {
+                    *byte = self.byte()?;
+                }
+                Ok(Some(bytes))
+            }
+            tag => Err(DecodeError::InvalidTag { tag, offset }),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        let len = self.len()?;
+        let offset = self.offset;
+        let end = offset.checked_add(len).ok_or(DecodeError::UnexpectedEnd)?;
+        let bytes = self
+            .bytes
+            .get(offset..end)
+            .ok_or(DecodeError::UnexpectedEnd)?;
+        self.offset = end;
+
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8 { offset })
+    }
+}
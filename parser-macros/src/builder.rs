@@ -0,0 +1,81 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Expression, Grammar, GrammarError, NonTerminal, Terminal};
+
+/// Builds a [`Grammar`] one production at a time, validating it at the end.
+///
+/// ```
+/// use parser_macros::{Expression, GrammarBuilder, NonTerminal, Terminal};
+///
+/// let grammar = GrammarBuilder::new(NonTerminal::Number)
+///     .production(NonTerminal::Number, vec![Expression::Terminal(Terminal::Zero)])
+///     .build()
+///     .expect("The grammar is valid");
+/// ```
+#[derive(Debug, Clone)]
+pub struct GrammarBuilder {
+    starting_symbol: NonTerminal,
+    rules: HashMap<NonTerminal, Vec<Vec<Expression>>>,
+    reduce_lookahead: HashMap<(NonTerminal, usize), HashSet<Terminal>>,
+    labels: HashMap<(NonTerminal, usize), String>,
+}
+
+impl GrammarBuilder {
+    pub fn new(starting_symbol: NonTerminal) -> Self {
+        GrammarBuilder {
+            starting_symbol,
+            rules: HashMap::new(),
+            reduce_lookahead: HashMap::new(),
+            labels: HashMap::new(),
+        }
+    }
+
+    /// Adds `non_terminal -> rhs` after any alternatives it already has.
+    pub fn production(mut self, non_terminal: NonTerminal, rhs: Vec<Expression>) -> Self {
+        self.rules.entry(non_terminal).or_default().push(rhs);
+        self
+    }
+
+    /// Adds `non_terminal -> rhs`, only reducing it when followed by one
+    /// of `lookahead`. See [`Grammar::reduce_lookahead`].
+    pub fn production_before(
+        mut self,
+        non_terminal: NonTerminal,
+        rhs: Vec<Expression>,
+        lookahead: HashSet<Terminal>,
+    ) -> Self {
+        let alternatives = self.rules.entry(non_terminal.clone()).or_default();
+        alternatives.push(rhs);
+        self.reduce_lookahead
+            .insert((non_terminal, alternatives.len() - 1), lookahead);
+        self
+    }
+
+    /// Adds `non_terminal -> rhs` under the name `label`. See
+    /// [`Grammar::labels`].
+    pub fn production_labelled(
+        mut self,
+        non_terminal: NonTerminal,
+        rhs: Vec<Expression>,
+        label: &str,
+    ) -> Self {
+        let alternatives = self.rules.entry(non_terminal.clone()).or_default();
+        alternatives.push(rhs);
+        self.labels
+            .insert((non_terminal, alternatives.len() - 1), label.to_string());
+        self
+    }
+
+    /// The grammar, if [`Grammar::validate`] accepts it.
+    pub fn build(self) -> Result<Grammar, GrammarError> {
+        let grammar = Grammar {
+            starting_symbol: self.starting_symbol,
+            rules: self.rules,
+            reduce_lookahead: self.reduce_lookahead,
+            labels: self.labels,
+        };
+
+        grammar.validate()?;
+        Ok(grammar)
+    }
+}
@@ -0,0 +1,109 @@
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+use crate::{Grammar, Lexer, ParseError, Parser, Terminal, Token, Tokenizer};
+
+impl Parser {
+    /// Lets rules name [`Terminal::class`] of `name` to match any terminal
+    /// `predicate` accepts, such as every operator, without listing them.
+    /// Lexed tokens that `predicate` accepts are wrapped in the class, so
+    /// the tree still holds the terminal that matched.
+    ///
+    /// The predicate lives on the parser, in its lexer, rather than in the
+    /// grammar as an `Expression::Predicate` would, so
+    /// [`Grammar`](crate::Grammar) stays plain data that can be compared,
+    /// cloned and encoded, and the reduction loop still only compares
+    /// terminals. The cost is that a wrapped token only matches its class:
+    /// terminals the grammar names itself are never wrapped, and one then
+    /// never matches the class. Which terminals those are follows
+    /// [`Parser::update_grammar`]. Classes added first take precedence.
+    pub fn add_terminal_class<F>(&mut self, name: &str, predicate: F)
+    where
+        F: Fn(&Terminal) -> bool + 'static,
+    {
+        let grammar = &self.grammar;
+        let literal = self
+            .class_literals
+            .get_or_insert_with(|| Rc::new(RefCell::new(literals(grammar))))
+            .clone();
+        let lexer = std::mem::replace(&mut self.lexer, Box::new(Tokenizer::new()));
+
+        self.lexer = Box::new(Classifying {
+            lexer,
+            name: name.to_string(),
+            predicate: Rc::new(predicate),
+            literal,
+        });
+    }
+}
+
+/// The terminals `grammar` names, by kind.
+pub(crate) fn literals(grammar: &Grammar) -> HashSet<Terminal> {
+    grammar.symbols_in_order().1.into_iter().collect()
+}
+
+/// Wraps the tokens of another lexer that a class's predicate accepts.
+struct Classifying {
+    lexer: Box<dyn Lexer>,
+    name: String,
+    predicate: Rc<dyn Fn(&Terminal) -> bool>,
+    /// Terminals the grammar names, by kind, which are left alone.
+    literal: Rc<RefCell<HashSet<Terminal>>>,
+}
+
+impl Classifying {
+    fn classify(&self, terminal: Terminal) -> Terminal {
+        let matches = !matches!(terminal, Terminal::Class(..))
+            && !self.literal.borrow().contains(&terminal.kind())
+            && (self.predicate)(&terminal);
+
+        if matches {
+            Terminal::Class(self.name.clone(), Some(Box::new(terminal)))
+        } else {
+            terminal
+        }
+    }
+
+    fn classify_token(&self, token: Token) -> Token {
+        Token {
+            terminal: self.classify(token.terminal.clone()),
+            ..token
+        }
+    }
+}
+
+impl Lexer for Classifying {
+    /// Those of the wrapped lexer, which the predicate may leave alone,
+    /// then the class.
+    fn terminal_kinds(&self) -> Option<Vec<Terminal>> {
+        let mut kinds = self.lexer.terminal_kinds()?;
+        kinds.push(Terminal::class(&self.name));
+
+        Some(kinds)
+    }
+
+    fn tokens(&self, input: &str) -> Result<Vec<Terminal>, ParseError> {
+        Ok(self
+            .lexer
+            .tokens(input)?
+            .into_iter()
+            .map(|terminal| self.classify(terminal))
+            .collect())
+    }
+
+    fn lex(&self, input: &str) -> Result<Vec<Token>, ParseError> {
+        Ok(self
+            .lexer
+            .lex(input)?
+            .into_iter()
+            .map(|token| self.classify_token(token))
+            .collect())
+    }
+
+    fn tokens_recovering(&self, input: &str) -> Vec<Result<Token, ParseError>> {
+        self.lexer
+            .tokens_recovering(input)
+            .into_iter()
+            .map(|token| token.map(|token| self.classify_token(token)))
+            .collect()
+    }
+}
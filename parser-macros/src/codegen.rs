@@ -0,0 +1,234 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+use crate::{
+    Expression, Grammar, GrammarError, LlConflict, NonTerminal, Terminal,
+    analysis::first_of_sequence,
+};
+
+/// The part of the generated source that is the same for every grammar:
+/// the tree type and the token cursor the rule functions share.
+const PRELUDE: &str = r#"#[derive(Debug, Clone, PartialEq)]
+pub enum Tree {
+    Token(String),
+    Node(&'static str, Vec<Tree>),
+}
+
+struct Parser<'a> {
+    tokens: &'a [&'a str],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn expect(&mut self, token: &str) -> Result<Tree, String> {
+        if self.peek() == Some(token) {
+            self.position += 1;
+            Ok(Tree::Token(token.to_string()))
+        } else {
+            Err(self.unexpected(&[token]))
+        }
+    }
+
+    fn unexpected(&self, expected: &[&str]) -> String {
+        match self.peek() {
+            Some(token) => format!(
+                "Unexpected token {token:?} at {}, expected one of {expected:?}",
+                self.position
+            ),
+            None => format!("Unexpected end of input, expected one of {expected:?}"),
+        }
+    }
+"#;
+
+impl Grammar {
+    /// Rust source for a recursive-descent parser of the grammar, to paste
+    /// into a project that should not depend on this crate. It has a
+    /// `parse(tokens: &[&str]) -> Result<Tree, String>` entry point and one
+    /// `parse_<nonterminal>` method per nonterminal with rules, in
+    /// [`Grammar::symbols_in_order`] order, each choosing its alternative
+    /// by the next token alone. Tokens are named as terminal kinds display,
+    /// so `"0"`, `"+"` or `"identifier"`, and the tree keeps them by those
+    /// names too.
+    ///
+    /// That only works for an LL(1) grammar, so a nonterminal used without
+    /// rules fails with [`GrammarError::Undefined`], and any lookahead that
+    /// more than one alternative can start with, as in a left-recursive
+    /// rule, with [`GrammarError::NotLl1`] listing every such conflict.
+    pub fn generate_rust(&self) -> Result<String, GrammarError> {
+        let (order, _) = self.symbols_in_order();
+        if let Some(undefined) = order.iter().find(|nt| !self.rules.contains_key(nt)) {
+            return Err(GrammarError::Undefined(undefined.clone()));
+        }
+
+        let predictions = self.predictions();
+        let conflicts: Vec<LlConflict> = order
+            .iter()
+            .flat_map(|nt| conflicts(nt, &predictions[nt]))
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(GrammarError::NotLl1(conflicts));
+        }
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "// Recursive-descent parser for `{}`, generated by `Grammar::generate_rust`.\n",
+            self.starting_symbol
+        )
+        .expect("Writing to a String cannot fail");
+        write!(
+            out,
+            r#"pub fn parse(tokens: &[&str]) -> Result<Tree, String> {{
+    let mut parser = Parser {{ tokens, position: 0 }};
+    let tree = parser.parse_{}()?;
+    match parser.peek() {{
+        None => Ok(tree),
+        Some(token) => Err(format!(
+            "Unexpected token {{token:?}} at {{}}, expected the end of input",
+            parser.position
+        )),
+    }}
+}}
+
+{PRELUDE}"#,
+            self.starting_symbol
+        )
+        .expect("Writing to a String cannot fail");
+
+        for nt in &order {
+            self.generate_function(&mut out, nt, &predictions[nt]);
+        }
+        writeln!(out, "}}").expect("Writing to a String cannot fail");
+
+        Ok(out)
+    }
+
+    /// For each nonterminal, the lookaheads (`None` at the end of input)
+    /// each of its alternatives can start with: the FIRST set of the
+    /// alternative, and its FOLLOW set too if the alternative is nullable.
+    fn predictions(&self) -> HashMap<NonTerminal, Vec<HashSet<Option<Terminal>>>> {
+        let nullable = self.nullable_nonterminals();
+        let first = self.first_sets();
+        let follow = self.follow_sets();
+
+        self.rules
+            .iter()
+            .map(|(nt, alternatives)| {
+                let sets = alternatives
+                    .iter()
+                    .map(|production| {
+                        let (terminals, nullable) =
+                            first_of_sequence(production, &first, &nullable);
+                        let mut set: HashSet<Option<Terminal>> =
+                            terminals.into_iter().map(Some).collect();
+                        if nullable && let Some(follow) = follow.get(nt) {
+                            set.extend(follow.iter().cloned());
+                        }
+                        set
+                    })
+                    .collect();
+                (nt.clone(), sets)
+            })
+            .collect()
+    }
+
+    fn generate_function(
+        &self,
+        out: &mut String,
+        nt: &NonTerminal,
+        predictions: &[HashSet<Option<Terminal>>],
+    ) {
+        let mut expected: Vec<String> = predictions
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|t| format!("{:?}", t.to_string()))
+            .collect();
+        expected.sort();
+        expected.dedup();
+
+        writeln!(
+            out,
+            "
+    fn parse_{nt}(&mut self) -> Result<Tree, String> {{
+        match self.peek() {{"
+        )
+        .expect("Writing to a String cannot fail");
+
+        for (production, lookaheads) in self.rules[nt].iter().zip(predictions) {
+            // An alternative nothing can start is never taken.
+            if lookaheads.is_empty() {
+                continue;
+            }
+
+            let mut patterns: Vec<String> = lookaheads
+                .iter()
+                .map(|lookahead| match lookahead {
+                    Some(t) => format!("Some({:?})", t.to_string()),
+                    None => "None".to_string(),
+                })
+                .collect();
+            patterns.sort();
+
+            let children: Vec<String> = production
+                .iter()
+                .map(|expr| match expr {
+                    Expression::Terminal(t) => format!("self.expect({:?})?", t.kind().to_string()),
+                    Expression::NonTerminal(nt) => format!("self.parse_{nt}()?"),
+                })
+                .collect();
+
+            writeln!(
+                out,
+                "            {} => Ok(Tree::Node({:?}, vec![{}])),",
+                patterns.join(" | "),
+                nt.to_string(),
+                children.join(", ")
+            )
+            .expect("Writing to a String cannot fail");
+        }
+
+        writeln!(
+            out,
+            "            _ => Err(self.unexpected(&[{}])),
+        }}
+    }}",
+            expected.join(", ")
+        )
+        .expect("Writing to a String cannot fail");
+    }
+}
+
+/// The lookaheads of `nt` that more than one alternative can start with,
+/// sorted by name with the end of input last.
+fn conflicts(nt: &NonTerminal, predictions: &[HashSet<Option<Terminal>>]) -> Vec<LlConflict> {
+    let mut alternatives: HashMap<&Option<Terminal>, Vec<usize>> = HashMap::new();
+    for (index, lookaheads) in predictions.iter().enumerate() {
+        for lookahead in lookaheads {
+            alternatives.entry(lookahead).or_default().push(index);
+        }
+    }
+
+    let mut conflicts: Vec<LlConflict> = alternatives
+        .into_iter()
+        .filter(|(_, alternatives)| alternatives.len() > 1)
+        .map(|(lookahead, alternatives)| LlConflict {
+            non_terminal: nt.clone(),
+            lookahead: lookahead.clone(),
+            alternatives,
+        })
+        .collect();
+    conflicts.sort_by_cached_key(|conflict| {
+        (
+            conflict.lookahead.is_none(),
+            conflict.lookahead.as_ref().map(Terminal::to_string),
+        )
+    });
+    conflicts
+}
@@ -0,0 +1,34 @@
+use std::collections::HashSet;
+
+use crate::{ParseError, Parser, Skeleton, Terminal, Token};
+
+impl Parser {
+    /// Terminals that could legally come after `prefix`, for autocomplete.
+    ///
+    /// The prefix is parsed as far as it goes and the terminals any of the
+    /// possible reductions would let through are collected. For an empty
+    /// prefix these are the terminals that can begin the starting symbol.
+    /// Fails with the parse error if the prefix itself is not valid.
+    pub fn completions(&self, prefix: &str) -> Result<HashSet<Terminal>, ParseError> {
+        let tables = self.tables();
+        let tokens = self.lexer.lex(prefix)?;
+
+        let state = self.shift_all(tables, &mut Skeleton, tokens.into_iter(), None)?;
+
+        Ok(state.expected(tables).into_iter().collect())
+    }
+    /// Whether `tokens` could still be extended into a sentence of the
+    /// grammar, for streaming input that should be rejected as soon as it
+    /// can no longer be completed. Every complete sentence is a viable
+    /// prefix, and so is no input at all.
+    ///
+    /// The tokens are run through the LR automaton without building a
+    /// tree. They are viable exactly when each one can be shifted, since
+    /// every state the automaton reaches lies on the way to a sentence.
+    pub fn is_viable_prefix(&self, tokens: &[Terminal]) -> bool {
+        let tables = self.tables();
+        let tokens = tokens.iter().cloned().map(Token::from);
+
+        self.shift_all(tables, &mut Skeleton, tokens, None).is_ok()
+    }
+}
@@ -0,0 +1,54 @@
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+
+use crate::{NonTerminal, Parser};
+
+impl Parser {
+    /// Starts counting how often each production reduces, across every
+    /// later parse through [`Parser::parse`] and the other entry points
+    /// that build a [`StackValue`](crate::StackValue) tree. Parses that
+    /// fail before the end of their input do not count. Enabling it again
+    /// keeps the counts so far.
+    pub fn enable_coverage(&mut self) {
+        if self.coverage.is_some() {
+            return;
+        }
+
+        let counts = self
+            .grammar
+            .rules
+            .iter()
+            .flat_map(|(nt, alternatives)| {
+                (0..alternatives.len()).map(move |alternative| ((nt.clone(), alternative), 0))
+            })
+            .collect();
+
+        self.coverage = Some(Rc::new(RefCell::new(counts)));
+        self.dispatched = OnceCell::new();
+    }
+
+    /// How often each production has reduced, keyed like
+    /// [`Grammar::reduce_lookahead`](crate::Grammar::reduce_lookahead) by
+    /// nonterminal and alternative index. Every production is listed, so
+    /// the ones at 0 never fired. Empty unless
+    /// [`Parser::enable_coverage`] was called.
+    pub fn production_coverage(&self) -> HashMap<(NonTerminal, usize), u64> {
+        self.coverage
+            .as_ref()
+            .map(|coverage| coverage.borrow().clone())
+            .unwrap_or_default()
+    }
+
+    /// Sets every count back to 0, for a new run over a corpus.
+    pub fn reset_coverage(&self) {
+        if let Some(coverage) = &self.coverage {
+            coverage
+                .borrow_mut()
+                .values_mut()
+                .for_each(|count| *count = 0);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use crate::{Expression, NonTerminal, ParseError, Parser, StackValue, SymbolRegistry, Token};
+
+/// How a chart entry was first derived.
+#[derive(Clone)]
+enum Back {
+    /// From the single token the entry spans.
+    Leaf,
+    /// From a pair of nonterminals, the first spanning this many tokens.
+    Split(usize, NonTerminal, NonTerminal),
+}
+
+impl Parser {
+    /// Parses `input` with the CYK algorithm over the grammar's
+    /// [Chomsky normal form](crate::Grammar::as_chomsky_normal_form). This
+    /// takes O(n³) time in the number of tokens but accepts any context
+    /// free grammar, ambiguous ones included, so it suits small inputs the
+    /// shift-reduce engine cannot handle. Of several parses the one using
+    /// the earliest productions is returned.
+    ///
+    /// Trees are given in the grammar's own nonterminals, generated ones
+    /// included: only the nodes the normal form introduced are spliced
+    /// into their parents. The normal
+    /// form has no unit or empty productions, though, so a chain like
+    /// `sum -> sub -> mult` shows up as a single `sum` node and nullable
+    /// nonterminals that matched nothing are missing. Fails with
+    /// [`ParseError::Rejected`] if the input is not a sentence, and with
+    /// [`ParseError::TooAmbiguous`] if it, or any part of it, has more
+    /// parses than [`Parser::set_max_parallel_parses`] allows. The limit is
+    /// checked as the chart is filled, so a part of the input that is too
+    /// ambiguous fails the parse without the rest of the chart being built.
+    pub fn parse_cyk(&self, input: &str) -> Result<StackValue, ParseError> {
+        let tokens: Vec<Token> = self
+            .lexer
+            .lex(input)?
+            .into_iter()
+            .filter(|token| !self.skips(&token.terminal))
+            .collect();
+
+        let (grammar, introduced) = self.cnf.get_or_init(|| {
+            let mut registry = SymbolRegistry::new();
+            registry.reserve(&self.grammar);
+            let reserved = registry.clone();
+
+            let grammar = self.grammar.as_chomsky_normal_form_in(&mut registry);
+            (grammar, registry.since(&reserved))
+        });
+        let start = &grammar.starting_symbol;
+        let productions: Vec<(&NonTerminal, &Vec<Expression>)> = grammar
+            .symbols_in_order()
+            .0
+            .iter()
+            .filter_map(|nt| grammar.rules.get_key_value(nt))
+            .flat_map(|(nt, alternatives)| alternatives.iter().map(move |p| (nt, p)))
+            .collect();
+
+        let n = tokens.len();
+        if n == 0 {
+            return if productions
+                .iter()
+                .any(|(nt, production)| *nt == start && production.is_empty())
+            {
+                Ok(StackValue::Tree {
+                    head: self.grammar.starting_symbol.clone(),
+                    values: vec![],
+                })
+            } else {
+                Err(ParseError::Rejected)
+            };
+        }
+
+        // `chart[i][len - 1]` holds the nonterminals deriving the `len`
+        // tokens from `i`, with how they were first derived and in how many
+        // ways. An entry with more ways than the limit allows fails the
+        // parse as soon as it is counted, rather than once the chart is full.
+        let limit = self.max_parallel_parses;
+        let mut chart: Vec<Vec<HashMap<&NonTerminal, (Back, usize)>>> =
+            (0..n).map(|i| vec![HashMap::new(); n - i]).collect();
+
+        for (i, token) in tokens.iter().enumerate() {
+            for (nt, production) in &productions {
+                if let [Expression::Terminal(t)] = production.as_slice()
+                    && t.kind() == token.terminal.kind()
+                {
+                    let entry = chart[i][0].entry(*nt).or_insert((Back::Leaf, 0));
+                    entry.1 += 1;
+                    if entry.1 > limit {
+                        return Err(ParseError::TooAmbiguous { limit });
+                    }
+                }
+            }
+        }
+
+        for len in 2..=n {
+            for i in 0..=n - len {
+                for split in 1..len {
+                    for (nt, production) in &productions {
+                        if let [
+                            Expression::NonTerminal(left),
+                            Expression::NonTerminal(right),
+                        ] = production.as_slice()
+                            && let Some((_, before)) = chart[i][split - 1].get(left)
+                            && let Some((_, after)) = chart[i + split][len - split - 1].get(right)
+                        {
+                            let ways = before.saturating_mul(*after);
+                            let entry = chart[i][len - 1].entry(*nt).or_insert_with(|| {
+                                (Back::Split(split, left.clone(), right.clone()), 0)
+                            });
+                            entry.1 = entry.1.saturating_add(ways);
+                            if entry.1 > limit {
+                                return Err(ParseError::TooAmbiguous { limit });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if !chart[0][n - 1].contains_key(start) {
+            return Err(ParseError::Rejected);
+        }
+
+        // Post-order walk over the derivation. Each node builds the values
+        // it contributes to its parent: itself as a tree, or its children
+        // if it is a node the normal form introduced.
+        let mut pending = vec![(0, n, start.clone(), false)];
+        let mut built: Vec<Vec<StackValue>> = vec![];
+
+        while let Some((i, len, nt, expanded)) = pending.pop() {
+            let values = match &chart[i][len - 1][&nt].0 {
+                Back::Leaf => vec![StackValue::Terminal(tokens[i].clone())],
+                Back::Split(split, left, right) if !expanded => {
+                    pending.push((i, len, nt.clone(), true));
+                    pending.push((i + split, len - split, right.clone(), false));
+                    pending.push((i, *split, left.clone(), false));
+                    continue;
+                }
+                Back::Split(..) => {
+                    let right = built.pop().expect("Right child was built");
+                    let mut left = built.pop().expect("Left child was built");
+                    left.extend(right);
+                    left
+                }
+            };
+
+            built.push(if introduced.contains(&nt) {
+                values
+            } else {
+                vec![StackValue::Tree { head: nt, values }]
+            });
+        }
+
+        Ok(StackValue::Tree {
+            head: self.grammar.starting_symbol.clone(),
+            values: built.pop().expect("The root is always built"),
+        })
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{NonTerminal, ParseError, ParseState, Parser, StackValue, Terminal, Token};
+
+/// What one call to [`Parser::debug_step`] did.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepResult {
+    /// The next token was pushed onto the stack.
+    Shifted(Terminal),
+    /// The top of the stack was rewritten to `nt` by its alternative
+    /// numbered `production`, as [`Grammar::production_label`](crate::Grammar::production_label)
+    /// indexes them.
+    Reduced { nt: NonTerminal, production: usize },
+    /// The input is parsed and this is its tree.
+    Accepted(StackValue),
+    /// The input does not parse.
+    Error(ParseError),
+}
+
+/// A parse in progress, driven one action at a time by
+/// [`Parser::debug_step`]. It must only be stepped by the parser that
+/// started it.
+pub struct DebugState {
+    pub(crate) tokens: Vec<Token>,
+    pub(crate) index: usize,
+    pub(crate) state: ParseState,
+    pub(crate) finished: Option<StepResult>,
+}
+
+impl DebugState {
+    /// The values on the stack, bottom first.
+    pub fn stack(&self) -> &[StackValue] {
+        &self.state.stack
+    }
+
+    /// How many tokens have been read, skipped ones included.
+    pub fn position(&self) -> usize {
+        self.index
+    }
+
+    /// The next token to shift, or `None` at the end of input.
+    pub fn next_token(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    /// Whether the parse has accepted or failed, after which every step
+    /// gives that result again.
+    pub fn is_finished(&self) -> bool {
+        self.finished.is_some()
+    }
+
+    /// Appends tokens to the input still to be parsed, so a parse can be
+    /// fed as its input arrives. Has no effect once the parse is finished.
+    pub fn extend<I: IntoIterator<Item = Token>>(&mut self, tokens: I) {
+        if self.finished.is_none() {
+            self.tokens.extend(tokens);
+        }
+    }
+}
+
+impl Parser {
+    /// Starts parsing `input` one action at a time, for visualizers and
+    /// debugging. Each [`Parser::debug_step`] then does what one line of
+    /// the parse trace describes. Input that does not tokenize gives a
+    /// state whose first step is the error.
+    pub fn debug_start(&self, input: &str) -> DebugState {
+        let (tokens, finished) = match self.lexer.lex(input) {
+            Ok(tokens) => (tokens, None),
+            Err(error) => (vec![], Some(StepResult::Error(error))),
+        };
+
+        DebugState {
+            tokens,
+            index: 0,
+            state: ParseState::new(),
+            finished,
+        }
+    }
+
+    /// Takes the next action of the parse: a reduction if the next token
+    /// (or the end of input) allows one, otherwise a shift, and at the end
+    /// of input acceptance or an error. The result is the same as
+    /// [`Parser::parse`] gives once the steps run out.
+    pub fn debug_step(&self, debug: &mut DebugState) -> StepResult {
+        if let Some(finished) = &debug.finished {
+            return finished.clone();
+        }
+
+        let tables = self.tables();
+
+        while debug
+            .tokens
+            .get(debug.index)
+            .is_some_and(|token| self.skips(&token.terminal))
+        {
+            debug.index += 1;
+        }
+
+        let lookahead = debug.tokens.get(debug.index).map(|token| &token.terminal);
+        if let Some((len, nt, item)) = debug.state.find_reduction(tables, lookahead) {
+            if let Some(limit) = self.max_nesting_depth
+                && let Err(error) = debug.state.check_nesting(len, limit)
+            {
+                let result = StepResult::Error(error);
+                debug.finished = Some(result.clone());
+                return result;
+            }
+
+            debug
+                .state
+                .rewrite(tables, &mut self.owned(), len, nt.clone());
+            return StepResult::Reduced {
+                nt,
+                production: tables.items[item].1,
+            };
+        }
+
+        let result = match debug.tokens.get(debug.index) {
+            Some(token) => {
+                let terminal = token.terminal.clone();
+                match debug
+                    .state
+                    .shift(tables, &mut self.owned(), token.clone(), debug.index)
+                {
+                    Ok(()) => {
+                        debug.index += 1;
+                        return StepResult::Shifted(terminal);
+                    }
+                    Err(error) => StepResult::Error(error),
+                }
+            }
+            None if debug.state.stack.is_empty() => StepResult::Error(ParseError::UnexpectedEnd {
+                expected: debug.state.expected(tables),
+            }),
+            None => match debug.state.clone().accept(&self.grammar.starting_symbol) {
+                Ok(tree) => StepResult::Accepted(tree),
+                Err(stack) => StepResult::Error(ParseError::BadStack { stack, next: None }),
+            },
+        };
+
+        debug.finished = Some(result.clone());
+        result
+    }
+}
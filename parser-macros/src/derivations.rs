@@ -0,0 +1,356 @@
+//! Counting the parse trees a grammar gives an input, as a measure of its
+//! ambiguity.
+//!
+//! The crate has no Earley or GLR backend, so there is no parse forest to
+//! count the trees of. The CYK chart of [`Parser::parse_cyk`] would be
+//! one, but it is built over the Chomsky normal form, which drops unit and
+//! empty productions: trees that differ only in those look the same there,
+//! and a cycle of unit productions, which gives a sentence infinitely many
+//! trees, disappears. Counts are instead memoized per nonterminal and span
+//! of tokens over the grammar itself. Each memoized cell stands for the
+//! trees of one nonterminal over one span, shared by every larger tree
+//! that uses it, much as a node of a packed forest would.
+//!
+//! [`Parser::parse_cyk`]: crate::Parser::parse_cyk
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::{Expression, Grammar, NonTerminal, StackValue, Terminal, TokenizeError, Tokenizer};
+
+/// The longest sentence [`Grammar::ambiguity_report`] looks for a witness
+/// in.
+const AMBIGUITY_SEARCH_LEN: usize = 8;
+
+impl Grammar {
+    /// How many distinct parse trees `input` has, counting at most `max`,
+    /// as a measure of how ambiguous the grammar is. An unambiguous
+    /// grammar gives 1 for every sentence, and every grammar gives 0 for
+    /// input that is not one. `input` is split by [`Tokenizer`].
+    ///
+    /// Derivations are counted over the grammar itself, not a normal form,
+    /// by memoizing the count for each nonterminal and span of tokens.
+    /// Where a nonterminal derives itself over the same span, through unit
+    /// productions or nullable neighbours, it has infinitely many trees
+    /// there, and the count is `max`.
+    pub fn count_derivations(&self, input: &str, max: usize) -> Result<usize, TokenizeError> {
+        let tokens = kinds(input)?;
+        let mut counter = Counter::new(self, &tokens, max);
+
+        Ok(counter.count(&self.starting_symbol, 0, tokens.len()).0)
+    }
+
+    /// Whether `input` has more than one parse tree, by the same count as
+    /// [`Grammar::count_derivations`] stopped at two. Input that is no
+    /// sentence is not ambiguous.
+    pub fn is_ambiguous(&self, input: &str) -> Result<bool, TokenizeError> {
+        Ok(self.count_derivations(input, 2)? > 1)
+    }
+
+    /// Whether the starting symbol derives `tokens`, compared by
+    /// [`Terminal::kind`].
+    pub(crate) fn derives(&self, tokens: &[Terminal]) -> bool {
+        let tokens: Vec<Terminal> = tokens.iter().map(Terminal::kind).collect();
+        let mut counter = Counter::new(self, &tokens, 1);
+
+        counter.count(&self.starting_symbol, 0, tokens.len()).0 > 0
+    }
+
+    /// Every count [`Grammar::count_derivations`] could need for `input`:
+    /// how many trees, up to `max`, each nonterminal with rules has over
+    /// each span of its tokens. Filling every cell costs more than one
+    /// count, but lets many questions about the same input share the work.
+    pub fn derivation_chart(
+        &self,
+        input: &str,
+        max: usize,
+    ) -> Result<DerivationChart, TokenizeError> {
+        let tokens = kinds(input)?;
+        let mut counter = Counter::new(self, &tokens, max);
+
+        let mut cells = HashMap::new();
+        for nt in self.rules.keys() {
+            for start in 0..=tokens.len() {
+                for end in start..=tokens.len() {
+                    let (count, _) = counter.count(nt, start, end);
+                    cells.insert((nt.clone(), start, end), count);
+                }
+            }
+        }
+
+        Ok(DerivationChart { tokens, cells })
+    }
+
+    /// Explains why the grammar is ambiguous, with the shortest sentence
+    /// that has two parse trees and both trees as s-expressions. `None` if
+    /// every sentence of up to eight terminals has at most one tree, which
+    /// does not prove the grammar unambiguous.
+    pub fn ambiguity_report(&self) -> Option<String> {
+        let mut sentences: Vec<Vec<Terminal>> = self
+            .sentences_up_to(AMBIGUITY_SEARCH_LEN)
+            .into_iter()
+            .collect();
+        sentences.sort_by_cached_key(|sentence| {
+            (
+                sentence.len(),
+                sentence
+                    .iter()
+                    .map(Terminal::to_string)
+                    .collect::<Vec<String>>(),
+            )
+        });
+
+        sentences.iter().find_map(|sentence| {
+            let tokens: Vec<Terminal> = sentence.iter().map(Terminal::kind).collect();
+            let mut counter = Counter::new(self, &tokens, 2);
+            if counter.count(&self.starting_symbol, 0, tokens.len()).0 < 2 {
+                return None;
+            }
+
+            let trees = counter.trees(&self.starting_symbol, 0, tokens.len(), &mut vec![]);
+            let [first, second, ..] = trees.as_slice() else {
+                return None;
+            };
+
+            let words: Vec<String> = sentence.iter().map(Terminal::to_string).collect();
+            Some(format!(
+                "The grammar is ambiguous: `{}` parses as a {} in two ways.\n  first:  {first}\n  second: {second}\n",
+                words.join(" "),
+                self.starting_symbol,
+            ))
+        })
+    }
+}
+
+/// The tokens of `input`, by [`Terminal::kind`].
+fn kinds(input: &str) -> Result<Vec<Terminal>, TokenizeError> {
+    Ok(Tokenizer::new()
+        .tokenize(input)?
+        .into_iter()
+        .map(|token| token.terminal.kind())
+        .collect())
+}
+
+/// Derivation counts for every nonterminal over every span of one input,
+/// from [`Grammar::derivation_chart`]. Spans index the input's tokens, so
+/// `0..tokens().len()` is all of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivationChart {
+    tokens: Vec<Terminal>,
+    cells: HashMap<(NonTerminal, usize, usize), usize>,
+}
+
+impl DerivationChart {
+    /// The tokens the counts are over, by [`Terminal::kind`].
+    pub fn tokens(&self) -> &[Terminal] {
+        &self.tokens
+    }
+
+    /// How many trees `non_terminal` has over the tokens from `start` to
+    /// `end`, up to the chart's maximum. Nonterminals without rules and
+    /// spans past the end have none.
+    pub fn count(&self, non_terminal: &NonTerminal, start: usize, end: usize) -> usize {
+        self.cells
+            .get(&(non_terminal.clone(), start, end))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// A nonterminal and the span of tokens it derives.
+type Cell<'a> = (&'a NonTerminal, usize, usize);
+
+/// A count, with the depths in [`Counter::in_progress`] of the spans it
+/// reached again while they were still being counted. Such a count is only
+/// a lower bound until those spans are done.
+type Partial = (usize, BTreeSet<usize>);
+
+struct Counter<'a> {
+    grammar: &'a Grammar,
+    tokens: &'a [Terminal],
+    nullable: HashSet<NonTerminal>,
+    max: usize,
+    memo: HashMap<Cell<'a>, usize>,
+    in_progress: Vec<Cell<'a>>,
+}
+
+impl<'a> Counter<'a> {
+    fn new(grammar: &'a Grammar, tokens: &'a [Terminal], max: usize) -> Self {
+        Counter {
+            grammar,
+            tokens,
+            nullable: grammar.nullable_nonterminals(),
+            max,
+            memo: HashMap::new(),
+            in_progress: vec![],
+        }
+    }
+
+    fn count(&mut self, nt: &'a NonTerminal, start: usize, end: usize) -> Partial {
+        let span = (nt, start, end);
+        if let Some(&count) = self.memo.get(&span) {
+            return (count, BTreeSet::new());
+        }
+        if let Some(depth) = self.in_progress.iter().position(|other| *other == span) {
+            return (0, BTreeSet::from([depth]));
+        }
+
+        let depth = self.in_progress.len();
+        self.in_progress.push(span);
+
+        let mut total: usize = 0;
+        let mut reentered = BTreeSet::new();
+        let grammar = self.grammar;
+        for production in grammar.rules.get(nt).into_iter().flatten() {
+            let (count, spans) = self.sequence(production, start, end);
+            total = total.saturating_add(count).min(self.max);
+            reentered.extend(spans);
+        }
+
+        self.in_progress.pop();
+
+        // Every other factor on the way back here derives something, so
+        // once this span has one tree it has infinitely many.
+        if reentered.remove(&depth) && total > 0 {
+            total = self.max;
+        }
+        if reentered.is_empty() {
+            self.memo.insert(span, total);
+        }
+
+        (total, reentered)
+    }
+
+    /// Up to `max` distinct trees for `nt` over the tokens from `start` to
+    /// `end`. `path` holds the spans being built around this one, and each
+    /// may repeat once on it, so a cycle is shown going round at most once.
+    fn trees(
+        &mut self,
+        nt: &'a NonTerminal,
+        start: usize,
+        end: usize,
+        path: &mut Vec<Cell<'a>>,
+    ) -> Vec<StackValue> {
+        let span = (nt, start, end);
+        if path.iter().filter(|other| **other == span).count() >= 2 {
+            return vec![];
+        }
+
+        path.push(span);
+        let mut trees = vec![];
+        let grammar = self.grammar;
+        for production in grammar.rules.get(nt).into_iter().flatten() {
+            for values in self.sequences(production, start, end, path) {
+                if trees.len() < self.max {
+                    trees.push(StackValue::Tree {
+                        head: nt.clone(),
+                        values,
+                    });
+                }
+            }
+        }
+        path.pop();
+
+        trees
+    }
+
+    /// Up to `max` distinct ways for `symbols` to derive the tokens from
+    /// `start` to `end`, as the trees of each symbol.
+    fn sequences(
+        &mut self,
+        symbols: &'a [Expression],
+        start: usize,
+        end: usize,
+        path: &mut Vec<Cell<'a>>,
+    ) -> Vec<Vec<StackValue>> {
+        let Some((first, rest)) = symbols.split_first() else {
+            return if start == end { vec![vec![]] } else { vec![] };
+        };
+
+        match first {
+            Expression::Terminal(t) => {
+                if start < end && self.tokens[start] == t.kind() {
+                    let leaf = StackValue::Terminal(self.tokens[start].clone().into());
+                    let mut sequences = self.sequences(rest, start + 1, end, path);
+                    for values in &mut sequences {
+                        values.insert(0, leaf.clone());
+                    }
+                    sequences
+                } else {
+                    vec![]
+                }
+            }
+            Expression::NonTerminal(nt) => {
+                let mut sequences = vec![];
+
+                for middle in start..=end {
+                    if middle == start && !self.nullable.contains(nt) {
+                        continue;
+                    }
+                    if self.sequence(rest, middle, end).0 == 0
+                        || self.count(nt, start, middle).0 == 0
+                    {
+                        continue;
+                    }
+
+                    let befores = self.trees(nt, start, middle, path);
+                    let afters = self.sequences(rest, middle, end, path);
+                    for before in &befores {
+                        for after in &afters {
+                            if sequences.len() < self.max {
+                                let mut values = vec![before.clone()];
+                                values.extend(after.iter().cloned());
+                                sequences.push(values);
+                            }
+                        }
+                    }
+                }
+
+                sequences
+            }
+        }
+    }
+
+    /// Ways for `symbols` to derive the tokens from `start` to `end`.
+    fn sequence(&mut self, symbols: &'a [Expression], start: usize, end: usize) -> Partial {
+        let Some((first, rest)) = symbols.split_first() else {
+            return (usize::from(start == end), BTreeSet::new());
+        };
+
+        match first {
+            Expression::Terminal(t) => {
+                if start < end && self.tokens[start] == t.kind() {
+                    self.sequence(rest, start + 1, end)
+                } else {
+                    (0, BTreeSet::new())
+                }
+            }
+            Expression::NonTerminal(nt) => {
+                let mut total: usize = 0;
+                let mut reentered = BTreeSet::new();
+
+                for middle in start..=end {
+                    // Only a nullable nonterminal can derive no tokens, and
+                    // checking first keeps dead paths from reaching spans
+                    // still being counted.
+                    if middle == start && !self.nullable.contains(nt) {
+                        continue;
+                    }
+
+                    let (after, spans) = self.sequence(rest, middle, end);
+                    reentered.extend(spans);
+                    if after == 0 {
+                        continue;
+                    }
+
+                    let (before, spans) = self.count(nt, start, middle);
+                    reentered.extend(spans);
+                    total = total
+                        .saturating_add(before.saturating_mul(after))
+                        .min(self.max);
+                }
+
+                (total, reentered)
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::{cell::OnceCell, collections::HashMap};
+
+use crate::{Grammar, NonTerminal, ParseError, Parser, Terminal, Token, Tokenizer};
+
+impl Parser {
+    /// Makes parsing pick the nonterminal to parse from by the first token,
+    /// for grammars whose top-level constructs each start with their own
+    /// keyword. Terminals are looked up by [`Terminal::kind`], skipped ones
+    /// are passed over, and the input is then parsed as that nonterminal
+    /// alone, without trying the others. Input starting with a terminal not
+    /// in `dispatch` fails with [`ParseError::UnexpectedToken`], and empty
+    /// input with [`ParseError::UnexpectedEnd`], both expecting the
+    /// terminals that are. An empty map parses from the starting symbol
+    /// again.
+    ///
+    /// This applies to [`Parser::parse`], [`Parser::parse_all`],
+    /// [`Parser::parse_with_metrics`], [`Parser::parse_with_cancel`],
+    /// [`Parser::parse_with_trace`] and [`Parser::recognize`], which
+    /// rejects such input, and to each segment of
+    /// [`Parser::parse_multiple`]. The other entry points always parse from
+    /// the starting symbol.
+    pub fn set_dispatch(&mut self, dispatch: HashMap<Terminal, NonTerminal>) {
+        self.dispatch = dispatch
+            .into_iter()
+            .map(|(terminal, nt)| (terminal.kind(), nt))
+            .collect();
+        self.dispatched = OnceCell::new();
+    }
+
+    /// The parser for the nonterminal `tokens` dispatch to, or this one if
+    /// there is no dispatch map.
+    pub(crate) fn dispatched(&self, tokens: &[Token]) -> Result<&Parser, ParseError> {
+        if self.dispatch.is_empty() {
+            return Ok(self);
+        }
+
+        let parsers = self.dispatched.get_or_init(|| {
+            self.dispatch
+                .values()
+                .map(|nt| (nt.clone(), self.starting_from(nt)))
+                .collect()
+        });
+
+        let first = tokens
+            .iter()
+            .enumerate()
+            .find(|(_, token)| !self.skips(&token.terminal));
+        let Some((position, token)) = first else {
+            return Err(ParseError::UnexpectedEnd {
+                expected: self.dispatch_terminals(),
+            });
+        };
+
+        match self.dispatch.get(&token.terminal.kind()) {
+            Some(nt) => Ok(&parsers[nt]),
+            None => Err(ParseError::UnexpectedToken {
+                expected: self.dispatch_terminals(),
+                found: token.terminal.clone(),
+                position,
+                span: token.span,
+            }),
+        }
+    }
+
+    /// A parser for the same grammar and settings, but starting from `nt`.
+    /// It is only ever given tokens, so its lexer goes unused, and it counts
+    /// coverage into this parser's counts. Every setter that changes what
+    /// it copies throws the dispatch parsers away.
+    fn starting_from(&self, nt: &NonTerminal) -> Parser {
+        let grammar = Grammar {
+            starting_symbol: nt.clone(),
+            ..self.grammar.clone()
+        };
+
+        let mut parser =
+            Parser::with_lexer_skipping(grammar, Tokenizer::new(), self.skipped.clone());
+        parser.collapse_unit_productions = self.collapse_unit_productions;
+        parser.strategy = self.strategy;
+        parser.post_check = self.post_check.clone();
+        parser.max_nesting_depth = self.max_nesting_depth;
+        parser.coverage = self.coverage.clone();
+        parser
+    }
+
+    /// The terminals in the dispatch map, in the order the grammar reaches
+    /// them, then by name.
+    fn dispatch_terminals(&self) -> Vec<Terminal> {
+        let order = self.grammar.symbols_in_order().1;
+
+        let mut terminals: Vec<Terminal> = self.dispatch.keys().cloned().collect();
+        terminals.sort_by_cached_key(|terminal| {
+            let index = order.iter().position(|other| other == terminal);
+            (index.unwrap_or(usize::MAX), terminal.to_string())
+        });
+        terminals
+    }
+}
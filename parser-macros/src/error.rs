@@ -0,0 +1,353 @@
+use std::fmt;
+
+use crate::{Action, NonTerminal, Span, StackValue, Terminal, TokenizeError};
+
+/// Why an input could not be parsed. Positions count tokens from zero.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The input could not be split into terminals.
+    Tokenize(TokenizeError),
+    /// A terminal the grammar does not allow at this point, at `span` in
+    /// the input. `expected` holds the terminals that could have come next
+    /// from the stack as it was when the error was found.
+    UnexpectedToken {
+        found: Terminal,
+        position: usize,
+        span: Span,
+        expected: Vec<Terminal>,
+    },
+    /// The input ended where one of `expected` had to come next.
+    UnexpectedEnd { expected: Vec<Terminal> },
+    /// The stack could not reduce to the starting symbol before `next`
+    /// (`None` at the end of input). `stack` holds the values left on it,
+    /// bottom first.
+    BadStack {
+        stack: Vec<StackValue>,
+        next: Option<Terminal>,
+    },
+    /// The input is not a sentence of the grammar, from a backend that
+    /// cannot tell where it went wrong.
+    Rejected,
+    /// `error` was found in the segment numbered `index`, counting from
+    /// zero, by [`Parser::parse_multiple`](crate::Parser::parse_multiple).
+    Segment {
+        index: usize,
+        error: Box<ParseError>,
+    },
+    /// A semantic action of
+    /// [`Parser::parse_with_actions`](crate::Parser::parse_with_actions)
+    /// rejected the value for the tokens at `span`, which is `None` if it
+    /// was built from no tokens.
+    Semantic { message: String, span: Option<Span> },
+    /// The parse was cancelled through
+    /// [`Parser::parse_with_cancel`](crate::Parser::parse_with_cancel).
+    Cancelled,
+    /// The group opened by the token at `span` nests deeper than `limit`,
+    /// set by
+    /// [`Parser::set_max_nesting_depth`](crate::Parser::set_max_nesting_depth).
+    NestingTooDeep { limit: usize, span: Span },
+    /// The input has more than `limit` parses, set by
+    /// [`Parser::set_max_parallel_parses`](crate::Parser::set_max_parallel_parses).
+    TooAmbiguous { limit: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Tokenize(error) => write!(f, "{error}"),
+            ParseError::UnexpectedToken {
+                found,
+                position,
+                span: _,
+                expected,
+            } => {
+                write!(f, "Unexpected '{found}' at position {position}")?;
+                if !expected.is_empty() {
+                    write!(
+                        f,
+                        ", expected one of: {}",
+                        expected
+                            .iter()
+                            .map(|t| format!("'{t}'"))
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    )?;
+                }
+                Ok(())
+            }
+            ParseError::UnexpectedEnd { expected } => write!(
+                f,
+                "Unexpected end of input, expected one of: {}",
+                expected
+                    .iter()
+                    .map(|t| format!("'{t}'"))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            ParseError::BadStack { stack, next } => {
+                write!(
+                    f,
+                    "Bad stack: {}",
+                    stack
+                        .iter()
+                        .map(StackValue::to_string)
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                )?;
+                match next {
+                    Some(t) => write!(f, " before '{t}'"),
+                    None => write!(f, " at the end of input"),
+                }
+            }
+            ParseError::Rejected => write!(f, "Input is not a sentence of the grammar"),
+            ParseError::Segment { index, error } => write!(f, "In segment {index}: {error}"),
+            ParseError::Semantic {
+                message,
+                span: Some(span),
+            } => write!(f, "{message} at {span}"),
+            ParseError::Semantic {
+                message,
+                span: None,
+            } => write!(f, "{message}"),
+            ParseError::Cancelled => write!(f, "Parse cancelled"),
+            ParseError::NestingTooDeep { limit, span } => {
+                write!(f, "Nesting deeper than {limit} at {span}")
+            }
+            ParseError::TooAmbiguous { limit } => {
+                write!(f, "Input has more than {limit} parses")
+            }
+        }
+    }
+}
+
+impl ParseError {
+    /// Where in the input the error is, for errors tied to one place.
+    /// Errors at the end of input have none.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ParseError::Tokenize(error) => Some(error.span()),
+            ParseError::UnexpectedToken { span, .. } => Some(*span),
+            ParseError::Segment { error, .. } => error.span(),
+            ParseError::Semantic { span, .. } => *span,
+            ParseError::NestingTooDeep { span, .. } => Some(*span),
+            ParseError::UnexpectedEnd { .. }
+            | ParseError::BadStack { .. }
+            | ParseError::Rejected
+            | ParseError::Cancelled
+            | ParseError::TooAmbiguous { .. } => None,
+        }
+    }
+
+    /// Pairs the error with the input it came from, for display with the
+    /// offending line and a caret under it.
+    pub fn with_source(self, source: &str) -> Diagnostic {
+        Diagnostic {
+            error: self,
+            source: source.to_string(),
+        }
+    }
+}
+
+/// A [`ParseError`] with the input it came from. Displays the error, then
+/// the line it is on with `^` under the offending text, rustc style.
+/// Errors at the end of input point just past the last character, and a
+/// cancelled or too ambiguous parse, which is about no one place, shows no
+/// line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub error: ParseError,
+    pub source: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+
+        let source = self.source.as_str();
+        let span = match (&self.error, self.error.span()) {
+            (ParseError::Cancelled | ParseError::TooAmbiguous { .. }, _) => return Ok(()),
+            (_, Some(span)) => span,
+            (_, None) => Span {
+                start: source.trim_end().len(),
+                end: source.trim_end().len(),
+            },
+        };
+        // Spans from a byte lexer can fall inside a char; they are taken
+        // from the start of it, as `LspPosition::from_offset` does.
+        let start = floor_char_boundary(source, span.start);
+        let end = floor_char_boundary(source, span.end);
+
+        let line_start = source[..start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |index| start + index);
+        let line = source[line_start..line_end].trim_end_matches('\r');
+        let number = source[..start].matches('\n').count() + 1;
+        let column = source[line_start..start].chars().count() + 1;
+
+        // Tabs are kept so the caret lines up however they are displayed.
+        let padding: String = source[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let width = source[start..end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let gutter = " ".repeat(number.to_string().len());
+
+        write!(
+            f,
+            "\n{gutter}--> line {number}, column {column}\n\
+             {gutter} |\n\
+             {number} | {line}\n\
+             {gutter} | {padding}{carets}",
+            carets = "^".repeat(width)
+        )
+    }
+}
+
+/// `offset`, or the start of the char of `source` it falls in, and at most
+/// the end of `source`.
+fn floor_char_boundary(source: &str, offset: usize) -> usize {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+impl std::error::Error for Diagnostic {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Tokenize(error) => Some(error),
+            ParseError::Segment { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<TokenizeError> for ParseError {
+    fn from(error: TokenizeError) -> Self {
+        ParseError::Tokenize(error)
+    }
+}
+
+/// Why a grammar was rejected by [`Grammar::validate`](crate::Grammar::validate),
+/// or why an edit to it was refused.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GrammarError {
+    /// A nonterminal that is the starting symbol or appears in a rule, but
+    /// has no rules of its own.
+    Undefined(NonTerminal),
+    /// Nonterminals with rules that the starting symbol never reaches.
+    Unreachable(Vec<NonTerminal>),
+    /// Nonterminals that cannot derive any sentence of terminals.
+    NonProductive(Vec<NonTerminal>),
+    /// A cell of the parse table with more than one action, for
+    /// `lookahead` (`None` at the end of input) in `state`.
+    Conflict {
+        state: usize,
+        lookahead: Option<Terminal>,
+        actions: Vec<Action>,
+    },
+    /// A rename onto a nonterminal the grammar already uses.
+    AlreadyDefined(NonTerminal),
+    /// The grammar is not LL(1): each conflict is a lookahead on which a
+    /// recursive-descent parser could take more than one alternative.
+    NotLl1(Vec<LlConflict>),
+}
+
+/// Alternatives of `non_terminal`, by index, that can all start with
+/// `lookahead` (`None` at the end of input), so one token of lookahead
+/// cannot choose between them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LlConflict {
+    pub non_terminal: NonTerminal,
+    pub lookahead: Option<Terminal>,
+    pub alternatives: Vec<usize>,
+}
+
+impl fmt::Display for LlConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nt = &self.non_terminal;
+        match &self.lookahead {
+            Some(t) => write!(f, "{nt} on '{t}': alternatives ")?,
+            None => write!(f, "{nt} at the end of input: alternatives ")?,
+        }
+        write!(
+            f,
+            "{}",
+            self.alternatives
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<String>>()
+                .join("/")
+        )
+    }
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = |non_terminals: &[NonTerminal]| {
+            non_terminals
+                .iter()
+                .map(NonTerminal::to_string)
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+
+        match self {
+            GrammarError::Undefined(nt) => write!(f, "Nonterminal {nt} is used but has no rules"),
+            GrammarError::Unreachable(non_terminals) => write!(
+                f,
+                "Nonterminals not reachable from the starting symbol: {}",
+                names(non_terminals)
+            ),
+            GrammarError::NonProductive(non_terminals) => write!(
+                f,
+                "Nonterminals that cannot derive any sentence: {}",
+                names(non_terminals)
+            ),
+            GrammarError::Conflict {
+                state,
+                lookahead,
+                actions,
+            } => {
+                match lookahead {
+                    Some(t) => write!(f, "Conflict in state {state} on '{t}': ")?,
+                    None => write!(f, "Conflict in state {state} at the end of input: ")?,
+                }
+                write!(
+                    f,
+                    "{}",
+                    actions
+                        .iter()
+                        .map(Action::to_string)
+                        .collect::<Vec<String>>()
+                        .join("/")
+                )
+            }
+            GrammarError::AlreadyDefined(nt) => {
+                write!(f, "Nonterminal {nt} is already used by the grammar")
+            }
+            GrammarError::NotLl1(conflicts) => write!(
+                f,
+                "The grammar is not LL(1): {}",
+                conflicts
+                    .iter()
+                    .map(LlConflict::to_string)
+                    .collect::<Vec<String>>()
+                    .join("; ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GrammarError {}
@@ -0,0 +1,139 @@
+use std::fmt::Write;
+
+use crate::{Expression, ParseState, Parser, Skeleton, Tables};
+
+impl Parser {
+    /// Explains in prose why `input` does not parse, for grammar authors
+    /// working out why an input is rejected.
+    ///
+    /// The parse is re-run up to the point of failure. The explanation
+    /// names the offending token (or the end of input), shows the stack as
+    /// it was, lists the terminals that could have come instead, and for
+    /// each of them the productions that were in progress and would have
+    /// accepted it. Productions are shown with a `•` before the terminal
+    /// they were waiting for, followed by their label if they have one.
+    pub fn explain_failure(&self, input: &str) -> String {
+        let tokens = match self.lexer.lex(input) {
+            Ok(tokens) => tokens,
+            Err(error) => return format!("The input could not be split into tokens: {error}.\n"),
+        };
+
+        let tables = self.tables();
+        let mut state = self.limited_state();
+
+        for (position, token) in tokens.into_iter().enumerate() {
+            let terminal = token.terminal.clone();
+            if self.skips(&terminal) {
+                continue;
+            }
+
+            if let Err(error) = state.reduce(tables, &mut Skeleton, Some(&terminal)) {
+                return format!(
+                    "Parsing stopped before '{terminal}' (token {position}): {error}.\n"
+                );
+            }
+
+            if state.shift(tables, &mut Skeleton, token, position).is_err() {
+                let mut out = format!("Parsing stopped at '{terminal}' (token {position}).\n");
+                explain_stack(&mut out, tables, &state);
+                return out;
+            }
+        }
+
+        if let Err(error) = state.reduce(tables, &mut Skeleton, None) {
+            return format!("Parsing stopped at the end of input: {error}.\n");
+        }
+
+        if state.accepts(&self.grammar.starting_symbol) {
+            return format!(
+                "The input parses as a {}, there is no failure to explain.\n",
+                self.grammar.starting_symbol
+            );
+        }
+
+        let mut out = format!(
+            "The input ended before it formed a complete {}.\n",
+            self.grammar.starting_symbol
+        );
+        explain_stack(&mut out, tables, &state);
+        out
+    }
+}
+
+/// Appends the stack, the expected terminals and the productions waiting
+/// for each of them.
+fn explain_stack(out: &mut String, tables: &Tables, state: &ParseState<()>) {
+    let stack = if state.stack.is_empty() {
+        "(empty)".to_string()
+    } else {
+        state
+            .symbols
+            .iter()
+            .map(|symbol| match symbol {
+                Expression::NonTerminal(nt) => nt.to_string(),
+                Expression::Terminal(t) => format!("'{t}'"),
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    writeln!(out, "Stack: {stack}").expect("Writing to a String cannot fail");
+
+    let expected = state.expected(tables);
+    if expected.is_empty() {
+        writeln!(out, "No terminal could have come next.")
+            .expect("Writing to a String cannot fail");
+        return;
+    }
+
+    writeln!(
+        out,
+        "Expected one of: {}",
+        expected
+            .iter()
+            .map(|t| format!("'{t}'"))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+    .expect("Writing to a String cannot fail");
+
+    for terminal in &expected {
+        writeln!(
+            out,
+            "With '{terminal}' next, these productions could have continued:"
+        )
+        .expect("Writing to a String cannot fail");
+
+        let trial = state.trial(tables, terminal);
+        for (production, (lhs, rhs)) in
+            (1..).map_while(|index| Some((index, tables.table.production(index)?)))
+        {
+            for (dot, expr) in rhs.iter().enumerate() {
+                if *expr != Expression::Terminal(terminal.kind())
+                    || !trial.matches_top(&rhs[..dot])
+                    || tables
+                        .table
+                        .goto(trial.states[trial.stack.len() - dot], lhs)
+                        .is_none()
+                {
+                    continue;
+                }
+
+                write!(out, "  {lhs} ->").expect("Writing to a String cannot fail");
+                for (index, expr) in rhs.iter().enumerate() {
+                    if index == dot {
+                        write!(out, " •").expect("Writing to a String cannot fail");
+                    }
+                    match expr {
+                        Expression::Terminal(t) => write!(out, " '{t}'"),
+                        Expression::NonTerminal(nt) => write!(out, " {nt}"),
+                    }
+                    .expect("Writing to a String cannot fail");
+                }
+                if let Some(label) = tables.table.label(production) {
+                    write!(out, " ({label})").expect("Writing to a String cannot fail");
+                }
+                writeln!(out).expect("Writing to a String cannot fail");
+            }
+        }
+    }
+}
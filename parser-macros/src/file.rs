@@ -0,0 +1,63 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{ParseError, Parser, StackValue};
+
+/// Why [`Parser::parse_file`] failed, with the file it was reading.
+#[derive(Debug)]
+pub enum FileError {
+    /// The file could not be read, or was not UTF-8.
+    Io { path: PathBuf, error: io::Error },
+    /// The file was read but its contents do not parse.
+    Parse { path: PathBuf, error: ParseError },
+}
+
+impl FileError {
+    /// The file that failed.
+    pub fn path(&self) -> &Path {
+        match self {
+            FileError::Io { path, .. } | FileError::Parse { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::Io { path, error } => {
+                write!(f, "Could not read {}: {error}", path.display())
+            }
+            FileError::Parse { path, error } => write!(f, "In {}: {error}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FileError::Io { error, .. } => Some(error),
+            FileError::Parse { error, .. } => Some(error),
+        }
+    }
+}
+
+impl Parser {
+    /// Reads the file at `path` whole and parses it as with
+    /// [`Parser::parse`]. Spans in a [`FileError::Parse`] are byte offsets
+    /// into the file.
+    pub fn parse_file<P: AsRef<Path>>(&self, path: P) -> Result<StackValue, FileError> {
+        let path = path.as_ref();
+
+        let input = fs::read_to_string(path).map_err(|error| FileError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        self.parse(&input).map_err(|error| FileError::Parse {
+            path: path.to_path_buf(),
+            error,
+        })
+    }
+}
@@ -1,44 +1,388 @@
+mod actions;
+mod analysis;
+pub mod arena;
+#[cfg(feature = "binary")]
+pub mod binary;
+pub mod builder;
+mod classes;
+mod codegen;
+mod completion;
+mod coverage;
+mod cyk;
+mod debug;
+mod derivations;
+mod dispatch;
+pub mod error;
+mod explain;
+#[cfg(feature = "std")]
+pub mod file;
+mod lint;
+mod lsp;
+#[cfg(feature = "regex")]
+pub mod patterns;
+pub mod pratt;
+mod railroad;
+mod recovery;
+mod registry;
+mod rewrite;
+mod spacing;
+mod suggest;
+pub mod table;
 pub mod tokenizer;
+mod trace;
+mod tree;
+mod validate;
 
-use std::{collections::HashMap, fmt};
+pub use arena::{Arena, Node, NodeId};
+#[cfg(feature = "binary")]
+pub use binary::DecodeError;
+pub use builder::GrammarBuilder;
+pub use debug::{DebugState, StepResult};
+pub use derivations::DerivationChart;
+pub use error::{Diagnostic, GrammarError, LlConflict, ParseError};
+#[cfg(feature = "std")]
+pub use file::FileError;
+pub use lint::{Lint, Severity};
+pub use lsp::{LspDiagnostic, LspPosition, LspRange};
+#[cfg(feature = "regex")]
+pub use patterns::{PatternError, TokenizerConfig};
+pub use pratt::PrecedenceTable;
+pub use registry::SymbolRegistry;
+pub use rewrite::Override;
+pub use spacing::Spacing;
+pub use suggest::Edit;
+pub use table::{Action, ParseTable};
+pub use tokenizer::{ByteLexer, CharLexer, Lexer, Span, Token, TokenizeError, Tokenizer, Trivia};
+pub use trace::TraceEvent;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::{HashMap, HashSet},
+    fmt,
+    ops::RangeInclusive,
+    rc::Rc,
+    sync::atomic::{AtomicBool, Ordering},
+    vec::Drain,
+};
+
+/// Nonterminals order as they are declared, and generated ones by their
+/// number after every named one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum NonTerminal {
     Sum,
     Sub,
     Mult,
     Atom,
     Number,
+    List,
+    Elements,
+    /// A single expression nonterminal, as used by
+    /// [`Parser::parse_pratt`] in place of a precedence hierarchy.
+    Expr,
+    /// A nonterminal minted by a grammar transformation such as
+    /// [`Grammar::as_chomsky_normal_form`], numbered so it never collides
+    /// with another.
+    Generated(usize),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Terminals order as their variants are declared, then by payload, with
+/// a kind's `None` before any of its values.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Terminal {
     Plus,
     Minus,
     Star,
     LeftParen,
     RightParen,
+    LeftBracket,
+    RightBracket,
+    Comma,
     Zero,
+    Equal,
+    DoubleEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Arrow,
+    Semicolon,
+    /// A double quoted string, holding its unescaped content.
+    StringLiteral(String),
+    /// A name such as `a` or `rate_2`, holding the name.
+    Identifier(String),
+    /// A run of whitespace, holding it, from a [`Tokenizer`] made with
+    /// [`Tokenizer::with_whitespace`]. Other tokenizers treat whitespace as
+    /// [`Trivia`].
+    Whitespace(String),
+    /// A single char from a [`CharLexer`], with its class, for grammars
+    /// that do their own lexing. Letters, digits and whitespace match by
+    /// class alone, so a rule naming [`Terminal::any`] of a class matches
+    /// every char in it. Other chars match only themselves.
+    Char(CharClass, Option<char>),
+    /// A byte from a [`ByteLexer`], for grammars over binary formats. A
+    /// byte in one of the lexer's ranges carries that range and matches a
+    /// rule naming [`Terminal::byte_range`] of it; any other byte only
+    /// matches [`Terminal::byte`] of itself.
+    Byte {
+        low: u8,
+        high: u8,
+        value: Option<u8>,
+    },
+    /// A terminal that the predicate of a terminal class accepted, wrapped
+    /// in the class's name by [`Parser::add_terminal_class`]. Rules name
+    /// the class with [`Terminal::class`], which matches all of them.
+    Class(String, Option<Box<Terminal>>),
+    /// An integer literal other than `0`, such as `42`, holding its value.
+    /// A rule naming `Int(None)` matches every one. `0` stays
+    /// [`Terminal::Zero`], so grammars that only know zeros are unaffected.
+    ///
+    /// Literals are never negative: `-1` is a [`Terminal::Minus`] and then
+    /// `1`, as for `Float`. Only the grammar knows whether a minus negates
+    /// or subtracts, as in `1-1`, so unary minus is one of its rules, such
+    /// as `atom -> '-' atom`.
+    Int(Option<i64>),
+    /// A decimal literal such as `3.14`, `3.` or `.5`, holding its value.
+    /// A rule naming `Float(None)` matches every one.
+    Float(Option<FloatLiteral>),
+    /// Input that spells no terminal, holding it, from a
+    /// [`Tokenizer::lenient`] tokenizer. No rule is meant to name it, so
+    /// parsing stops or recovers at it with the span of the bad input.
+    Error(String),
+}
+
+/// The value of a [`Terminal::Float`]. It compares and hashes by its bits,
+/// so terminals holding one can still go in sets, and orders by
+/// [`f64::total_cmp`], so they can be sorted too.
+#[derive(Debug, Clone, Copy)]
+pub struct FloatLiteral(pub f64);
+
+impl PartialEq for FloatLiteral {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for FloatLiteral {}
+
+impl PartialOrd for FloatLiteral {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FloatLiteral {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl std::hash::Hash for FloatLiteral {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+/// The Unicode classes [`Terminal::Char`] matches by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CharClass {
+    /// Alphabetic chars, as [`char::is_alphabetic`] has it, `é` and `ж`
+    /// included.
+    Letter,
+    /// Numeric chars, as [`char::is_numeric`] has it.
+    Digit,
+    Whitespace,
+    /// Anything else, such as punctuation, which matches char by char.
+    Other,
+}
+
+impl CharClass {
+    pub fn of(c: char) -> CharClass {
+        if c.is_alphabetic() {
+            CharClass::Letter
+        } else if c.is_numeric() {
+            CharClass::Digit
+        } else if c.is_whitespace() {
+            CharClass::Whitespace
+        } else {
+            CharClass::Other
+        }
+    }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Grammar {
     pub starting_symbol: NonTerminal,
     pub rules: HashMap<NonTerminal, Vec<Vec<Expression>>>,
+    /// Lookahead restrictions keyed by nonterminal and alternative index.
+    /// A restricted production only reduces when the next terminal is in
+    /// its set, on top of the usual FOLLOW check. At the end of input a
+    /// restricted production still reduces whenever FOLLOW allows it, since
+    /// there is no terminal to check.
+    pub reduce_lookahead: HashMap<(NonTerminal, usize), HashSet<Terminal>>,
+    /// Names for productions, keyed like [`Grammar::reduce_lookahead`],
+    /// such as `"addition"` for `sum -> sum '+' sub`. Traces,
+    /// [`Parser::explain_failure`], [`ParseTable::to_table_string`] and the
+    /// ambiguity panic call a labelled production by its label.
+    pub labels: HashMap<(NonTerminal, usize), String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl Grammar {
+    pub fn new(
+        starting_symbol: NonTerminal,
+        rules: HashMap<NonTerminal, Vec<Vec<Expression>>>,
+    ) -> Self {
+        Grammar {
+            starting_symbol,
+            rules,
+            reduce_lookahead: HashMap::new(),
+            labels: HashMap::new(),
+        }
+    }
+
+    /// Builds a grammar from `(nonterminal, alternatives)` pairs. Pairs for
+    /// the same nonterminal are merged, keeping their alternatives in order.
+    pub fn from_rules<I>(starting_symbol: NonTerminal, rules: I) -> Self
+    where
+        I: IntoIterator<Item = (NonTerminal, Vec<Vec<Expression>>)>,
+    {
+        let mut merged: HashMap<NonTerminal, Vec<Vec<Expression>>> = HashMap::new();
+        for (non_terminal, alternatives) in rules {
+            merged.entry(non_terminal).or_default().extend(alternatives);
+        }
+
+        Grammar::new(starting_symbol, merged)
+    }
+
+    /// The label of the `alternative`th production of `non_terminal`, or
+    /// the production itself as `sum -> sum '+' sub` if it has none. `None`
+    /// if there is no such production.
+    pub fn production_label(
+        &self,
+        non_terminal: &NonTerminal,
+        alternative: usize,
+    ) -> Option<String> {
+        let production = self.rules.get(non_terminal)?.get(alternative)?;
+
+        if let Some(label) = self.labels.get(&(non_terminal.clone(), alternative)) {
+            return Some(label.clone());
+        }
+
+        let mut label = format!("{non_terminal} ->");
+        for expr in production {
+            match expr {
+                Expression::Terminal(t) => label.push_str(&format!(" '{t}'")),
+                Expression::NonTerminal(nt) => label.push_str(&format!(" {nt}")),
+            }
+        }
+        Some(label)
+    }
+
+    /// How many alternatives `non_terminal` has, 0 if it has no rules.
+    pub fn alternatives(&self, non_terminal: &NonTerminal) -> usize {
+        self.rules.get(non_terminal).map_or(0, Vec::len)
+    }
+
+    /// How many productions the grammar has, counting each alternative of
+    /// each nonterminal once.
+    pub fn total_productions(&self) -> usize {
+        self.rules.values().map(Vec::len).sum()
+    }
+}
+
+/// Appends each production after the alternatives its nonterminal
+/// already has, so generated rules can be added in a loop.
+impl Extend<(NonTerminal, Vec<Expression>)> for Grammar {
+    fn extend<I>(&mut self, productions: I)
+    where
+        I: IntoIterator<Item = (NonTerminal, Vec<Expression>)>,
+    {
+        for (non_terminal, production) in productions {
+            self.rules.entry(non_terminal).or_default().push(production);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Expression {
     Terminal(Terminal),
     NonTerminal(NonTerminal),
 }
 
-#[derive(Debug, Clone)]
+/// `Clone`, `PartialEq` and `Debug` are written out in `tree.rs` without
+/// recursion, like the other walks, so trees as deep as the input are safe.
 pub enum StackValue {
+    /// A reduced production. `values` are the children in source order,
+    /// matching the production's right hand side left to right.
     Tree {
         head: NonTerminal,
         values: Vec<StackValue>,
     },
-    Terminal(Terminal),
+    /// A shifted token, with its span and trivia.
+    Terminal(Token),
+}
+
+impl Terminal {
+    /// The terminal with any payload cleared. Rules match terminals by
+    /// kind, so a `StringLiteral` in a rule matches every string literal
+    /// and an `Identifier` every identifier. `Whitespace` is the same, and
+    /// so is a `Char` of any class but [`CharClass::Other`] and a `Byte`
+    /// in a range of more than one byte. A `Class` matches by name, and
+    /// `Int` and `Float` by kind alone.
+    pub fn kind(&self) -> Terminal {
+        match self {
+            Terminal::StringLiteral(_) => Terminal::StringLiteral(String::new()),
+            Terminal::Identifier(_) => Terminal::Identifier(String::new()),
+            Terminal::Whitespace(_) => Terminal::Whitespace(String::new()),
+            Terminal::Char(class, _) if *class != CharClass::Other => Terminal::any(*class),
+            Terminal::Byte { low, high, .. } if low != high => Terminal::byte_range(*low..=*high),
+            Terminal::Class(name, _) => Terminal::class(name),
+            Terminal::Int(_) => Terminal::Int(None),
+            Terminal::Float(_) => Terminal::Float(None),
+            Terminal::Error(_) => Terminal::Error(String::new()),
+            other => other.clone(),
+        }
+    }
+
+    /// The terminal for `c`, as a [`CharLexer`] produces it.
+    pub fn char(c: char) -> Terminal {
+        Terminal::Char(CharClass::of(c), Some(c))
+    }
+
+    /// The terminal a rule names to match any char of `class`. For
+    /// [`CharClass::Other`] it matches nothing, since those chars only
+    /// match themselves; name them with [`Terminal::char`].
+    pub fn any(class: CharClass) -> Terminal {
+        Terminal::Char(class, None)
+    }
+
+    /// The terminal a rule names to match every terminal of the class
+    /// called `name`; see [`Parser::add_terminal_class`].
+    pub fn class(name: &str) -> Terminal {
+        Terminal::Class(name.to_string(), None)
+    }
+
+    /// The terminal for the single byte `value`.
+    pub fn byte(value: u8) -> Terminal {
+        Terminal::Byte {
+            low: value,
+            high: value,
+            value: Some(value),
+        }
+    }
+
+    /// The terminal a rule names to match any byte in `range`, when the
+    /// [`ByteLexer`] has that range.
+    pub fn byte_range(range: RangeInclusive<u8>) -> Terminal {
+        let (low, high) = range.into_inner();
+        if low == high {
+            return Terminal::byte(low);
+        }
+        Terminal::Byte {
+            low,
+            high,
+            value: None,
+        }
+    }
 }
 
 impl fmt::Display for NonTerminal {
@@ -49,6 +393,10 @@ impl fmt::Display for NonTerminal {
             NonTerminal::Mult => write!(f, "mult"),
             NonTerminal::Atom => write!(f, "atom"),
             NonTerminal::Number => write!(f, "number"),
+            NonTerminal::List => write!(f, "list"),
+            NonTerminal::Elements => write!(f, "elements"),
+            NonTerminal::Expr => write!(f, "expr"),
+            NonTerminal::Generated(index) => write!(f, "aux{index}"),
         }
     }
 }
@@ -61,7 +409,56 @@ impl fmt::Display for Terminal {
             Terminal::Star => write!(f, "*"),
             Terminal::LeftParen => write!(f, "("),
             Terminal::RightParen => write!(f, ")"),
+            Terminal::LeftBracket => write!(f, "["),
+            Terminal::RightBracket => write!(f, "]"),
+            Terminal::Comma => write!(f, ","),
+            Terminal::Semicolon => write!(f, ";"),
             Terminal::Zero => write!(f, "0"),
+            Terminal::Equal => write!(f, "="),
+            Terminal::DoubleEqual => write!(f, "=="),
+            Terminal::Less => write!(f, "<"),
+            Terminal::LessEqual => write!(f, "<="),
+            Terminal::Greater => write!(f, ">"),
+            Terminal::GreaterEqual => write!(f, ">="),
+            Terminal::Arrow => write!(f, "->"),
+            Terminal::StringLiteral(content) => {
+                write!(f, "\"")?;
+                for c in content.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        c => write!(f, "{c}")?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            // An identifier's kind has no name, so it shows what it stands for.
+            Terminal::Identifier(name) if name.is_empty() => write!(f, "identifier"),
+            Terminal::Identifier(name) => write!(f, "{name}"),
+            Terminal::Whitespace(text) if text.is_empty() => write!(f, "whitespace"),
+            Terminal::Whitespace(text) => write!(f, "{text}"),
+            Terminal::Char(_, Some(c)) => write!(f, "{c}"),
+            Terminal::Char(CharClass::Letter, None) => write!(f, "any letter"),
+            Terminal::Char(CharClass::Digit, None) => write!(f, "any digit"),
+            Terminal::Char(CharClass::Whitespace, None) => write!(f, "any whitespace"),
+            Terminal::Char(CharClass::Other, None) => write!(f, "any char"),
+            Terminal::Byte {
+                value: Some(value), ..
+            } => write!(f, "0x{value:02x}"),
+            Terminal::Byte {
+                low,
+                high,
+                value: None,
+            } => write!(f, "0x{low:02x}..=0x{high:02x}"),
+            Terminal::Class(_, Some(terminal)) => write!(f, "{terminal}"),
+            Terminal::Class(name, None) => write!(f, "{name}"),
+            Terminal::Int(Some(value)) => write!(f, "{value}"),
+            Terminal::Int(None) => write!(f, "integer"),
+            Terminal::Float(Some(FloatLiteral(value))) => write!(f, "{value:?}"),
+            Terminal::Float(None) => write!(f, "float"),
+            Terminal::Error(text) if text.is_empty() => write!(f, "invalid input"),
+            Terminal::Error(text) => write!(f, "{text}"),
         }
     }
 }
@@ -86,130 +483,1164 @@ impl fmt::Display for Grammar {
 
 pub struct Parser {
     grammar: Grammar,
+    lexer: Box<dyn Lexer>,
+    /// Terminals dropped before they reach the reduction loop.
+    skipped: HashSet<Terminal>,
+    /// Derived from `grammar` on first use and shared by every parse.
+    tables: OnceCell<Tables>,
+    /// The grammar in Chomsky normal form, built on first use by
+    /// [`Parser::parse_cyk`], with the nonterminals the conversion added.
+    cnf: OnceCell<(Grammar, HashSet<NonTerminal>)>,
+    /// Reductions per production across parses, once enabled. Shared with
+    /// the dispatch parsers, so their parses count too.
+    coverage: Option<Coverage>,
+    /// See [`Parser::collapse_unit_productions`].
+    collapse_unit_productions: bool,
+    strategy: ReduceStrategy,
+    /// See [`Parser::set_dispatch`].
+    dispatch: HashMap<Terminal, NonTerminal>,
+    /// A parser per nonterminal in `dispatch`, starting from it, built on
+    /// first use.
+    dispatched: OnceCell<HashMap<NonTerminal, Parser>>,
+    /// See [`Parser::with_post_check`].
+    post_check: Option<Rc<PostCheck>>,
+    /// See [`Parser::set_max_nesting_depth`].
+    max_nesting_depth: Option<usize>,
+    /// See [`Parser::set_max_parallel_parses`].
+    max_parallel_parses: usize,
+    /// The terminals the grammar names, by kind, shared with the lexers of
+    /// [`Parser::add_terminal_class`] once there are any.
+    class_literals: Option<Rc<RefCell<HashSet<Terminal>>>>,
+}
+
+/// Reductions per production, keyed by nonterminal and alternative index.
+type Coverage = Rc<RefCell<HashMap<(NonTerminal, usize), u64>>>;
+
+/// A check on the whole tree of a successful parse; see
+/// [`Parser::with_post_check`].
+pub type PostCheck = dyn Fn(&StackValue) -> Result<(), ParseError>;
+
+/// When the reduction loop rewrites the top of the stack, chosen with
+/// [`Parser::set_reduce_strategy`]. The strategies only differ where the
+/// grammar has a shift-reduce conflict, so for grammars without one they
+/// build the same trees. Neither settles a choice between two reductions
+/// of the same length, which still panics as ambiguous.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ReduceStrategy {
+    /// Reduce whenever a rule matches the top of the stack, preferring the
+    /// rule that consumes the most values. A shift-reduce conflict goes to
+    /// the reduction, so an ambiguous `sum -> sum '+' sum` groups to the
+    /// left.
+    #[default]
+    Longest,
+    /// Reduce only when the lookahead cannot be shifted instead, the way
+    /// yacc resolves conflicts. A shift-reduce conflict goes to the shift,
+    /// so the same rule groups to the right, and an `else` binds to the
+    /// nearest `if`.
+    PreferShift,
+}
+
+/// How many parses of one input are allowed unless
+/// [`Parser::set_max_parallel_parses`] says otherwise.
+pub(crate) const DEFAULT_MAX_PARALLEL_PARSES: usize = 1024;
+
+/// A production as the reduction loop sees it: its nonterminal, its index
+/// among that nonterminal's alternatives, its right hand side and its
+/// lookahead restriction.
+pub(crate) type Item = (
+    NonTerminal,
+    usize,
+    Vec<Expression>,
+    Option<HashSet<Terminal>>,
+);
+
+/// Tables derived from the grammar that drive the reduction loop.
+pub(crate) struct Tables {
+    /// Every production, with its restriction from
+    /// [`Grammar::reduce_lookahead`] if it has one. Longest right hand sides
+    /// come first; productions of equal length keep the order of
+    /// [`Grammar::symbols_in_order`], so the order never depends on how the
+    /// rule maps iterate.
+    pub(crate) items: Vec<Item>,
+    /// What to call each of `items`, from [`Grammar::production_label`].
+    pub(crate) labels: Vec<String>,
+    pub(crate) follow: HashMap<NonTerminal, HashSet<Option<Terminal>>>,
+    pub(crate) table: ParseTable,
+    /// The parser's [`ReduceStrategy`], kept here since every reduction
+    /// consults it.
+    pub(crate) strategy: ReduceStrategy,
+}
+
+/// Whether a [`Grammar::reduce_lookahead`] restriction lets a production
+/// reduce with `lookahead` next. The end of input is always allowed.
+pub(crate) fn restriction_allows(
+    restriction: Option<&HashSet<Terminal>>,
+    lookahead: Option<&Terminal>,
+) -> bool {
+    match (restriction, lookahead) {
+        (Some(allowed), Some(lookahead)) => allowed.iter().any(|t| t.kind() == lookahead.kind()),
+        _ => true,
+    }
+}
+
+/// Whether `terminal` opens a group that [`Parser::set_max_nesting_depth`]
+/// counts.
+fn opens(terminal: &Terminal) -> bool {
+    matches!(terminal, Terminal::LeftParen | Terminal::LeftBracket)
+}
+
+/// The stack of a parse in progress. `symbols` holds the grammar symbol of
+/// each stack value, with terminals by [`Terminal::kind`], and `states` the
+/// LR state reached below and after each stack value, so it is always one
+/// longer than `stack`.
+#[derive(Clone)]
+pub(crate) struct ParseState<V = StackValue> {
+    pub(crate) stack: Vec<V>,
+    pub(crate) symbols: Vec<Expression>,
+    pub(crate) states: Vec<usize>,
+    pub(crate) metrics: ParseMetrics,
+    /// Reductions per production in [`Tables::items`], when the parser
+    /// records coverage.
+    pub(crate) coverage: Option<Vec<u64>>,
+    /// The steps taken, when they are collected by
+    /// [`Parser::parse_with_trace`] rather than printed.
+    pub(crate) trace: Option<Vec<TraceEvent>>,
+    /// See [`Parser::set_max_nesting_depth`].
+    pub(crate) max_nesting_depth: Option<usize>,
+    /// The stack index and span of each opening token still on the stack,
+    /// bottom first, which the nesting limit counts.
+    pub(crate) open: Vec<(usize, Span)>,
+}
+
+/// Counters from one parse, as returned by [`Parser::parse_with_metrics`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseMetrics {
+    /// Tokens shifted onto the stack.
+    pub shifts: usize,
+    /// Productions reduced.
+    pub reductions: usize,
+    /// The most values the stack held at once.
+    pub peak_stack_depth: usize,
+    /// Tokens read from the lexer, including skipped ones.
+    pub tokens: usize,
+}
+
+/// Builds the values a [`ParseState`] keeps on its stack as tokens are
+/// shifted and productions reduced.
+pub(crate) trait TreeBuilder {
+    type Value;
+
+    fn leaf(&mut self, token: Token) -> Self::Value;
+
+    /// A tree for `head` over `children`, in source order.
+    fn tree(&mut self, head: &NonTerminal, children: Drain<'_, Self::Value>) -> Self::Value;
+}
+
+/// Builds owned [`StackValue`] trees, collapsing unit productions if
+/// `collapse` is set.
+pub(crate) struct Owned {
+    pub(crate) collapse: bool,
+}
+
+impl TreeBuilder for Owned {
+    type Value = StackValue;
+
+    fn leaf(&mut self, token: Token) -> StackValue {
+        StackValue::Terminal(token)
+    }
+
+    fn tree(&mut self, head: &NonTerminal, children: Drain<'_, StackValue>) -> StackValue {
+        let mut values: Vec<StackValue> = children.collect();
+        if self.collapse
+            && let [
+                StackValue::Tree {
+                    head: _,
+                    values: inner,
+                },
+            ] = values.as_mut_slice()
+        {
+            values = std::mem::take(inner);
+        }
+
+        StackValue::Tree {
+            head: head.clone(),
+            values,
+        }
+    }
+}
+
+/// Builds nothing, for runs that only need to know what the stack would
+/// look like.
+pub(crate) struct Skeleton;
+
+impl TreeBuilder for Skeleton {
+    type Value = ();
+
+    fn leaf(&mut self, _token: Token) {}
+
+    fn tree(&mut self, _head: &NonTerminal, _children: Drain<'_, ()>) {}
 }
 
 impl Parser {
+    /// A parser using the built-in [`Tokenizer`].
     pub fn new(grammar: Grammar) -> Self {
-        Parser { grammar }
+        Parser::with_lexer(grammar, Tokenizer::new())
     }
 
-    pub fn parse(&self, input: &str) -> Result<(), String> {
-        println!("\n==============\nParsing {input}");
+    /// Like [`Parser::new`], but refuses any grammar whose parse table has
+    /// a shift-reduce or reduce-reduce conflict, returning every
+    /// [`GrammarError::Conflict`] instead. [`Parser::new`] accepts such
+    /// grammars and only fails at parse time, when an ambiguous reduction
+    /// panics, so this is the safer choice for grammars that must not be
+    /// ambiguous. The table built for the check is kept for parsing.
+    pub fn new_strict(grammar: Grammar) -> Result<Self, Vec<GrammarError>> {
+        let parser = Parser::new(grammar);
 
-        let mut tokens = input.split_whitespace().map(|val| match val {
-            "+" => Terminal::Plus,
-            "-" => Terminal::Minus,
-            "*" => Terminal::Star,
-            "(" => Terminal::LeftParen,
-            ")" => Terminal::RightParen,
-            "0" => Terminal::Zero,
-            invalid => panic!("Invalid character: {invalid}"),
-        });
+        let conflicts = validate::conflicts(&parser.tables().table);
+        if !conflicts.is_empty() {
+            return Err(conflicts);
+        }
 
-        self.parse_expression(&mut tokens)
+        Ok(parser)
     }
 
-    fn parse_expression<I>(&self, tokens: &mut I) -> Result<(), String>
-    where
-        I: Iterator<Item = Terminal> + Clone,
-    {
-        let items: HashMap<NonTerminal, Vec<Expression>> = self
-            .grammar
-            .rules
-            .clone()
-            .into_iter()
-            .flat_map(|(rule_non_terminal, val)| {
-                val.into_iter()
-                    .map(move |rule| (rule_non_terminal.clone(), rule.clone()))
-            })
-            .collect();
+    pub fn with_lexer(grammar: Grammar, lexer: impl Lexer + 'static) -> Self {
+        Parser {
+            grammar,
+            lexer: Box::new(lexer),
+            skipped: HashSet::new(),
+            tables: OnceCell::new(),
+            cnf: OnceCell::new(),
+            coverage: None,
+            collapse_unit_productions: false,
+            strategy: ReduceStrategy::default(),
+            dispatch: HashMap::new(),
+            dispatched: OnceCell::new(),
+            post_check: None,
+            max_nesting_depth: None,
+            max_parallel_parses: DEFAULT_MAX_PARALLEL_PARSES,
+            class_literals: None,
+        }
+    }
 
-        let mut stack: Vec<StackValue> = vec![];
+    /// A parser that runs `check` on the tree of every successful parse
+    /// and fails with its error if it rejects the tree, for constraints
+    /// the grammar cannot express, such as names being declared before
+    /// they are used. Unlike a semantic action of
+    /// [`Parser::parse_with_actions`], the check sees the whole tree at
+    /// once, after every reduction is done. It usually rejects a tree with
+    /// [`ParseError::Semantic`].
+    pub fn with_post_check(grammar: Grammar, check: Box<PostCheck>) -> Self {
+        Parser {
+            post_check: Some(Rc::from(check)),
+            ..Parser::new(grammar)
+        }
+    }
 
-        while let Some(token) = tokens.next() {
-            let terminal = loop {
-                let matching_non_terminals: Vec<(usize, NonTerminal)> = items
-                    .iter()
-                    .filter_map(|(nt, rhs)| {
-                        if stack.len() < rhs.len() {
-                            return None;
-                        }
+    /// `tree` if the post check, if any, accepts it.
+    pub(crate) fn post_check(&self, tree: StackValue) -> Result<StackValue, ParseError> {
+        match &self.post_check {
+            Some(check) => check(&tree).map(|()| tree),
+            None => Ok(tree),
+        }
+    }
 
-                        let comp: Vec<(&StackValue, &Expression)> =
-                            Iterator::zip(stack.iter(), rhs.iter()).collect();
+    /// Limits how many parses of one input a backend that follows several
+    /// at once may keep, so a grammar that is ambiguous, intentionally or
+    /// not, fails with [`ParseError::TooAmbiguous`] rather than blowing up
+    /// exponentially on adversarial input. The shift-reduce engine behind
+    /// [`Parser::parse`] only ever follows one parse, so the limit does not
+    /// affect it. [`Parser::parse_cyk`] counts parses in the grammar's
+    /// Chomsky normal form, where `0 + 0 + 0` under `sum -> sum '+' sum`
+    /// has two. The default is 1024.
+    pub fn set_max_parallel_parses(&mut self, limit: usize) {
+        self.max_parallel_parses = limit;
+    }
 
-                        // println!(
-                        //     "==\nChecking if the following comparison:\n {comp:#?} can be replaced with {nt}\n"
-                        // );
+    /// The limit set by [`Parser::set_max_parallel_parses`].
+    pub fn max_parallel_parses(&self) -> usize {
+        self.max_parallel_parses
+    }
 
-                        if comp.iter().all(|(left, right)| match (left, right) {
-                            (
-                                StackValue::Tree { head, values: _ },
-                                Expression::NonTerminal(non_terminal),
-                            ) => head == non_terminal,
-                            (StackValue::Terminal(left), Expression::Terminal(right)) => {
-                                left == right
-                            }
-                            _ => false,
-                        }) {
-                            Some((rhs.len(), nt.clone()))
-                        } else {
-                            None
-                        }
+    /// A parser that ignores every terminal in `skipped`, so they can appear
+    /// anywhere in the input without being part of any rule. Skipped tokens
+    /// still count towards error positions. They are compared by
+    /// [`Terminal::kind`].
+    pub fn with_skipped(grammar: Grammar, skipped: HashSet<Terminal>) -> Self {
+        Parser::with_lexer_skipping(grammar, Tokenizer::new(), skipped)
+    }
+
+    /// A parser with both a lexer of the caller's own, as with
+    /// [`Parser::with_lexer`], and terminals to skip, as with
+    /// [`Parser::with_skipped`].
+    pub fn with_lexer_skipping(
+        grammar: Grammar,
+        lexer: impl Lexer + 'static,
+        skipped: HashSet<Terminal>,
+    ) -> Self {
+        Parser {
+            skipped: skipped.iter().map(Terminal::kind).collect(),
+            ..Parser::with_lexer(grammar, lexer)
+        }
+    }
+
+    /// When set, a tree whose only child is another tree takes that
+    /// child's children instead, so unit productions such as
+    /// `atom -> number` add no nesting and `0` parses as `(sum '0')`
+    /// rather than `(sum (sub (mult (atom (number '0')))))`. The outer
+    /// nonterminal is kept, so the root is still the starting symbol. This
+    /// applies to every parse that builds a [`StackValue`] tree; arena
+    /// trees keep their full structure. Off by default.
+    pub fn collapse_unit_productions(&mut self, collapse: bool) {
+        self.collapse_unit_productions = collapse;
+        self.dispatched = OnceCell::new();
+    }
+
+    /// Sets when reductions happen; see [`ReduceStrategy`] for how the
+    /// choices differ. The default is [`ReduceStrategy::Longest`].
+    pub fn set_reduce_strategy(&mut self, strategy: ReduceStrategy) {
+        self.strategy = strategy;
+        if let Some(tables) = self.tables.get_mut() {
+            tables.strategy = strategy;
+        }
+        self.dispatched = OnceCell::new();
+    }
+
+    /// Limits how deeply parentheses and brackets may nest, so hostile
+    /// input such as a million nested groups fails with
+    /// [`ParseError::NestingTooDeep`] instead of building a tree that deep.
+    /// The check is made as each group, a production starting with `(` or
+    /// `[`, is reduced, counting the groups still open around it, so
+    /// `( 0 ) ( 0 )` nests one deep. Every entry point that builds trees
+    /// checks it; the recovering ones report the error and stop there.
+    /// `None`, the default, allows any depth.
+    pub fn set_max_nesting_depth(&mut self, limit: Option<usize>) {
+        self.max_nesting_depth = limit;
+        self.dispatched = OnceCell::new();
+    }
+
+    /// The grammar the parser was built from.
+    pub fn grammar(&self) -> &Grammar {
+        &self.grammar
+    }
+
+    /// Gives up the parser for the grammar it was built from, for example
+    /// to change it and build a new parser.
+    pub fn into_grammar(self) -> Grammar {
+        self.grammar
+    }
+
+    /// Changes the grammar in place with `f`, keeping the lexer and every
+    /// setting, for example to add rules from a REPL. Everything derived
+    /// from the old grammar is thrown away and rebuilt on the next parse:
+    /// the parse table, the Chomsky normal form, the dispatch parsers and
+    /// the terminals that terminal classes leave alone. Coverage, if
+    /// enabled, starts over from 0 for the new productions.
+    pub fn update_grammar(&mut self, f: impl FnOnce(&mut Grammar)) {
+        f(&mut self.grammar);
+
+        self.tables = OnceCell::new();
+        self.cnf = OnceCell::new();
+        self.dispatched = OnceCell::new();
+        if self.coverage.take().is_some() {
+            self.enable_coverage();
+        }
+        if let Some(literals) = &self.class_literals {
+            *literals.borrow_mut() = classes::literals(&self.grammar);
+        }
+    }
+
+    /// The builder for [`StackValue`] trees, as this parser shapes them.
+    pub(crate) fn owned(&self) -> Owned {
+        Owned {
+            collapse: self.collapse_unit_productions,
+        }
+    }
+
+    pub(crate) fn skips(&self, terminal: &Terminal) -> bool {
+        self.skipped.contains(&terminal.kind())
+    }
+
+    /// Parses every input in turn. The tables derived from the grammar are
+    /// built by the first parse and reused by every later one, so this is
+    /// the same as calling [`Parser::parse`] on each input.
+    pub fn parse_all<'a>(
+        &self,
+        inputs: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<Result<StackValue, ParseError>> {
+        inputs.into_iter().map(|input| self.parse(input)).collect()
+    }
+
+    /// Parses a sequence of independent inputs separated by `separator`,
+    /// one tree per segment, each parsed from the starting symbol. The
+    /// segments are split by [`Terminal::kind`] after lexing, so the
+    /// grammar need not mention `separator`. A trailing separator leaves an
+    /// empty last segment, which only parses if the grammar accepts empty
+    /// input.
+    ///
+    /// The first segment that fails is reported as
+    /// [`ParseError::Segment`] with its index. Positions in the error count
+    /// tokens from the start of that segment; spans still point into
+    /// `input`.
+    pub fn parse_multiple(
+        &self,
+        input: &str,
+        separator: Terminal,
+    ) -> Result<Vec<StackValue>, ParseError> {
+        let separator = separator.kind();
+        let tokens = self.lexer.lex(input)?;
+
+        tokens
+            .split(|token| token.terminal.kind() == separator)
+            .enumerate()
+            .map(|(index, segment)| {
+                self.dispatched(segment)
+                    .and_then(|parser| parser.parse_expression(&mut segment.iter().cloned(), None))
+                    .map(|(tree, _)| tree)
+                    .map_err(|error| ParseError::Segment {
+                        index,
+                        error: Box::new(error),
                     })
-                    .collect();
-
-                if matching_non_terminals.len() > 1 {
-                    panic!(
-                        "Ambiguous grammar, multiple applicable rewrites: {}",
-                        matching_non_terminals
-                            .into_iter()
-                            .map(|(len, nt)| {
-                                format!(
-                                    "{nt} => {:?}",
-                                    stack.get(stack.len().saturating_sub(len)..).expect(
-                                        "Stack will at least have length \
-                                        of right hand side of rewrite rule"
-                                    )
-                                )
-                            })
-                            .collect::<Vec<String>>()
-                            .join(", ")
-                    )
+            })
+            .collect()
+    }
+
+    /// Parses tokens from a lexer of the caller's own, keeping their spans
+    /// in the tree and in errors. This is the same token-based entry point
+    /// [`Parser::parse`] uses after running the parser's [`Lexer`].
+    pub fn parse_spanned(&self, tokens: Vec<(Terminal, Span)>) -> Result<StackValue, ParseError> {
+        let mut tokens = tokens
+            .into_iter()
+            .map(|(terminal, span)| Token::new(terminal, span));
+
+        self.parse_expression(&mut tokens, None)
+            .map(|(tree, _)| tree)
+    }
+
+    /// Parses the longest prefix of `tokens` that forms a complete parse,
+    /// for embedding this grammar inside a larger one. Returns the tree and
+    /// how many tokens it consumed, skipped ones included, so the caller
+    /// can carry on from there. Fails with the error [`Parser::parse_spanned`]
+    /// would give if no prefix parses.
+    pub fn parse_prefix(
+        &self,
+        tokens: Vec<(Terminal, Span)>,
+    ) -> Result<(StackValue, usize), ParseError> {
+        let tables = self.tables();
+        let start = &self.grammar.starting_symbol;
+
+        let mut state = ParseState::<()>::new();
+        let mut complete = state.finishes(tables, start).then_some(0);
+
+        for (index, (terminal, _)) in tokens.iter().enumerate() {
+            if self.skips(terminal) {
+                continue;
+            }
+
+            let Some(next) = state.advance(tables, terminal) else {
+                break;
+            };
+            state = next;
+
+            if state.finishes(tables, start) {
+                complete = Some(index + 1);
+            }
+        }
+
+        let Some(consumed) = complete else {
+            return Err(self
+                .parse_spanned(tokens)
+                .expect_err("No prefix parses, so neither does the whole input"));
+        };
+
+        let mut tokens = tokens
+            .into_iter()
+            .take(consumed)
+            .map(|(terminal, span)| Token::new(terminal, span));
+
+        self.parse_expression(&mut tokens, None)
+            .map(|(tree, _)| (tree, consumed))
+    }
+
+    /// A tree for every prefix of `input` that parses on its own, with how
+    /// many tokens it spans, skipped ones included, shortest first. An
+    /// editor can use this to show structure for the part of the input
+    /// typed so far. Input that does not tokenize ends the prefixes there.
+    ///
+    /// This is a single pass of the push-based parse: after each shift, a
+    /// copy of the state is reduced as if the input ended there, but only
+    /// when its skeleton shows that would finish. Parsing stops at the
+    /// first token that cannot be shifted, since no longer prefix can
+    /// parse. Each tree is built separately, so the cost grows with the
+    /// number of complete prefixes times the depth of the stack.
+    pub fn parse_prefixes(&self, input: &str) -> Vec<(usize, StackValue)> {
+        let tables = self.tables();
+        let start = &self.grammar.starting_symbol;
+
+        let mut prefixes = vec![];
+        let mut state = self.limited_state();
+        let mut finish = |state: &ParseState, consumed: usize| {
+            if state.finishes(tables, start) {
+                let mut last = state.clone();
+                if last.reduce(tables, &mut self.owned(), None).is_ok()
+                    && let Ok(tree) = last.accept(start)
+                {
+                    prefixes.push((consumed, tree));
                 }
+            }
+        };
+
+        finish(&state, 0);
+
+        let tokens = self.lexer.tokens_recovering(input).into_iter();
+        for (position, token) in tokens.map_while(Result::ok).enumerate() {
+            if self.skips(&token.terminal) {
+                continue;
+            }
+
+            if state
+                .reduce(tables, &mut self.owned(), Some(&token.terminal))
+                .and_then(|()| state.shift(tables, &mut self.owned(), token, position))
+                .is_err()
+            {
+                break;
+            }
+
+            finish(&state, position + 1);
+        }
+
+        prefixes
+    }
+
+    pub fn parse(&self, input: &str) -> Result<StackValue, ParseError> {
+        let tokens = self.lexer.lex(input)?;
+
+        self.dispatched(&tokens)?
+            .parse_expression(&mut tokens.into_iter(), None)
+            .map(|(tree, _)| tree)
+    }
+
+    /// Like [`Parser::parse`], but also returns counters for the work the
+    /// parse did.
+    pub fn parse_with_metrics(
+        &self,
+        input: &str,
+    ) -> Result<(StackValue, ParseMetrics), ParseError> {
+        let tokens = self.lexer.lex(input)?;
+
+        self.dispatched(&tokens)?
+            .parse_expression(&mut tokens.into_iter(), None)
+    }
+
+    /// Whether `tokens` form a sentence of the grammar. Runs the same
+    /// shifts and reductions as [`Parser::parse`], but only tracks the
+    /// symbols on the stack, so no tree is allocated.
+    pub fn recognize(&self, tokens: &[Terminal]) -> bool {
+        let tokens: Vec<Token> = tokens.iter().cloned().map(Token::from).collect();
+        let Ok(parser) = self.dispatched(&tokens) else {
+            return false;
+        };
+        let tables = parser.tables();
+
+        let Ok(mut state) = parser.shift_all(tables, &mut Skeleton, tokens.into_iter(), None)
+        else {
+            return false;
+        };
+        if state.reduce(tables, &mut Skeleton, None).is_err() {
+            return false;
+        }
+
+        state.accepts(&parser.grammar.starting_symbol)
+    }
+
+    pub(crate) fn tables(&self) -> &Tables {
+        self.tables.get_or_init(|| {
+            let mut items: Vec<_> = self
+                .grammar
+                .symbols_in_order()
+                .0
+                .into_iter()
+                .flat_map(|rule_non_terminal| {
+                    self.grammar
+                        .rules
+                        .get(&rule_non_terminal)
+                        .into_iter()
+                        .flatten()
+                        .enumerate()
+                        .map(move |(alternative, rule)| {
+                            (
+                                rule_non_terminal.clone(),
+                                alternative,
+                                rule.clone(),
+                                self.grammar
+                                    .reduce_lookahead
+                                    .get(&(rule_non_terminal.clone(), alternative))
+                                    .cloned(),
+                            )
+                        })
+                })
+                .collect();
+            items.sort_by_key(|(_, _, rule, _)| std::cmp::Reverse(rule.len()));
+
+            let labels = items
+                .iter()
+                .map(|(nt, alternative, _, _)| {
+                    self.grammar
+                        .production_label(nt, *alternative)
+                        .expect("Every item is a production of the grammar")
+                })
+                .collect();
+
+            Tables {
+                items,
+                labels,
+                follow: self.grammar.follow_sets(),
+                table: ParseTable::new(&self.grammar),
+                strategy: self.strategy,
+            }
+        })
+    }
+
+    /// Like [`Parser::parse`], but gives up with [`ParseError::Cancelled`]
+    /// once `cancel` is set. The flag is checked before each token is
+    /// shifted, so a parse stops at most one token and the reductions it
+    /// allows after the flag is set.
+    pub fn parse_with_cancel(
+        &self,
+        input: &str,
+        cancel: &AtomicBool,
+    ) -> Result<StackValue, ParseError> {
+        let tokens = self.lexer.lex(input)?;
+
+        self.dispatched(&tokens)?
+            .parse_expression(&mut tokens.into_iter(), Some(cancel))
+            .map(|(tree, _)| tree)
+    }
+
+    fn parse_expression<I>(
+        &self,
+        tokens: &mut I,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<(StackValue, ParseMetrics), ParseError>
+    where
+        I: Iterator<Item = Token> + Clone,
+    {
+        let tables = self.tables();
+        let mut state = self.start_state(tables);
+
+        self.run(&mut state, tables, tokens, cancel)?;
+
+        let metrics = state.metrics;
+        let tree = state
+            .accept(&self.grammar.starting_symbol)
+            .map_err(|stack| ParseError::BadStack { stack, next: None })?;
+        self.post_check(tree).map(|tree| (tree, metrics))
+    }
+
+    /// Shifts every token and reduces at the end of input, leaving
+    /// `state` ready to accept. Fails if a token cannot be shifted or the
+    /// input is empty.
+    pub(crate) fn run<I>(
+        &self,
+        state: &mut ParseState,
+        tables: &Tables,
+        tokens: I,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<(), ParseError>
+    where
+        I: Iterator<Item = Token>,
+    {
+        self.shift_each(state, tables, &mut self.owned(), tokens, cancel)?;
 
-                let Some((len, nt)) = matching_non_terminals.first() else {
-                    break token;
-                };
+        state.reduce(tables, &mut self.owned(), None)?;
 
-                let old = stack
-                    .drain(stack.len().saturating_sub(*len)..)
-                    .as_slice()
-                    .to_owned();
+        if let (Some(coverage), Some(counts)) = (&self.coverage, &state.coverage) {
+            let mut coverage = coverage.borrow_mut();
+            for ((nt, alternative, _, _), count) in tables.items.iter().zip(counts) {
+                *coverage.entry((nt.clone(), *alternative)).or_default() += count;
+            }
+        }
+
+        // With nothing on the stack there is no stack to report, only the
+        // input that had to come first.
+        if state.stack.is_empty() {
+            return Err(ParseError::UnexpectedEnd {
+                expected: state.expected(tables),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Parser {
+    /// Shifts every token, reducing before each one as its lookahead
+    /// allows. The state is left as it is after the last shift. Stops with
+    /// [`ParseError::Cancelled`] if `cancel` is set before a token.
+    pub(crate) fn shift_all<B, I>(
+        &self,
+        tables: &Tables,
+        builder: &mut B,
+        tokens: I,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<ParseState<B::Value>, ParseError>
+    where
+        B: TreeBuilder,
+        B::Value: fmt::Debug,
+        I: Iterator<Item = Token>,
+    {
+        let mut state = self.start_state(tables);
+        self.shift_each(&mut state, tables, builder, tokens, cancel)?;
+
+        Ok(state)
+    }
+
+    /// A state to parse from, recording coverage if the parser does.
+    pub(crate) fn start_state<V: fmt::Debug>(&self, tables: &Tables) -> ParseState<V> {
+        let mut state = self.limited_state();
+        if self.coverage.is_some() {
+            state.coverage = Some(vec![0; tables.items.len()]);
+        }
+
+        state
+    }
+
+    /// A state to parse from that keeps to the parser's nesting limit.
+    pub(crate) fn limited_state<V: fmt::Debug>(&self) -> ParseState<V> {
+        let mut state = ParseState::new();
+        state.max_nesting_depth = self.max_nesting_depth;
+        state
+    }
+
+    /// Like [`Parser::shift_all`], but onto an existing state.
+    fn shift_each<B, I>(
+        &self,
+        state: &mut ParseState<B::Value>,
+        tables: &Tables,
+        builder: &mut B,
+        tokens: I,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<(), ParseError>
+    where
+        B: TreeBuilder,
+        B::Value: fmt::Debug,
+        I: Iterator<Item = Token>,
+    {
+        for (position, token) in tokens.enumerate() {
+            if cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+                return Err(ParseError::Cancelled);
+            }
+
+            state.metrics.tokens += 1;
+
+            if self.skips(&token.terminal) {
+                continue;
+            }
+
+            state.reduce(tables, builder, Some(&token.terminal))?;
+            state.shift(tables, builder, token, position)?;
+        }
+
+        Ok(())
+    }
+}
 
-                println!("Replacing stack values {old:?} with nonterminal {nt}");
+impl<V: fmt::Debug> ParseState<V> {
+    pub(crate) fn new() -> Self {
+        ParseState {
+            stack: vec![],
+            symbols: vec![],
+            states: vec![0],
+            metrics: ParseMetrics::default(),
+            coverage: None,
+            trace: None,
+            max_nesting_depth: None,
+            open: vec![],
+        }
+    }
+
+    fn top_state(&self) -> usize {
+        *self
+            .states
+            .last()
+            .expect("There is always a state below the stack")
+    }
 
-                stack.push(StackValue::Tree {
-                    head: nt.clone(),
-                    values: old,
+    /// Applies rewrites to the top of the stack until none match. Fails
+    /// instead of reducing a group nested deeper than the nesting limit.
+    pub(crate) fn reduce<B>(
+        &mut self,
+        tables: &Tables,
+        builder: &mut B,
+        lookahead: Option<&Terminal>,
+    ) -> Result<(), ParseError>
+    where
+        B: TreeBuilder<Value = V>,
+    {
+        while let Some((len, nt, item)) = self.find_reduction(tables, lookahead) {
+            if let Some(limit) = self.max_nesting_depth {
+                self.check_nesting(len, limit)?;
+            }
+
+            if self.trace.is_some() {
+                self.record(TraceEvent::Reduce {
+                    values: format!("{:?}", &self.stack[self.stack.len() - len..]),
+                    nt: nt.clone(),
+                    production: tables.labels[item].clone(),
                 });
+            }
 
-                println!("Stack state: {:?}", stack)
-            };
+            if let Some(coverage) = &mut self.coverage {
+                coverage[item] += 1;
+            }
 
-            println!("Adding terminal: {terminal}");
+            self.rewrite(tables, builder, len, nt);
 
-            stack.push(StackValue::Terminal(terminal));
+            if self.trace.is_some() {
+                self.record(TraceEvent::Stack(format!("{:?}", self.stack)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fails with [`ParseError::NestingTooDeep`] if the top `len` values
+    /// start with an opening token and reducing them would close a group
+    /// nested deeper than `limit`, counting it and the groups still open
+    /// below it.
+    pub(crate) fn check_nesting(&self, len: usize, limit: usize) -> Result<(), ParseError> {
+        let start = self.stack.len() - len;
+        let below = self.open.partition_point(|(index, _)| *index < start);
+
+        match self.open.get(below) {
+            Some((index, span)) if *index == start && below >= limit => {
+                Err(ParseError::NestingTooDeep { limit, span: *span })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Forgets the opening tokens from stack index `start` up, once the
+    /// values there are reduced or popped.
+    fn close_groups(&mut self, start: usize) {
+        let below = self.open.partition_point(|(index, _)| *index < start);
+        self.open.truncate(below);
+    }
 
-            println!("Stack state: {:?}", stack);
+    /// Collects `event` if the trace is being collected. Callers check
+    /// first, so events are only formatted for a trace that keeps them.
+    fn record(&mut self, event: TraceEvent) {
+        if let Some(trace) = &mut self.trace {
+            trace.push(event);
         }
+    }
 
-        if stack.len() == 1
-            && let Some(StackValue::Terminal(_)) = stack.first()
+    /// The rewrite to apply to the top of the stack, as the number of stack
+    /// values it consumes, the nonterminal replacing them and the index of
+    /// the production in [`Tables::items`].
+    ///
+    /// A rule only applies when `lookahead` (`None` at the end of input) can
+    /// follow its nonterminal and the resulting stack is still a viable
+    /// prefix of the grammar, and only for the lookaheads its
+    /// [`Grammar::reduce_lookahead`] restriction allows. Under
+    /// [`ReduceStrategy::PreferShift`] none applies while `lookahead` can
+    /// be shifted. When several apply the one consuming the most stack
+    /// values wins. If more than one consumes that many the grammar is
+    /// ambiguous and this panics, listing the candidates sorted by
+    /// nonterminal name, then by how many values they consume, then in
+    /// [`Tables::items`] order.
+    fn find_reduction(
+        &self,
+        tables: &Tables,
+        lookahead: Option<&Terminal>,
+    ) -> Option<(usize, NonTerminal, usize)> {
+        if tables.strategy == ReduceStrategy::PreferShift
+            && lookahead.is_some_and(|terminal| self.shift_target(tables, terminal).is_some())
         {
-            Ok(())
+            return None;
+        }
+
+        let symbols = &self.symbols;
+
+        let mut matching_non_terminals: Vec<(usize, NonTerminal, usize)> = tables
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(item, (nt, _, rhs, restriction))| {
+                if symbols.len() < rhs.len() {
+                    return None;
+                }
+
+                if !restriction_allows(restriction.as_ref(), lookahead) {
+                    return None;
+                }
+
+                if !tables
+                    .follow
+                    .get(nt)
+                    .is_some_and(|set| set.contains(&lookahead.map(Terminal::kind)))
+                {
+                    return None;
+                }
+
+                let below = self.states[symbols.len() - rhs.len()];
+                tables.table.goto(below, nt)?;
+
+                self.matches_top(rhs).then(|| (rhs.len(), nt.clone(), item))
+            })
+            .collect();
+
+        // Candidates arrive longest first, in the deterministic order of
+        // `tables.items`.
+        let longest = matching_non_terminals.first().map(|(len, _, _)| *len);
+        matching_non_terminals.retain(|(len, _, _)| Some(*len) == longest);
+
+        if matching_non_terminals.len() > 1 {
+            // Listed by name, so the message does not depend on how the
+            // grammar happens to order its rules.
+            matching_non_terminals
+                .sort_by_cached_key(|(len, nt, item)| (nt.to_string(), *len, *item));
+            panic!(
+                "Ambiguous grammar, multiple applicable rewrites: {}",
+                matching_non_terminals
+                    .into_iter()
+                    .map(|(len, nt, item)| {
+                        format!(
+                            "{nt} => {:?} by {}",
+                            self.stack
+                                .get(self.stack.len().saturating_sub(len)..)
+                                .expect(
+                                    "Stack will at least have length \
+                                of right hand side of rewrite rule"
+                                ),
+                            tables.labels[item]
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )
+        }
+
+        matching_non_terminals.pop()
+    }
+
+    /// Whether the top of the stack spells `symbols`. Terminals compare by
+    /// kind.
+    pub(crate) fn matches_top(&self, symbols: &[Expression]) -> bool {
+        let Some(start) = self.symbols.len().checked_sub(symbols.len()) else {
+            return false;
+        };
+
+        self.symbols[start..]
+            .iter()
+            .zip(symbols)
+            .all(|(left, right)| match (left, right) {
+                (Expression::NonTerminal(left), Expression::NonTerminal(right)) => left == right,
+                (Expression::Terminal(left), Expression::Terminal(right)) => *left == right.kind(),
+                _ => false,
+            })
+    }
+
+    /// Replaces the top `len` stack values with a tree headed by `nt`. The
+    /// stack grows left to right through the input, so the drained values
+    /// are already in source order.
+    pub(crate) fn rewrite<B>(
+        &mut self,
+        tables: &Tables,
+        builder: &mut B,
+        len: usize,
+        nt: NonTerminal,
+    ) where
+        B: TreeBuilder<Value = V>,
+    {
+        let start = self.stack.len().saturating_sub(len);
+        let value = builder.tree(&nt, self.stack.drain(start..));
+        self.symbols.truncate(start);
+        self.states.truncate(start + 1);
+        self.close_groups(start);
+        self.metrics.reductions += 1;
+
+        self.push(tables, value, nt);
+    }
+
+    /// Pops the top `len` stack values.
+    pub(crate) fn pop(&mut self, len: usize) -> Vec<V> {
+        let start = self.stack.len() - len;
+        self.symbols.truncate(start);
+        self.states.truncate(start + 1);
+        self.close_groups(start);
+        self.stack.split_off(start)
+    }
+
+    /// Pushes a tree for `head` built from `values`.
+    pub(crate) fn push_tree<B>(
+        &mut self,
+        tables: &Tables,
+        builder: &mut B,
+        head: NonTerminal,
+        mut values: Vec<V>,
+    ) where
+        B: TreeBuilder<Value = V>,
+    {
+        let value = builder.tree(&head, values.drain(..));
+        self.push(tables, value, head);
+    }
+
+    fn push(&mut self, tables: &Tables, value: V, head: NonTerminal) {
+        let next = tables
+            .table
+            .goto(self.top_state(), &head)
+            .expect("Trees are only pushed where the goto exists");
+
+        self.stack.push(value);
+        self.symbols.push(Expression::NonTerminal(head));
+        self.states.push(next);
+        self.metrics.peak_stack_depth = self.metrics.peak_stack_depth.max(self.stack.len());
+    }
+
+    /// The state reached by shifting `terminal`, if the grammar allows it
+    /// here.
+    fn shift_target(&self, tables: &Tables, terminal: &Terminal) -> Option<usize> {
+        tables
+            .table
+            .actions(self.top_state(), Some(terminal))
+            .iter()
+            .find_map(|action| match action {
+                Action::Shift(target) => Some(*target),
+                _ => None,
+            })
+    }
+
+    pub(crate) fn shift<B>(
+        &mut self,
+        tables: &Tables,
+        builder: &mut B,
+        token: Token,
+        position: usize,
+    ) -> Result<(), ParseError>
+    where
+        B: TreeBuilder<Value = V>,
+    {
+        let Some(target) = self.shift_target(tables, &token.terminal) else {
+            return Err(ParseError::UnexpectedToken {
+                expected: self.expected(tables),
+                found: token.terminal,
+                position,
+                span: token.span,
+            });
+        };
+
+        if self.trace.is_some() {
+            self.record(TraceEvent::Shift(token.terminal.clone()));
+        }
+
+        if opens(&token.terminal) {
+            self.open.push((self.stack.len(), token.span));
+        }
+
+        self.symbols
+            .push(Expression::Terminal(token.terminal.kind()));
+        self.stack.push(builder.leaf(token));
+        self.states.push(target);
+        self.metrics.shifts += 1;
+        self.metrics.peak_stack_depth = self.metrics.peak_stack_depth.max(self.stack.len());
+
+        if self.trace.is_some() {
+            self.record(TraceEvent::Stack(format!("{:?}", self.stack)));
+        }
+
+        Ok(())
+    }
+
+    /// Terminals that could be shifted next, after whatever rewrites they
+    /// would allow.
+    pub(crate) fn expected(&self, tables: &Tables) -> Vec<Terminal> {
+        tables
+            .table
+            .terminals()
+            .iter()
+            .filter(|terminal| {
+                self.trial(tables, terminal)
+                    .shift_target(tables, terminal)
+                    .is_some()
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// A skeleton of the state after the rewrites that `terminal` as the
+    /// lookahead would allow.
+    pub(crate) fn trial(&self, tables: &Tables, terminal: &Terminal) -> ParseState<()> {
+        let mut trial = self.skeleton();
+        while let Some((len, nt, _)) = trial.find_reduction(tables, Some(terminal)) {
+            trial.rewrite(tables, &mut Skeleton, len, nt);
+        }
+        trial
+    }
+
+    /// The state after `terminal` is shifted, with the rewrites it allows
+    /// first, if the grammar allows it here.
+    pub(crate) fn advance(&self, tables: &Tables, terminal: &Terminal) -> Option<ParseState<()>> {
+        let mut next = self.trial(tables, terminal);
+        let target = next.shift_target(tables, terminal)?;
+
+        next.stack.push(());
+        next.symbols.push(Expression::Terminal(terminal.kind()));
+        next.states.push(target);
+
+        Some(next)
+    }
+
+    /// Whether the stack reduces to `start` once the input has run out.
+    pub(crate) fn finishes(&self, tables: &Tables, start: &NonTerminal) -> bool {
+        let mut last = self.skeleton();
+        while let Some((len, nt, _)) = last.find_reduction(tables, None) {
+            last.rewrite(tables, &mut Skeleton, len, nt);
+        }
+        last.accepts(start)
+    }
+
+    /// A copy of the state without any values, which is all the reduction
+    /// loop needs to look at.
+    fn skeleton(&self) -> ParseState<()> {
+        ParseState {
+            stack: vec![(); self.stack.len()],
+            symbols: self.symbols.clone(),
+            states: self.states.clone(),
+            metrics: ParseMetrics::default(),
+            coverage: None,
+            trace: None,
+            max_nesting_depth: None,
+            open: vec![],
+        }
+    }
+
+    /// Whether the stack has been reduced to the starting symbol.
+    pub(crate) fn accepts(&self, starting_symbol: &NonTerminal) -> bool {
+        matches!(
+            self.symbols.as_slice(),
+            [Expression::NonTerminal(head)] if head == starting_symbol
+        )
+    }
+
+    /// The finished tree, if the stack has been reduced to the starting
+    /// symbol. Otherwise the values left on the stack come back for the
+    /// caller to report.
+    pub(crate) fn accept(mut self, starting_symbol: &NonTerminal) -> Result<V, Vec<V>> {
+        if self.accepts(starting_symbol) {
+            Ok(self.stack.pop().expect("Stack has exactly one value"))
         } else {
-            Err(format!("Bad stack: {stack:#?}"))
+            Err(self.stack)
+        }
+    }
+}
+
+impl ParseState {
+    /// The error for a stack that the end of input left unreduced.
+    pub(crate) fn bad_stack(&self) -> ParseError {
+        ParseError::BadStack {
+            stack: self.stack.clone(),
+            next: None,
         }
     }
 }
@@ -0,0 +1,198 @@
+use std::{collections::HashSet, fmt};
+
+use crate::{Expression, Grammar, GrammarError, NonTerminal};
+
+/// How much a [`Lint`] matters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Only matters to some backends, such as predictive parsers.
+    Note,
+    /// Part of the grammar is dead weight, but parsing works.
+    Warning,
+    /// Parsing can fail or panic because of it.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Note => write!(f, "note"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A suspicious pattern in a grammar, found by [`Grammar::lint`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Lint {
+    fn new(severity: Severity, message: impl fmt::Display) -> Self {
+        Lint {
+            severity,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Symbols the way [`Grammar`]'s `Display` writes them.
+fn symbols(exprs: &[Expression]) -> String {
+    exprs
+        .iter()
+        .map(|expr| match expr {
+            Expression::Terminal(t) => format!("'{t}'"),
+            Expression::NonTerminal(nt) => nt.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+impl Grammar {
+    /// Every suspicious pattern in the grammar, as one health check.
+    ///
+    /// Lints come in this order: nonterminals used without rules,
+    /// unreachable and non-productive nonterminals, cycles of unit
+    /// productions, and productions listed twice for the same nonterminal,
+    /// which make the reduction loop panic as ambiguous; then, as notes,
+    /// left recursion and alternatives sharing a prefix, which only a
+    /// predictive parser would have trouble with. Within each kind,
+    /// nonterminals follow the order the rules reach them.
+    pub fn lint(&self) -> Vec<Lint> {
+        let (order, _) = self.symbols_in_order();
+        let mut lints = vec![];
+
+        for undefined in order.iter().filter(|nt| !self.rules.contains_key(nt)) {
+            lints.push(Lint::new(
+                Severity::Error,
+                GrammarError::Undefined(undefined.clone()),
+            ));
+        }
+
+        let reachable = self.reachable();
+        let unreachable: Vec<NonTerminal> = order
+            .iter()
+            .filter(|nt| !reachable.contains(nt))
+            .cloned()
+            .collect();
+        if !unreachable.is_empty() {
+            lints.push(Lint::new(
+                Severity::Warning,
+                GrammarError::Unreachable(unreachable),
+            ));
+        }
+
+        let productive = self.shortest_sentences();
+        let non_productive: Vec<NonTerminal> = order
+            .iter()
+            .filter(|nt| self.rules.contains_key(nt) && !productive.contains_key(nt))
+            .cloned()
+            .collect();
+        if !non_productive.is_empty() {
+            lints.push(Lint::new(
+                Severity::Warning,
+                GrammarError::NonProductive(non_productive),
+            ));
+        }
+
+        for cycle in self.unit_cycles() {
+            let path: Vec<String> = cycle
+                .iter()
+                .chain(&cycle[..1])
+                .map(NonTerminal::to_string)
+                .collect();
+            lints.push(Lint::new(
+                Severity::Error,
+                format!("Unit productions form a cycle: {}", path.join(" -> ")),
+            ));
+        }
+
+        for non_terminal in &order {
+            let mut seen: Vec<&Vec<Expression>> = vec![];
+            for production in self.rules.get(non_terminal).into_iter().flatten() {
+                if seen.contains(&production) {
+                    lints.push(Lint::new(
+                        Severity::Error,
+                        format!(
+                            "Production {non_terminal} -> {} is listed more than once",
+                            symbols(production)
+                        ),
+                    ));
+                } else {
+                    seen.push(production);
+                }
+            }
+        }
+
+        for non_terminal in self.left_recursive() {
+            lints.push(Lint::new(
+                Severity::Note,
+                format!("Nonterminal {non_terminal} is left recursive"),
+            ));
+        }
+
+        for (non_terminal, prefix) in self.common_prefixes() {
+            lints.push(Lint::new(
+                Severity::Note,
+                format!(
+                    "Alternatives of {non_terminal} share the prefix {}",
+                    symbols(&prefix)
+                ),
+            ));
+        }
+
+        lints
+    }
+
+    /// Nonterminals that can derive a sequence starting with themselves, in
+    /// discovery order.
+    fn left_recursive(&self) -> Vec<NonTerminal> {
+        let nullable = self.nullable_nonterminals();
+
+        // Nonterminals that can start a sentential form of `nt` in one step.
+        let leading = |nt: &NonTerminal| -> Vec<&NonTerminal> {
+            let mut leading = vec![];
+            for production in self.rules.get(nt).into_iter().flatten() {
+                for expr in production {
+                    let Expression::NonTerminal(symbol) = expr else {
+                        break;
+                    };
+                    leading.push(symbol);
+                    if !nullable.contains(symbol) {
+                        break;
+                    }
+                }
+            }
+            leading
+        };
+
+        self.symbols_in_order()
+            .0
+            .into_iter()
+            .filter(|start| {
+                let mut seen = HashSet::new();
+                let mut pending = leading(start);
+
+                while let Some(nt) = pending.pop() {
+                    if nt == start {
+                        return true;
+                    }
+                    if seen.insert(nt) {
+                        pending.extend(leading(nt));
+                    }
+                }
+
+                false
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{ParseError, Parser, Severity, Span, StackValue};
+
+/// A place in the input as the Language Server Protocol counts it: a
+/// zero-based line, and a zero-based column in UTF-16 code units, so a char
+/// outside the Basic Multilingual Plane such as an emoji is two columns
+/// wide. Lines end at `\n`; a `\r` before it counts as part of the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LspPosition {
+    pub line: u32,
+    pub character: u32,
+}
+
+impl LspPosition {
+    /// The position of byte `offset` of `source`. Offsets past the end are
+    /// taken as the end, and offsets inside a char as the start of it.
+    pub fn from_offset(source: &str, offset: usize) -> LspPosition {
+        let mut offset = offset.min(source.len());
+        while !source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        LspPosition {
+            line: before.matches('\n').count() as u32,
+            character: before[line_start..].encode_utf16().count() as u32,
+        }
+    }
+}
+
+/// The stretch of input from `start` up to but not including `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LspRange {
+    pub start: LspPosition,
+    pub end: LspPosition,
+}
+
+impl LspRange {
+    pub fn from_span(source: &str, span: Span) -> LspRange {
+        LspRange {
+            start: LspPosition::from_offset(source, span.start),
+            end: LspPosition::from_offset(source, span.end),
+        }
+    }
+}
+
+/// A problem in the input in the shape of an LSP `Diagnostic`, ready to be
+/// copied field by field into the protocol's own struct.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LspDiagnostic {
+    pub range: LspRange,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl LspDiagnostic {
+    /// `error` at its place in `source`. Errors without a span, such as
+    /// [`ParseError::UnexpectedEnd`], get an empty range just past the last
+    /// non-whitespace character, where the missing input would go.
+    pub fn from_error(source: &str, error: &ParseError) -> LspDiagnostic {
+        let span = error.span().unwrap_or_else(|| {
+            let end = source.trim_end().len();
+            Span { start: end, end }
+        });
+
+        LspDiagnostic {
+            range: LspRange::from_span(source, span),
+            severity: Severity::Error,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl Parser {
+    /// Like [`Parser::parse_recovering`], for a language server: the
+    /// recovered tree, if any, and every error as an [`LspDiagnostic`]
+    /// whose columns count UTF-16 code units, as the protocol requires by
+    /// default. Every parse error has [`Severity::Error`].
+    pub fn parse_lsp(&self, input: &str) -> (Option<StackValue>, Vec<LspDiagnostic>) {
+        let (tree, errors) = self.parse_recovering(input);
+        let diagnostics = errors
+            .iter()
+            .map(|error| LspDiagnostic::from_error(input, error))
+            .collect();
+
+        (tree, diagnostics)
+    }
+}
@@ -0,0 +1,560 @@
+//! A lexer built from patterns, one per terminal kind, the way lexer
+//! generators work.
+//!
+//! Patterns are a small regular expression language: literal characters,
+//! `.` for any character but a line break, classes such as `[a-z_]` and
+//! `[^"]`, the escapes `\d`, `\w`, `\s`, `\n`, `\t` and `\r`, groups, `|`,
+//! and the quantifiers `*`, `+` and `?`. A backslash before any other
+//! character matches that character, so `\+` matches `+`. Patterns are
+//! matched with a Thompson NFA, so matching takes time linear in the input
+//! whatever the pattern.
+
+use std::{fmt, iter::Peekable, str::CharIndices};
+
+use crate::{FloatLiteral, Grammar, Lexer, ParseError, Span, Terminal, Token, TokenizeError};
+
+/// Why a pattern given to [`TokenizerConfig::with_pattern`] or
+/// [`Grammar::accepts_both`] was rejected. Offsets count bytes from the
+/// start of the pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+    /// A `(` with no `)` after it, or a `)` with no `(` before it.
+    UnbalancedGroup { offset: usize },
+    /// A `[` with no `]` after it.
+    UnterminatedClass { offset: usize },
+    /// A quantifier with nothing before it to repeat.
+    MissingOperand { offset: usize },
+    /// A range in a class that ends before it starts, such as `z-a`.
+    InvalidRange { offset: usize },
+    /// A backslash at the end of the pattern.
+    DanglingEscape { offset: usize },
+    /// The pattern matches the empty string, so a token could be found
+    /// without consuming any input.
+    MatchesEmpty,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::UnbalancedGroup { offset } => {
+                write!(f, "Unbalanced group at byte {offset} of the pattern")
+            }
+            PatternError::UnterminatedClass { offset } => {
+                write!(f, "Unterminated class at byte {offset} of the pattern")
+            }
+            PatternError::MissingOperand { offset } => {
+                write!(f, "Nothing to repeat at byte {offset} of the pattern")
+            }
+            PatternError::InvalidRange { offset } => {
+                write!(f, "Invalid range at byte {offset} of the pattern")
+            }
+            PatternError::DanglingEscape { offset } => {
+                write!(f, "Dangling escape at byte {offset} of the pattern")
+            }
+            PatternError::MatchesEmpty => write!(f, "Pattern matches the empty string"),
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+/// Splits input by patterns, one per terminal kind.
+///
+/// At each point in the input every pattern is tried and the longest match
+/// wins, ties going to the pattern added first. Whitespace that no pattern
+/// matches separates tokens. Tokens of [`Terminal::StringLiteral`],
+/// [`Terminal::Identifier`] and [`Terminal::Whitespace`] hold the text they
+/// matched, and tokens of [`Terminal::Int`] and [`Terminal::Float`] the
+/// value it spells; other kinds have no text to hold. Tokens carry no
+/// trivia.
+///
+/// The fixed operators of [`Tokenizer`](crate::Tokenizer) are the
+/// degenerate case of a pattern that matches one string, such as `\+` for
+/// [`Terminal::Plus`].
+#[derive(Debug, Clone, Default)]
+pub struct TokenizerConfig {
+    patterns: Vec<(Terminal, Pattern)>,
+}
+
+impl TokenizerConfig {
+    pub fn new() -> Self {
+        TokenizerConfig { patterns: vec![] }
+    }
+
+    /// Adds `pattern` as the pattern for `kind`. Any payload of `kind` is
+    /// ignored.
+    pub fn with_pattern(mut self, kind: Terminal, pattern: &str) -> Result<Self, PatternError> {
+        let pattern = Pattern::new(pattern)?;
+        if pattern.longest_match("") == Some(0) {
+            return Err(PatternError::MatchesEmpty);
+        }
+
+        self.patterns.push((kind.kind(), pattern));
+        Ok(self)
+    }
+
+    /// Tokens of `input`. Text that no pattern matches ends the input with
+    /// [`TokenizeError::InvalidToken`], covering it up to the next
+    /// whitespace, and text matched as an [`Terminal::Int`] or
+    /// [`Terminal::Float`] that does not parse as an `i64` or `f64` with
+    /// [`TokenizeError::MalformedNumber`], covering the match.
+    pub fn tokenize(&self, input: &str) -> Result<Vec<Token>, TokenizeError> {
+        let mut tokens = vec![];
+        let mut start = 0;
+
+        while let Some(c) = input[start..].chars().next() {
+            let rest = &input[start..];
+            let longest = self
+                .patterns
+                .iter()
+                .filter_map(|(kind, pattern)| Some((pattern.longest_match(rest)?, kind)))
+                .fold(None, |longest, (length, kind)| match longest {
+                    Some((best, _)) if best >= length => longest,
+                    _ => Some((length, kind)),
+                });
+
+            match longest {
+                Some((length, kind)) => {
+                    let text = rest[..length].to_string();
+                    let end = start + length;
+                    let span = Span { start, end };
+                    let malformed =
+                        |text: String| TokenizeError::MalformedNumber { number: text, span };
+
+                    let terminal = match kind {
+                        Terminal::StringLiteral(_) => Terminal::StringLiteral(text),
+                        Terminal::Identifier(_) => Terminal::Identifier(text),
+                        Terminal::Whitespace(_) => Terminal::Whitespace(text),
+                        Terminal::Int(_) => match text.parse() {
+                            Ok(value) => Terminal::Int(Some(value)),
+                            Err(_) => return Err(malformed(text)),
+                        },
+                        Terminal::Float(_) => match text.parse() {
+                            Ok(value) => Terminal::Float(Some(FloatLiteral(value))),
+                            Err(_) => return Err(malformed(text)),
+                        },
+                        kind => kind.clone(),
+                    };
+                    tokens.push(Token::new(terminal, span));
+                    start = end;
+                }
+                None if c.is_whitespace() => start += c.len_utf8(),
+                None => {
+                    let length = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                    return Err(TokenizeError::InvalidToken {
+                        token: rest[..length].to_string(),
+                        span: Span {
+                            start,
+                            end: start + length,
+                        },
+                    });
+                }
+            }
+        }
+
+        Ok(tokens)
+    }
+}
+
+impl Lexer for TokenizerConfig {
+    fn terminal_kinds(&self) -> Option<Vec<Terminal>> {
+        let mut kinds: Vec<Terminal> = vec![];
+        for (kind, _) in &self.patterns {
+            if !kinds.contains(kind) {
+                kinds.push(kind.clone());
+            }
+        }
+
+        Some(kinds)
+    }
+
+    fn tokens(&self, input: &str) -> Result<Vec<Terminal>, ParseError> {
+        Ok(self
+            .tokenize(input)?
+            .into_iter()
+            .map(|token| token.terminal)
+            .collect())
+    }
+
+    fn lex(&self, input: &str) -> Result<Vec<Token>, ParseError> {
+        Ok(self.tokenize(input)?)
+    }
+}
+
+/// Characters matched by one step of a pattern: those in `ranges`, or
+/// those outside them when `negated`.
+#[derive(Debug, Clone)]
+struct Class {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        self.ranges.iter().any(|&(low, high)| low <= c && c <= high) != self.negated
+    }
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+
+/// One character or escape of a pattern.
+enum Member {
+    Char(char),
+    Set(&'static [(char, char)]),
+}
+
+impl Member {
+    fn ranges(&self) -> Vec<(char, char)> {
+        match self {
+            Member::Char(c) => vec![(*c, *c)],
+            Member::Set(ranges) => ranges.to_vec(),
+        }
+    }
+}
+
+enum Node {
+    Class(Class),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Star(Box<Node>),
+    Plus(Box<Node>),
+    Optional(Box<Node>),
+}
+
+/// Reads a pattern into a [`Node`] by recursive descent.
+struct Reader<'a> {
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Reader<'_> {
+    fn eat(&mut self, expected: char) -> bool {
+        self.chars.next_if(|&(_, c)| c == expected).is_some()
+    }
+
+    fn alternate(&mut self) -> Result<Node, PatternError> {
+        let mut branches = vec![self.concat()?];
+        while self.eat('|') {
+            branches.push(self.concat()?);
+        }
+
+        Ok(match branches.len() {
+            1 => branches.remove(0),
+            _ => Node::Alternate(branches),
+        })
+    }
+
+    fn concat(&mut self) -> Result<Node, PatternError> {
+        let mut items = vec![];
+
+        while let Some(&(offset, c)) = self.chars.peek() {
+            match c {
+                '|' | ')' => break,
+                '*' | '+' | '?' => {
+                    self.chars.next();
+                    let item =
+                        Box::new(items.pop().ok_or(PatternError::MissingOperand { offset })?);
+                    items.push(match c {
+                        '*' => Node::Star(item),
+                        '+' => Node::Plus(item),
+                        _ => Node::Optional(item),
+                    });
+                }
+                _ => items.push(self.atom()?),
+            }
+        }
+
+        Ok(Node::Concat(items))
+    }
+
+    fn atom(&mut self) -> Result<Node, PatternError> {
+        let Some((offset, c)) = self.chars.next() else {
+            unreachable!("atoms are only read after peeking a character");
+        };
+
+        let ranges = match c {
+            '(' => {
+                let inner = self.alternate()?;
+                if !self.eat(')') {
+                    return Err(PatternError::UnbalancedGroup { offset });
+                }
+                return Ok(inner);
+            }
+            '[' => return self.class(offset).map(Node::Class),
+            '.' => {
+                return Ok(Node::Class(Class {
+                    ranges: vec![('\n', '\n')],
+                    negated: true,
+                }));
+            }
+            '\\' => self.escape(offset)?.ranges(),
+            c => vec![(c, c)],
+        };
+
+        Ok(Node::Class(Class {
+            ranges,
+            negated: false,
+        }))
+    }
+
+    fn escape(&mut self, offset: usize) -> Result<Member, PatternError> {
+        let (_, c) = self
+            .chars
+            .next()
+            .ok_or(PatternError::DanglingEscape { offset })?;
+
+        Ok(match c {
+            'd' => Member::Set(DIGIT),
+            'w' => Member::Set(WORD),
+            's' => Member::Set(SPACE),
+            'n' => Member::Char('\n'),
+            't' => Member::Char('\t'),
+            'r' => Member::Char('\r'),
+            c => Member::Char(c),
+        })
+    }
+
+    /// A class whose `[` is at `open`. A `]` right after the `[` or `[^`
+    /// is a member, as is a `-` that cannot be part of a range.
+    fn class(&mut self, open: usize) -> Result<Class, PatternError> {
+        let negated = self.eat('^');
+        let mut ranges = vec![];
+        let mut first = true;
+
+        loop {
+            let (offset, c) = self
+                .chars
+                .next()
+                .ok_or(PatternError::UnterminatedClass { offset: open })?;
+            if c == ']' && !first {
+                return Ok(Class { ranges, negated });
+            }
+            first = false;
+
+            let member = match c {
+                '\\' => self.escape(offset)?,
+                c => Member::Char(c),
+            };
+            let Member::Char(low) = member else {
+                ranges.extend(member.ranges());
+                continue;
+            };
+
+            let mut lookahead = self.chars.clone();
+            let is_range = lookahead.next().is_some_and(|(_, c)| c == '-')
+                && lookahead.next().is_some_and(|(_, c)| c != ']');
+            if !is_range {
+                ranges.push((low, low));
+                continue;
+            }
+
+            self.chars.next();
+            let (end, c) = self
+                .chars
+                .next()
+                .ok_or(PatternError::UnterminatedClass { offset: open })?;
+            let high = match c {
+                '\\' => match self.escape(end)? {
+                    Member::Char(high) => high,
+                    Member::Set(_) => return Err(PatternError::InvalidRange { offset }),
+                },
+                c => c,
+            };
+            if high < low {
+                return Err(PatternError::InvalidRange { offset });
+            }
+            ranges.push((low, high));
+        }
+    }
+}
+
+impl Grammar {
+    /// Whether `input` is both a sentence of the grammar and matched in
+    /// full by `pattern`, which may match the empty string. `input` is
+    /// split by [`Tokenizer`](crate::Tokenizer), and the pattern sees the
+    /// text as written.
+    pub fn accepts_both(&self, pattern: &str, input: &str) -> Result<bool, PatternError> {
+        let pattern = Pattern::new(pattern)?;
+
+        Ok(pattern.matches(input)
+            && self
+                .count_derivations(input, 1)
+                .is_ok_and(|count| count > 0))
+    }
+
+    /// The sentences of at most `max_len` terminals that `pattern` also
+    /// matches, shortest first, as a bounded intersection of the grammar
+    /// with a regular language. Each sentence is matched as it would be
+    /// written for [`Grammar::accepts_both`]: its terminals, by kind,
+    /// separated by single spaces. Sentences longer than `max_len` are
+    /// never looked at, so an empty result only means there are none that
+    /// short.
+    pub fn intersect_regular(
+        &self,
+        pattern: &str,
+        max_len: usize,
+    ) -> Result<Vec<Vec<Terminal>>, PatternError> {
+        let pattern = Pattern::new(pattern)?;
+
+        let mut sentences: Vec<Vec<Terminal>> = self
+            .sentences_up_to(max_len)
+            .into_iter()
+            .filter(|sentence| {
+                let words: Vec<String> = sentence.iter().map(Terminal::to_string).collect();
+                pattern.matches(&words.join(" "))
+            })
+            .collect();
+        sentences.sort_by_cached_key(|sentence| {
+            (
+                sentence.len(),
+                sentence
+                    .iter()
+                    .map(Terminal::to_string)
+                    .collect::<Vec<String>>(),
+            )
+        });
+
+        Ok(sentences)
+    }
+}
+
+/// A step of the NFA. `Split` moves to both states without reading a
+/// character.
+#[derive(Debug, Clone)]
+enum State {
+    Class(Class, usize),
+    Split(usize, usize),
+    Match,
+}
+
+/// A compiled pattern: its NFA states and the state it starts in.
+#[derive(Debug, Clone)]
+struct Pattern {
+    states: Vec<State>,
+    start: usize,
+}
+
+impl Pattern {
+    fn new(pattern: &str) -> Result<Self, PatternError> {
+        let mut reader = Reader {
+            chars: pattern.char_indices().peekable(),
+        };
+        let node = reader.alternate()?;
+        // `alternate` only stops early at a `)` it did not open.
+        if let Some((offset, _)) = reader.chars.next() {
+            return Err(PatternError::UnbalancedGroup { offset });
+        }
+
+        let mut states = vec![State::Match];
+        let start = compile(&node, 0, &mut states);
+        Ok(Pattern { states, start })
+    }
+
+    /// Whether the pattern matches all of `text`.
+    fn matches(&self, text: &str) -> bool {
+        self.longest_match(text) == Some(text.len())
+    }
+
+    /// The length in bytes of the longest prefix of `text` the pattern
+    /// matches.
+    fn longest_match(&self, text: &str) -> Option<usize> {
+        let mut seen = vec![false; self.states.len()];
+        let mut current = vec![];
+        self.add(self.start, &mut current, &mut seen);
+
+        let mut longest = None;
+        let mut position = 0;
+        let mut chars = text.chars();
+        loop {
+            if current
+                .iter()
+                .any(|&state| matches!(self.states[state], State::Match))
+            {
+                longest = Some(position);
+            }
+            let Some(c) = chars.next() else {
+                break;
+            };
+
+            seen.fill(false);
+            let mut next = vec![];
+            for &state in &current {
+                if let State::Class(class, to) = &self.states[state]
+                    && class.matches(c)
+                {
+                    self.add(*to, &mut next, &mut seen);
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            current = next;
+            position += c.len_utf8();
+        }
+
+        longest
+    }
+
+    /// Adds `state` and every state it splits into to `set`, skipping
+    /// states already `seen`. Only `Class` and `Match` states are added.
+    fn add(&self, state: usize, set: &mut Vec<usize>, seen: &mut [bool]) {
+        let mut pending = vec![state];
+
+        while let Some(state) = pending.pop() {
+            if std::mem::replace(&mut seen[state], true) {
+                continue;
+            }
+            match self.states[state] {
+                State::Split(first, second) => {
+                    pending.push(second);
+                    pending.push(first);
+                }
+                _ => set.push(state),
+            }
+        }
+    }
+}
+
+/// Compiles `node` into `states`, continuing at `next` once it has
+/// matched, and returns the state it starts in.
+fn compile(node: &Node, next: usize, states: &mut Vec<State>) -> usize {
+    let push = |states: &mut Vec<State>, state| {
+        states.push(state);
+        states.len() - 1
+    };
+
+    match node {
+        Node::Class(class) => push(states, State::Class(class.clone(), next)),
+        Node::Concat(items) => items
+            .iter()
+            .rev()
+            .fold(next, |next, item| compile(item, next, states)),
+        Node::Alternate(branches) => {
+            let starts: Vec<usize> = branches
+                .iter()
+                .map(|branch| compile(branch, next, states))
+                .collect();
+            starts
+                .into_iter()
+                .rev()
+                .reduce(|rest, first| push(states, State::Split(first, rest)))
+                .unwrap_or(next)
+        }
+        Node::Optional(item) => {
+            let body = compile(item, next, states);
+            push(states, State::Split(body, next))
+        }
+        Node::Star(item) | Node::Plus(item) => {
+            // The loop state is patched once the body, which returns to
+            // it, has been compiled.
+            let repeat = push(states, State::Split(next, next));
+            let body = compile(item, repeat, states);
+            states[repeat] = State::Split(body, next);
+            match node {
+                Node::Star(_) => repeat,
+                _ => body,
+            }
+        }
+    }
+}
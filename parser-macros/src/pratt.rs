@@ -0,0 +1,212 @@
+use std::{iter::Peekable, vec::IntoIter};
+
+use crate::{NonTerminal, ParseError, Parser, StackValue, Terminal, Token};
+
+/// Operators and operands for [`Parser::parse_pratt`].
+///
+/// Infix operators have a left and a right binding power. An operator
+/// whose left power is lower than its right one is left associative
+/// (`a - b - c` is `(a - b) - c`), and one whose left power is higher is
+/// right associative. Operators with higher powers bind tighter. Every
+/// tree built has the same `head`.
+#[derive(Debug, Clone)]
+pub struct PrecedenceTable {
+    head: NonTerminal,
+    atoms: Vec<Terminal>,
+    groups: Vec<(Terminal, Terminal)>,
+    prefix: Vec<(Terminal, u8)>,
+    infix: Vec<(Terminal, u8, u8)>,
+}
+
+impl PrecedenceTable {
+    pub fn new(head: NonTerminal) -> Self {
+        PrecedenceTable {
+            head,
+            atoms: vec![],
+            groups: vec![],
+            prefix: vec![],
+            infix: vec![],
+        }
+    }
+
+    /// A terminal that is an operand on its own, such as `0`.
+    pub fn atom(mut self, terminal: Terminal) -> Self {
+        self.atoms.push(terminal.kind());
+        self
+    }
+
+    /// Brackets around a nested expression, such as `(` and `)`.
+    pub fn group(mut self, open: Terminal, close: Terminal) -> Self {
+        self.groups.push((open.kind(), close.kind()));
+        self
+    }
+
+    /// An operator before its operand, binding it with `power`.
+    pub fn prefix(mut self, terminal: Terminal, power: u8) -> Self {
+        self.prefix.push((terminal.kind(), power));
+        self
+    }
+
+    /// An operator between two operands, with its left and right binding
+    /// powers.
+    pub fn infix(mut self, terminal: Terminal, left: u8, right: u8) -> Self {
+        self.infix.push((terminal.kind(), left, right));
+        self
+    }
+
+    /// Terminals that can start an operand.
+    fn operand_starts(&self) -> Vec<Terminal> {
+        self.atoms
+            .iter()
+            .chain(self.groups.iter().map(|(open, _)| open))
+            .chain(self.prefix.iter().map(|(t, _)| t))
+            .cloned()
+            .collect()
+    }
+
+    /// Terminals that can follow an operand, `close` being the bracket of
+    /// the group it is in.
+    fn operand_follows(&self, close: Option<&Terminal>) -> Vec<Terminal> {
+        self.infix
+            .iter()
+            .map(|(t, _, _)| t)
+            .chain(close)
+            .cloned()
+            .collect()
+    }
+}
+
+impl Parser {
+    /// Parses `input` with operator precedence instead of the grammar's
+    /// rules, building trees headed by the table's nonterminal: a leaf
+    /// operand as `head -> atom`, a group as `head -> open head close`,
+    /// and operators as `head -> op head` or `head -> head op head`.
+    pub fn parse_pratt(
+        &self,
+        input: &str,
+        table: &PrecedenceTable,
+    ) -> Result<StackValue, ParseError> {
+        let tokens: Vec<(usize, Token)> = self
+            .lexer
+            .lex(input)?
+            .into_iter()
+            .enumerate()
+            .filter(|(_, token)| !self.skips(&token.terminal))
+            .collect();
+
+        let mut pratt = Pratt {
+            table,
+            tokens: tokens.into_iter().peekable(),
+        };
+
+        let tree = pratt.expression(0, None)?;
+
+        match pratt.tokens.next() {
+            Some((position, token)) => {
+                Err(unexpected(position, token, table.operand_follows(None)))
+            }
+            None => Ok(tree),
+        }
+    }
+}
+
+struct Pratt<'a> {
+    table: &'a PrecedenceTable,
+    tokens: Peekable<IntoIter<(usize, Token)>>,
+}
+
+impl Pratt<'_> {
+    /// An expression whose operators all bind at least as tightly as
+    /// `min_power`, stopping before `close`.
+    fn expression(
+        &mut self,
+        min_power: u8,
+        close: Option<&Terminal>,
+    ) -> Result<StackValue, ParseError> {
+        let mut lhs = self.operand()?;
+
+        while let Some((_, token)) = self.tokens.peek() {
+            let kind = token.terminal.kind();
+            if Some(&kind) == close {
+                break;
+            }
+
+            let Some((_, left, right)) = self.table.infix.iter().find(|(t, _, _)| *t == kind)
+            else {
+                let (position, token) = self.tokens.next().expect("Just peeked");
+                return Err(unexpected(
+                    position,
+                    token,
+                    self.table.operand_follows(close),
+                ));
+            };
+
+            if *left < min_power {
+                break;
+            }
+
+            let (_, operator) = self.tokens.next().expect("Just peeked");
+            let rhs = self.expression(*right, close)?;
+
+            lhs = self.tree(vec![lhs, StackValue::Terminal(operator), rhs]);
+        }
+
+        Ok(lhs)
+    }
+
+    fn operand(&mut self) -> Result<StackValue, ParseError> {
+        let Some((position, token)) = self.tokens.next() else {
+            return Err(ParseError::UnexpectedEnd {
+                expected: self.table.operand_starts(),
+            });
+        };
+        let kind = token.terminal.kind();
+
+        if self.table.atoms.contains(&kind) {
+            return Ok(self.tree(vec![StackValue::Terminal(token)]));
+        }
+
+        if let Some((_, power)) = self.table.prefix.iter().find(|(t, _)| *t == kind) {
+            let operand = self.expression(*power, None)?;
+            return Ok(self.tree(vec![StackValue::Terminal(token), operand]));
+        }
+
+        if let Some((_, close)) = self.table.groups.iter().find(|(open, _)| *open == kind) {
+            let inner = self.expression(0, Some(close))?;
+
+            return match self.tokens.next() {
+                Some((_, closing)) if closing.terminal.kind() == *close => Ok(self.tree(vec![
+                    StackValue::Terminal(token),
+                    inner,
+                    StackValue::Terminal(closing),
+                ])),
+                Some((position, other)) => Err(unexpected(
+                    position,
+                    other,
+                    self.table.operand_follows(Some(close)),
+                )),
+                None => Err(ParseError::UnexpectedEnd {
+                    expected: self.table.operand_follows(Some(close)),
+                }),
+            };
+        }
+
+        Err(unexpected(position, token, self.table.operand_starts()))
+    }
+
+    fn tree(&self, values: Vec<StackValue>) -> StackValue {
+        StackValue::Tree {
+            head: self.table.head.clone(),
+            values,
+        }
+    }
+}
+
+fn unexpected(position: usize, token: Token, expected: Vec<Terminal>) -> ParseError {
+    ParseError::UnexpectedToken {
+        found: token.terminal,
+        position,
+        span: token.span,
+        expected,
+    }
+}
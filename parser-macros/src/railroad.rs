@@ -0,0 +1,129 @@
+use std::fmt::Write;
+
+use crate::{Expression, Grammar};
+
+/// Height of one alternative's row.
+const ROW: usize = 40;
+/// Height of a symbol's box.
+const BOX: usize = 24;
+/// Space between boxes, and around the rails.
+const GAP: usize = 20;
+/// Width of one char of a label, for the monospace font used.
+const CHAR: usize = 7;
+
+impl Grammar {
+    /// Renders the grammar as railroad diagrams in one SVG document, a
+    /// diagram per nonterminal with rules in [`Grammar::symbols_in_order`]
+    /// order. Each alternative is a track of boxes, left to right, and the
+    /// tracks of one nonterminal branch off a shared rail as a choice. An
+    /// empty alternative is a track with nothing on it. Terminals are
+    /// rounded boxes and nonterminals square ones, as is usual.
+    pub fn to_railroad_diagram(&self) -> String {
+        let mut body = String::new();
+        let mut top = 0;
+        let mut width = 0;
+
+        for non_terminal in self.symbols_in_order().0 {
+            let Some(alternatives) = self.rules.get(&non_terminal) else {
+                continue;
+            };
+
+            writeln!(body, "<g>").expect("Writing to a String cannot fail");
+            text(&mut body, GAP / 2, top + GAP, &format!("{non_terminal}:"));
+
+            // Each track is laid out first, so the rail on the right can go
+            // past the longest.
+            let tracks: Vec<Vec<(usize, String, bool)>> = alternatives
+                .iter()
+                .map(|production| {
+                    let mut x = 2 * GAP;
+                    production
+                        .iter()
+                        .map(|expr| {
+                            let (label, terminal) = match expr {
+                                Expression::Terminal(t) => (format!("'{t}'"), true),
+                                Expression::NonTerminal(nt) => (nt.to_string(), false),
+                            };
+                            let start = x;
+                            x += label_width(&label) + GAP;
+                            (start, label, terminal)
+                        })
+                        .collect()
+                })
+                .collect();
+            let rail = tracks
+                .iter()
+                .filter_map(|track| track.last())
+                .map(|(x, label, _)| x + label_width(label) + GAP)
+                .max()
+                .unwrap_or(0)
+                .max(3 * GAP);
+
+            let first = top + ROW;
+            let last = first + ROW * (alternatives.len().max(1) - 1);
+            line(&mut body, GAP / 2, first, GAP, first);
+            line(&mut body, GAP, first, GAP, last);
+            line(&mut body, rail, first, rail, last);
+            line(&mut body, rail, first, rail + GAP / 2, first);
+
+            for (row, track) in tracks.iter().enumerate() {
+                let y = first + row * ROW;
+                let mut x = GAP;
+
+                for (start, label, terminal) in track {
+                    line(&mut body, x, y, *start, y);
+                    let box_width = label_width(label);
+                    writeln!(
+                        body,
+                        r#"<rect x="{start}" y="{}" width="{box_width}" height="{BOX}" rx="{}" fill="none" stroke="black"/>"#,
+                        y - BOX / 2,
+                        if *terminal { BOX / 2 } else { 0 },
+                    )
+                    .expect("Writing to a String cannot fail");
+                    text(&mut body, start + GAP / 2, y + 4, label);
+                    x = start + box_width;
+                }
+
+                line(&mut body, x, y, rail, y);
+            }
+
+            writeln!(body, "</g>").expect("Writing to a String cannot fail");
+            width = width.max(rail + GAP);
+            top = last + ROW / 2;
+        }
+
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{top}\" \
+            font-family=\"monospace\" font-size=\"12\">\n{body}</svg>\n"
+        )
+    }
+}
+
+/// The width of the box around `label`.
+fn label_width(label: &str) -> usize {
+    label.chars().count() * CHAR + GAP
+}
+
+fn line(out: &mut String, x1: usize, y1: usize, x2: usize, y2: usize) {
+    writeln!(
+        out,
+        r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="black"/>"#
+    )
+    .expect("Writing to a String cannot fail");
+}
+
+fn text(out: &mut String, x: usize, y: usize, content: &str) {
+    let mut escaped = String::new();
+    for c in content.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+
+    writeln!(out, r#"<text x="{x}" y="{y}">{escaped}</text>"#)
+        .expect("Writing to a String cannot fail");
+}
@@ -0,0 +1,302 @@
+use std::collections::HashSet;
+
+use crate::{NonTerminal, ParseError, ParseState, Parser, StackValue, Tables, Terminal, Token};
+
+/// How many tokens have to be shifted after a recovery before another
+/// parse error is reported. Errors sooner than that are most likely
+/// knock-on effects of the one recovered from.
+const RECOVERY_WINDOW: usize = 3;
+
+impl Parser {
+    /// Parses `input`, recovering from errors so that every error in the
+    /// input is reported rather than just the first.
+    ///
+    /// Recovery is panic mode. On an error, values are popped off the stack
+    /// until some nonterminal could be pushed in their place, and input
+    /// tokens are discarded until one that can follow that nonterminal (its
+    /// FOLLOW set is the synchronization set). The nonterminal is pushed as
+    /// a tree holding the popped values and discarded tokens, and parsing
+    /// resumes from the synchronizing token. Input that does not tokenize
+    /// is reported and skipped.
+    ///
+    /// The tree is returned when the recovered stack still reduces to the
+    /// starting symbol; it contains the error trees described above. If it
+    /// does not at the end of input, the whole stack is wrapped in one error
+    /// tree for the starting symbol.
+    ///
+    /// Parse errors found before three tokens have been shifted since the
+    /// last recovery are dropped, since one mistake often derails the next
+    /// few tokens too. Tokenize errors are always kept. The errors come
+    /// sorted by where they are in the input, errors at the end of input
+    /// last, without duplicates.
+    ///
+    /// Nesting deeper than [`Parser::set_max_nesting_depth`] allows is not
+    /// recovered from: the error is reported and no tree is returned.
+    pub fn parse_recovering(&self, input: &str) -> (Option<StackValue>, Vec<ParseError>) {
+        self.recover(input, None)
+    }
+
+    /// Like [`Parser::parse_recovering`], but only ever resumes at a token
+    /// in `sync`, such as `)` or a statement separator, or at the end of
+    /// input, so the grammar's author decides where recovery picks up
+    /// rather than the FOLLOW sets. On an error every token up to the next
+    /// one in `sync` is discarded, and values are popped off the stack
+    /// until a nonterminal that the sync token can follow can be pushed in
+    /// their place. A sync token that no such nonterminal can come before
+    /// is discarded too. Terminals in `sync` are compared by
+    /// [`Terminal::kind`].
+    pub fn parse_with_recovery_points(
+        &self,
+        input: &str,
+        sync: &HashSet<Terminal>,
+    ) -> (Option<StackValue>, Vec<ParseError>) {
+        let sync = sync.iter().map(Terminal::kind).collect();
+        self.recover(input, Some(&sync))
+    }
+
+    /// Panic-mode recovery, resuming at any token or only at those in
+    /// `sync`.
+    fn recover(
+        &self,
+        input: &str,
+        sync: Option<&HashSet<Terminal>>,
+    ) -> (Option<StackValue>, Vec<ParseError>) {
+        let mut errors = vec![];
+        let mut tokens = vec![];
+
+        for (position, token) in self.lexer.tokens_recovering(input).into_iter().enumerate() {
+            match token {
+                Ok(token) if self.skips(&token.terminal) => {}
+                Ok(token) => tokens.push((position, token)),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        let tables = self.tables();
+        let mut state = self.limited_state();
+        let mut index = 0;
+        let mut last_recovery = None;
+        // Tokens shifted since the last recovery, if there has been one.
+        let mut shifted = None;
+
+        loop {
+            let lookahead = tokens.get(index).map(|(_, token)| &token.terminal);
+
+            if let Err(error) = state.reduce(tables, &mut self.owned(), lookahead) {
+                errors.push(error);
+                return (None, sorted(errors));
+            }
+
+            let error = match tokens.get(index) {
+                Some((position, token)) => {
+                    match state.shift(tables, &mut self.owned(), token.clone(), *position) {
+                        Ok(()) => {
+                            index += 1;
+                            shifted = shifted.map(|shifted: usize| shifted + 1);
+                            continue;
+                        }
+                        Err(error) => error,
+                    }
+                }
+                None if state.accepts(&self.grammar.starting_symbol) => {
+                    return (
+                        state.accept(&self.grammar.starting_symbol).ok(),
+                        sorted(errors),
+                    );
+                }
+                None => state.bad_stack(),
+            };
+
+            // Resuming at the same token that just failed would fail the same
+            // way again, so it has to be discarded instead.
+            let retrying = last_recovery == Some(index);
+            let suppressed = shifted.is_some_and(|shifted| shifted < RECOVERY_WINDOW);
+            if !retrying && !suppressed {
+                errors.push(error);
+            }
+
+            let skip_from = if retrying { index + 1 } else { index };
+
+            // Once the end of input has been retried, everything left on the
+            // stack becomes a single tree for the starting symbol.
+            let point = if retrying && index == tokens.len() {
+                Some((
+                    state.stack.len(),
+                    self.grammar.starting_symbol.clone(),
+                    index,
+                ))
+            } else {
+                recovery_point(tables, &state, &tokens, skip_from, sync)
+            };
+
+            let Some((depth, nt, resume)) = point else {
+                return (None, sorted(errors));
+            };
+
+            let mut values = state.pop(depth);
+            values.extend(
+                tokens[index..resume]
+                    .iter()
+                    .map(|(_, token)| StackValue::Terminal(token.clone())),
+            );
+
+            state.push_tree(tables, &mut self.owned(), nt, values);
+
+            index = resume;
+            last_recovery = Some(resume);
+            shifted = Some(0);
+        }
+    }
+}
+
+impl Parser {
+    /// Parses what it can of `input`, for editors that want to work with
+    /// the valid parts of broken input. Returns the valid islands in input
+    /// order: maximal trees built from the input by ordinary reductions,
+    /// with the errors that separate them.
+    ///
+    /// Unlike [`Parser::parse_recovering`] nothing is invented to bridge an
+    /// error. When a token cannot be shifted, the stack is reduced as if
+    /// the input ended there and each tree on it becomes an island, while
+    /// terminals left on it are dropped. Parsing then starts afresh from
+    /// the token that failed, which is dropped too if it cannot begin a
+    /// sentence. Errors before anything has been shifted again are
+    /// knock-on effects and are not reported. Input that does not tokenize
+    /// is reported and skipped. Errors come sorted like those of
+    /// [`Parser::parse_recovering`]. Nesting deeper than
+    /// [`Parser::set_max_nesting_depth`] allows ends the parse there, with
+    /// the islands found before it.
+    pub fn parse_best_effort(&self, input: &str) -> (Vec<StackValue>, Vec<ParseError>) {
+        let mut errors = vec![];
+        let mut islands = vec![];
+
+        if let Err(error) = self.find_islands(input, &mut islands, &mut errors) {
+            errors.push(error);
+        }
+
+        (islands, sorted(errors))
+    }
+
+    /// Collects the islands and errors of [`Parser::parse_best_effort`],
+    /// stopping at nesting deeper than the limit allows.
+    fn find_islands(
+        &self,
+        input: &str,
+        islands: &mut Vec<StackValue>,
+        errors: &mut Vec<ParseError>,
+    ) -> Result<(), ParseError> {
+        let tables = self.tables();
+        let mut state = self.limited_state();
+        let mut reported = false;
+
+        for (position, token) in self.lexer.tokens_recovering(input).into_iter().enumerate() {
+            let token = match token {
+                Ok(token) if self.skips(&token.terminal) => continue,
+                Ok(token) => token,
+                Err(error) => {
+                    errors.push(error);
+                    continue;
+                }
+            };
+
+            state.reduce(tables, &mut self.owned(), Some(&token.terminal))?;
+            match state.shift(tables, &mut self.owned(), token.clone(), position) {
+                Ok(()) => reported = false,
+                Err(error) => {
+                    if !reported {
+                        errors.push(error);
+                        reported = true;
+                    }
+                    self.strand(tables, &mut state, islands)?;
+                    if state
+                        .shift(tables, &mut self.owned(), token, position)
+                        .is_ok()
+                    {
+                        reported = false;
+                    }
+                }
+            }
+        }
+
+        state.reduce(tables, &mut self.owned(), None)?;
+        if state.accepts(&self.grammar.starting_symbol) {
+            islands.extend(state.accept(&self.grammar.starting_symbol).ok());
+        } else if !state.stack.is_empty() || islands.is_empty() && errors.is_empty() {
+            errors.push(ParseError::UnexpectedEnd {
+                expected: state.expected(tables),
+            });
+            self.strand(tables, &mut state, islands)?;
+        }
+
+        Ok(())
+    }
+
+    /// Ends the island `state` was building, moving its trees to `islands`
+    /// and leaving it ready to start again.
+    fn strand(
+        &self,
+        tables: &Tables,
+        state: &mut ParseState,
+        islands: &mut Vec<StackValue>,
+    ) -> Result<(), ParseError> {
+        let mut finished = std::mem::replace(state, self.limited_state());
+        finished.reduce(tables, &mut self.owned(), None)?;
+
+        islands.extend(
+            finished
+                .stack
+                .drain(..)
+                .filter(|value| matches!(value, StackValue::Tree { .. })),
+        );
+
+        Ok(())
+    }
+}
+
+/// `errors` ordered by where they start in the input, those without a
+/// place last, with duplicates removed.
+fn sorted(mut errors: Vec<ParseError>) -> Vec<ParseError> {
+    errors.sort_by_key(|error| error.span().map_or(usize::MAX, |span| span.start));
+    errors.dedup();
+    errors
+}
+
+/// Where to resume after an error: how many stack values to pop, the
+/// nonterminal to push in their place, and the index of the synchronizing
+/// token (`tokens.len()` for the end of input). Prefers discarding as few
+/// tokens as possible, then popping as few values as possible. With `sync`,
+/// only its terminals and the end of input synchronize.
+fn recovery_point(
+    tables: &Tables,
+    state: &ParseState,
+    tokens: &[(usize, Token)],
+    skip_from: usize,
+    sync: Option<&HashSet<Terminal>>,
+) -> Option<(usize, NonTerminal, usize)> {
+    for resume in skip_from..=tokens.len() {
+        let lookahead = tokens.get(resume).map(|(_, token)| token.terminal.kind());
+        if let (Some(sync), Some(terminal)) = (sync, &lookahead)
+            && !sync.contains(terminal)
+        {
+            continue;
+        }
+
+        for depth in 0..=state.stack.len() {
+            let below = state.states[state.states.len() - 1 - depth];
+
+            let sync = tables.table.non_terminals().iter().find(|nt| {
+                tables.table.goto(below, nt).is_some()
+                    && tables
+                        .follow
+                        .get(*nt)
+                        .is_some_and(|follow| follow.contains(&lookahead))
+            });
+
+            if let Some(nt) = sync {
+                return Some((depth, nt.clone(), resume));
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,105 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Expression, Grammar, NonTerminal};
+
+/// Hands out [`NonTerminal::Generated`] symbols that no other grammar
+/// built against the same registry uses, so grammars from one family can
+/// be combined, for example by [`Grammar::extend_with`], without two
+/// unrelated generated nonterminals being taken for one.
+///
+/// The named nonterminals are the same in every grammar and need no
+/// registry. Only generated ones are numbered, and each grammar numbers
+/// its own from zero unless told otherwise.
+///
+/// A registry lives as long as its family of grammars is still being
+/// combined. Make one, [`SymbolRegistry::reserve`] the generated symbols of
+/// any grammar built without it, then mint new ones through it, as
+/// [`Grammar::as_chomsky_normal_form_in`] does, or renumber a grammar built
+/// elsewhere with [`SymbolRegistry::adopt`]. Its count only grows, so a
+/// symbol it handed out is never handed out again. Grammars stay valid
+/// once it is dropped; only their ids are no longer kept apart from new
+/// grammars.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolRegistry {
+    next: usize,
+}
+
+impl SymbolRegistry {
+    pub fn new() -> Self {
+        SymbolRegistry::default()
+    }
+
+    /// A generated nonterminal the registry has not handed out or
+    /// reserved before.
+    pub fn fresh(&mut self) -> NonTerminal {
+        self.next += 1;
+        NonTerminal::Generated(self.next - 1)
+    }
+
+    /// Marks every generated nonterminal in `grammar` as taken, so none of
+    /// them is handed out later.
+    pub fn reserve(&mut self, grammar: &Grammar) {
+        for nt in grammar.symbols_in_order().0 {
+            if let NonTerminal::Generated(index) = nt {
+                self.next = self.next.max(index + 1);
+            }
+        }
+    }
+
+    /// The nonterminals this registry handed out or reserved after it was
+    /// in the state of `earlier`, a copy of it from before.
+    pub(crate) fn since(&self, earlier: &SymbolRegistry) -> HashSet<NonTerminal> {
+        (earlier.next..self.next)
+            .map(NonTerminal::Generated)
+            .collect()
+    }
+
+    /// `grammar` with each of its generated nonterminals replaced by a
+    /// fresh one, for a grammar built without the registry. Its rules,
+    /// restrictions, labels and starting symbol all follow the new names.
+    pub fn adopt(&mut self, grammar: &Grammar) -> Grammar {
+        let renamed: HashMap<NonTerminal, NonTerminal> = grammar
+            .symbols_in_order()
+            .0
+            .into_iter()
+            .filter(|nt| matches!(nt, NonTerminal::Generated(_)))
+            .map(|nt| (nt, self.fresh()))
+            .collect();
+        let rename = |nt: &NonTerminal| renamed.get(nt).unwrap_or(nt).clone();
+
+        Grammar {
+            starting_symbol: rename(&grammar.starting_symbol),
+            rules: grammar
+                .rules
+                .iter()
+                .map(|(nt, alternatives)| {
+                    let alternatives = alternatives
+                        .iter()
+                        .map(|production| {
+                            production
+                                .iter()
+                                .map(|expr| match expr {
+                                    Expression::NonTerminal(nt) => {
+                                        Expression::NonTerminal(rename(nt))
+                                    }
+                                    Expression::Terminal(_) => expr.clone(),
+                                })
+                                .collect()
+                        })
+                        .collect();
+                    (rename(nt), alternatives)
+                })
+                .collect(),
+            reduce_lookahead: grammar
+                .reduce_lookahead
+                .iter()
+                .map(|((nt, alternative), set)| ((rename(nt), *alternative), set.clone()))
+                .collect(),
+            labels: grammar
+                .labels
+                .iter()
+                .map(|((nt, alternative), label)| ((rename(nt), *alternative), label.clone()))
+                .collect(),
+        }
+    }
+}
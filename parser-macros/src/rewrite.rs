@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+
+use crate::{Expression, Grammar, GrammarError, NonTerminal, SymbolRegistry, Terminal};
+
+impl Grammar {
+    /// Renames `from` to `to` everywhere: its rules, every right hand side
+    /// using it, its [`Grammar::reduce_lookahead`] restrictions and the
+    /// starting symbol. Fails with [`GrammarError::AlreadyDefined`] if the
+    /// grammar already uses `to`; see [`Grammar::merge_nonterminal`] for
+    /// folding one nonterminal into another.
+    pub fn rename_nonterminal(
+        &mut self,
+        from: NonTerminal,
+        to: NonTerminal,
+    ) -> Result<(), GrammarError> {
+        if from == to {
+            return Ok(());
+        }
+
+        if self.uses(&to) {
+            return Err(GrammarError::AlreadyDefined(to));
+        }
+
+        self.merge_nonterminal(from, to);
+        Ok(())
+    }
+
+    /// Like [`Grammar::rename_nonterminal`], but `into` may already be in
+    /// use. The alternatives of `from` are appended to those of `into`, so
+    /// the merged nonterminal derives what either of them did. Their
+    /// restrictions and labels move with them.
+    pub fn merge_nonterminal(&mut self, from: NonTerminal, into: NonTerminal) {
+        if from == into {
+            return;
+        }
+
+        let offset = self.rules.get(&into).map_or(0, Vec::len);
+
+        if let Some(alternatives) = self.rules.remove(&from) {
+            self.rules
+                .entry(into.clone())
+                .or_default()
+                .extend(alternatives);
+        }
+
+        for alternatives in self.rules.values_mut() {
+            for expr in alternatives.iter_mut().flatten() {
+                if *expr == Expression::NonTerminal(from.clone()) {
+                    *expr = Expression::NonTerminal(into.clone());
+                }
+            }
+        }
+
+        move_keys(&mut self.reduce_lookahead, &from, &into, offset);
+        move_keys(&mut self.labels, &from, &into, offset);
+
+        if self.starting_symbol == from {
+            self.starting_symbol = into;
+        }
+    }
+
+    /// Replaces `from` with `to` in every production and every
+    /// [`Grammar::reduce_lookahead`] restriction, so the grammar accepts
+    /// `to` wherever it accepted `from`. Terminals compare by
+    /// [`Terminal::kind`], so replacing a string literal replaces them all.
+    pub fn replace_terminal(&mut self, from: Terminal, to: Terminal) {
+        let from = from.kind();
+
+        for expr in self.rules.values_mut().flatten().flatten() {
+            if let Expression::Terminal(t) = expr
+                && t.kind() == from
+            {
+                *t = to.clone();
+            }
+        }
+
+        for lookahead in self.reduce_lookahead.values_mut() {
+            let len = lookahead.len();
+            lookahead.retain(|t| t.kind() != from);
+            if lookahead.len() < len {
+                lookahead.insert(to.clone());
+            }
+        }
+    }
+
+    /// A grammar for the same language with single-use nonterminals
+    /// inlined, the cleanup after transformations such as
+    /// [`Grammar::as_chomsky_normal_form`] that mint them in numbers.
+    ///
+    /// A nonterminal is inlined when it has exactly one alternative and
+    /// appears exactly once across all right hand sides, its own excluded.
+    /// The starting symbol is kept, and so is any nonterminal whose
+    /// production has a lookahead restriction or a label, since those
+    /// would have nowhere to go. Inlining repeats until none is left.
+    pub fn minimize(&self) -> Grammar {
+        let mut grammar = self.clone();
+
+        while let Some(inlined) = grammar
+            .symbols_in_order()
+            .0
+            .into_iter()
+            .find(|nt| grammar.inlinable(nt))
+        {
+            let body = grammar
+                .rules
+                .remove(&inlined)
+                .and_then(|mut alternatives| alternatives.pop())
+                .expect("Inlinable nonterminals have one alternative");
+
+            let reference = Expression::NonTerminal(inlined);
+            for production in grammar.rules.values_mut().flatten() {
+                if let Some(index) = production.iter().position(|expr| *expr == reference) {
+                    production.splice(index..=index, body);
+                    break;
+                }
+            }
+        }
+
+        grammar
+    }
+
+    /// A variant of this grammar, with each nonterminal in `overrides`
+    /// changed as its [`Override`] says and every other one kept as it is,
+    /// for families of grammars that share a base. Several overrides of
+    /// one nonterminal apply in order, and either kind adds a nonterminal
+    /// the base lacks.
+    pub fn extend_with<I>(&self, overrides: I) -> Grammar
+    where
+        I: IntoIterator<Item = (NonTerminal, Override)>,
+    {
+        let mut grammar = self.clone();
+
+        for (non_terminal, change) in overrides {
+            match change {
+                Override::Append(alternatives) => {
+                    grammar
+                        .rules
+                        .entry(non_terminal)
+                        .or_default()
+                        .extend(alternatives);
+                }
+                Override::Replace(alternatives) => {
+                    grammar
+                        .reduce_lookahead
+                        .retain(|(nt, _), _| *nt != non_terminal);
+                    grammar.labels.retain(|(nt, _), _| *nt != non_terminal);
+                    grammar.rules.insert(non_terminal, alternatives);
+                }
+            }
+        }
+
+        grammar
+    }
+
+    fn inlinable(&self, non_terminal: &NonTerminal) -> bool {
+        let key = (non_terminal.clone(), 0);
+        let reference = Expression::NonTerminal(non_terminal.clone());
+
+        let single = self.rules.get(non_terminal).is_some_and(|alternatives| {
+            alternatives.len() == 1 && !alternatives[0].contains(&reference)
+        });
+        let references = self
+            .rules
+            .values()
+            .flatten()
+            .flatten()
+            .filter(|expr| **expr == reference)
+            .count();
+
+        *non_terminal != self.starting_symbol
+            && single
+            && references == 1
+            && !self.reduce_lookahead.contains_key(&key)
+            && !self.labels.contains_key(&key)
+    }
+
+    /// Whether `non_terminal` is the starting symbol, has rules, or appears
+    /// in a rule.
+    fn uses(&self, non_terminal: &NonTerminal) -> bool {
+        self.starting_symbol == *non_terminal
+            || self.rules.contains_key(non_terminal)
+            || self
+                .rules
+                .values()
+                .flatten()
+                .flatten()
+                .any(|expr| *expr == Expression::NonTerminal(non_terminal.clone()))
+    }
+}
+
+/// How [`Grammar::extend_with`] changes one nonterminal of its base.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Override {
+    /// Adds alternatives after the base's, which keep their places, so
+    /// their [`Grammar::reduce_lookahead`] restrictions and labels still
+    /// apply.
+    Append(Vec<Vec<Expression>>),
+    /// Drops the base's alternatives for these. The base's restrictions
+    /// and labels for the nonterminal go too, since they name alternatives
+    /// that are gone.
+    Replace(Vec<Vec<Expression>>),
+}
+
+/// Rekeys the entries of `map` for alternatives of `from` as the same
+/// alternatives of `into`, `offset` places further down.
+fn move_keys<V>(
+    map: &mut HashMap<(NonTerminal, usize), V>,
+    from: &NonTerminal,
+    into: &NonTerminal,
+    offset: usize,
+) {
+    let keys: Vec<(NonTerminal, usize)> =
+        map.keys().filter(|(nt, _)| nt == from).cloned().collect();
+    for key in keys {
+        let value = map.remove(&key).expect("Key was just listed");
+        map.insert((into.clone(), offset + key.1), value);
+    }
+}
+
+impl Grammar {
+    /// An equivalent grammar in Chomsky normal form, where every production
+    /// is two nonterminals or a single terminal. If the language contains
+    /// the empty sentence the new starting symbol also has an empty
+    /// production, and the starting symbol never appears on a right hand
+    /// side.
+    ///
+    /// The standard steps run in order: a new starting symbol, a nonterminal
+    /// per terminal in longer productions, splitting productions into
+    /// pairs, then removing empty and unit productions. New nonterminals are
+    /// [`NonTerminal::Generated`], numbered after any the grammar already
+    /// has. Lookahead restrictions and labels do not carry over, since the
+    /// productions they name are gone, and nonterminals the new starting
+    /// symbol no longer reaches are dropped.
+    pub fn as_chomsky_normal_form(&self) -> Grammar {
+        self.as_chomsky_normal_form_in(&mut SymbolRegistry::new())
+    }
+
+    /// Like [`Grammar::as_chomsky_normal_form`], but the new nonterminals
+    /// come from `registry`, so they clash with none it has handed out for
+    /// other grammars.
+    pub fn as_chomsky_normal_form_in(&self, registry: &mut SymbolRegistry) -> Grammar {
+        use Expression::{NonTerminal as N, Terminal as T};
+
+        registry.reserve(self);
+        let mut mint = || registry.fresh();
+
+        let start = mint();
+        let mut rules: Vec<(NonTerminal, Vec<Vec<Expression>>)> =
+            vec![(start.clone(), vec![vec![N(self.starting_symbol.clone())]])];
+        rules.extend(self.symbols_in_order().0.into_iter().filter_map(|nt| {
+            let alternatives = self.rules.get(&nt)?.clone();
+            Some((nt, alternatives))
+        }));
+
+        // Terminals in productions of two or more symbols get a nonterminal
+        // of their own.
+        let mut terminals: Vec<(Terminal, NonTerminal)> = vec![];
+        for (_, alternatives) in &mut rules {
+            for production in alternatives.iter_mut().filter(|p| p.len() > 1) {
+                for expr in production.iter_mut() {
+                    let T(terminal) = expr else {
+                        continue;
+                    };
+                    let nt = match terminals.iter().find(|(t, _)| t == terminal) {
+                        Some((_, nt)) => nt.clone(),
+                        None => {
+                            let nt = mint();
+                            terminals.push((terminal.clone(), nt.clone()));
+                            nt
+                        }
+                    };
+                    *expr = N(nt);
+                }
+            }
+        }
+        rules.extend(
+            terminals
+                .into_iter()
+                .map(|(terminal, nt)| (nt, vec![vec![T(terminal)]])),
+        );
+
+        // Longer productions become chains of pairs.
+        let mut chains = vec![];
+        for (_, alternatives) in &mut rules {
+            for production in alternatives.iter_mut().filter(|p| p.len() > 2) {
+                let symbols = std::mem::take(production);
+                let links: Vec<NonTerminal> = (2..symbols.len()).map(|_| mint()).collect();
+
+                *production = vec![symbols[0].clone(), N(links[0].clone())];
+                for (index, link) in links.iter().enumerate() {
+                    let rest = match links.get(index + 1) {
+                        Some(next) => N(next.clone()),
+                        None => symbols[index + 2].clone(),
+                    };
+                    chains.push((link.clone(), vec![vec![symbols[index + 1].clone(), rest]]));
+                }
+            }
+        }
+        rules.extend(chains);
+
+        // Empty productions go, with every production that used a nullable
+        // nonterminal also given without it. Only the new starting symbol
+        // keeps an empty production.
+        let nullable = Grammar::from_rules(start.clone(), rules.clone()).nullable_nonterminals();
+        for (non_terminal, alternatives) in &mut rules {
+            let mut expanded: Vec<Vec<Expression>> = vec![];
+            for production in alternatives.iter() {
+                let mut variants: Vec<Vec<Expression>> = vec![vec![]];
+                for expr in production {
+                    let optional = matches!(expr, N(nt) if nullable.contains(nt));
+                    variants = variants
+                        .into_iter()
+                        .flat_map(|variant| {
+                            let mut with = variant.clone();
+                            with.push(expr.clone());
+                            if optional {
+                                vec![with, variant]
+                            } else {
+                                vec![with]
+                            }
+                        })
+                        .collect();
+                }
+                for variant in variants {
+                    if !variant.is_empty() && !expanded.contains(&variant) {
+                        expanded.push(variant);
+                    }
+                }
+            }
+            if *non_terminal == start && nullable.contains(&start) {
+                expanded.push(vec![]);
+            }
+            *alternatives = expanded;
+        }
+
+        // Unit productions are replaced by the productions of every
+        // nonterminal they lead to.
+        let units: HashMap<NonTerminal, Vec<Vec<Expression>>> = rules.iter().cloned().collect();
+        for (non_terminal, alternatives) in &mut rules {
+            let mut reached = vec![non_terminal.clone()];
+            let mut index = 0;
+            let mut replaced: Vec<Vec<Expression>> = vec![];
+
+            while let Some(current) = reached.get(index).cloned() {
+                index += 1;
+                for production in units.get(&current).into_iter().flatten() {
+                    match production.as_slice() {
+                        [N(target)] => {
+                            if !reached.contains(target) {
+                                reached.push(target.clone());
+                            }
+                        }
+                        _ => {
+                            if !replaced.contains(production) {
+                                replaced.push(production.clone());
+                            }
+                        }
+                    }
+                }
+            }
+
+            *alternatives = replaced;
+        }
+
+        let mut grammar = Grammar::from_rules(start, rules);
+        let reachable = grammar.reachable();
+        grammar.rules.retain(|nt, _| reachable.contains(nt));
+        grammar
+    }
+}
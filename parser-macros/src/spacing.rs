@@ -0,0 +1,75 @@
+use std::collections::HashSet;
+
+use crate::{StackValue, Terminal};
+
+/// Where [`StackValue::to_source_with`] puts spaces between tokens. Every
+/// pair of tokens is separated by one space, so binary operators get one
+/// on either side, except after an `open` terminal and before a `close`
+/// one. Terminals are compared by [`Terminal::kind`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spacing {
+    /// Terminals with no space after them, such as opening brackets.
+    pub open: HashSet<Terminal>,
+    /// Terminals with no space before them, such as closing brackets and
+    /// commas.
+    pub close: HashSet<Terminal>,
+}
+
+impl Spacing {
+    /// A space between every pair of tokens, as in `( 0 * 0 )`.
+    pub fn everywhere() -> Self {
+        Spacing {
+            open: HashSet::new(),
+            close: HashSet::new(),
+        }
+    }
+
+    fn separates(&self, before: &Terminal, after: &Terminal) -> bool {
+        !self.open.contains(&before.kind()) && !self.close.contains(&after.kind())
+    }
+}
+
+/// No space inside parentheses and brackets or before a comma, as in
+/// `(0 * 0)` and `[0, 0]`.
+impl Default for Spacing {
+    fn default() -> Self {
+        Spacing {
+            open: HashSet::from([Terminal::LeftParen, Terminal::LeftBracket]),
+            close: HashSet::from([
+                Terminal::RightParen,
+                Terminal::RightBracket,
+                Terminal::Comma,
+            ]),
+        }
+    }
+}
+
+impl StackValue {
+    /// The terminals below this value written out as source, spaced as
+    /// [`Spacing::default`] has it, so `( 0 * 0 )` comes out as `(0 * 0)`.
+    /// Unlike the `Display` s-expression this leaves out the tree's
+    /// structure, and unlike the input it ignores trivia, so comments and
+    /// the original spacing are lost.
+    pub fn to_source(&self) -> String {
+        self.to_source_with(&Spacing::default())
+    }
+
+    /// Like [`StackValue::to_source`], with spaces where `spacing` puts
+    /// them.
+    pub fn to_source_with(&self, spacing: &Spacing) -> String {
+        let mut source = String::new();
+        let mut previous: Option<&Terminal> = None;
+
+        for terminal in self.leaves() {
+            if let Some(previous) = previous
+                && spacing.separates(previous, terminal)
+            {
+                source.push(' ');
+            }
+            source.push_str(&terminal.to_string());
+            previous = Some(terminal);
+        }
+
+        source
+    }
+}
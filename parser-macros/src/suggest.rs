@@ -0,0 +1,117 @@
+use std::fmt;
+
+use crate::{NonTerminal, ParseError, ParseState, Parser, Tables, Terminal};
+
+/// How far back from the failing token [`Parser::suggest_edit`] looks for a
+/// place to edit.
+const SUGGESTION_WINDOW: usize = 8;
+
+/// A single token edit to the input, as suggested by
+/// [`Parser::suggest_edit`]. Positions count tokens from 0, skipped ones
+/// included, like [`ParseError::UnexpectedToken`] positions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    /// Insert `terminal` before the token at `position`, or at the end when
+    /// `position` is the number of tokens.
+    Insert { terminal: Terminal, position: usize },
+    /// Delete the token at `position`.
+    Delete { terminal: Terminal, position: usize },
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edit::Insert { terminal, position } => {
+                write!(f, "insert '{terminal}' at position {position}")
+            }
+            Edit::Delete { terminal, position } => {
+                write!(f, "delete '{terminal}' at position {position}")
+            }
+        }
+    }
+}
+
+impl Parser {
+    /// The smallest edit that makes `input` parse, for editors to offer as a
+    /// quick fix.
+    ///
+    /// Only single token edits are tried, and only at the token the parse
+    /// fails on or up to a few tokens before it, nearest first. At each
+    /// place deleting the token is tried before inserting any of the
+    /// terminals expected there. `Ok(None)` means the input already parses
+    /// or no such edit was found. Fails if the input does not tokenize.
+    pub fn suggest_edit(&self, input: &str) -> Result<Option<Edit>, ParseError> {
+        let tables = self.tables();
+        let terminals = self.lexer.tokens(input)?;
+        let end = terminals.len();
+        let tokens: Vec<(usize, Terminal)> = terminals
+            .into_iter()
+            .enumerate()
+            .filter(|(_, terminal)| !self.skips(terminal))
+            .collect();
+        let start = &self.grammar.starting_symbol;
+
+        // `before[index]` is the state before the token at `index`.
+        let mut before = vec![ParseState::new()];
+        for (_, terminal) in &tokens {
+            let Some(next) = before[before.len() - 1].advance(tables, terminal) else {
+                break;
+            };
+            before.push(next);
+        }
+
+        let failure = before.len() - 1;
+        if failure == tokens.len() && before[failure].finishes(tables, start) {
+            return Ok(None);
+        }
+
+        let rest = |from: usize| tokens[from..].iter().map(|(_, terminal)| terminal);
+
+        for index in (failure.saturating_sub(SUGGESTION_WINDOW)..=failure).rev() {
+            let state = &before[index];
+
+            if let Some((position, terminal)) = tokens.get(index)
+                && state.parses(tables, rest(index + 1), start)
+            {
+                return Ok(Some(Edit::Delete {
+                    terminal: terminal.clone(),
+                    position: *position,
+                }));
+            }
+
+            for terminal in state.expected(tables) {
+                let Some(inserted) = state.advance(tables, &terminal) else {
+                    continue;
+                };
+
+                if inserted.parses(tables, rest(index), start) {
+                    return Ok(Some(Edit::Insert {
+                        terminal,
+                        position: tokens.get(index).map_or(end, |(position, _)| *position),
+                    }));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl ParseState<()> {
+    /// Whether shifting all of `rest` and then finishing parses.
+    fn parses<'a>(
+        &self,
+        tables: &Tables,
+        rest: impl Iterator<Item = &'a Terminal>,
+        start: &NonTerminal,
+    ) -> bool {
+        let mut state = self.skeleton();
+        for terminal in rest {
+            match state.advance(tables, terminal) {
+                Some(next) => state = next,
+                None => return false,
+            }
+        }
+        state.finishes(tables, start)
+    }
+}
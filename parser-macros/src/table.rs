@@ -0,0 +1,320 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::{self, Write},
+};
+
+use crate::{Expression, Grammar, NonTerminal, Terminal, restriction_allows};
+
+/// An entry in the action part of a [`ParseTable`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Shift the lookahead and move to the given state.
+    Shift(usize),
+    /// Reduce by the given production number.
+    Reduce(usize),
+    Accept,
+}
+
+/// An LR(0) item: a production number and the position of the dot.
+type Item = (usize, usize);
+
+/// SLR(1) parse table built from the canonical LR(0) item sets.
+///
+/// Production 0 is the augmented rule `start' -> start`; the grammar's own
+/// productions are numbered from 1, starting with the starting symbol's and
+/// continuing in the order the rules reach each nonterminal.
+/// Cells keep every action that applies, so conflicts stay visible.
+/// Reductions are left out for lookaheads that the production's
+/// [`Grammar::reduce_lookahead`] restriction rules out.
+pub struct ParseTable {
+    productions: Vec<(NonTerminal, Vec<Expression>)>,
+    /// The [`Grammar::labels`] entry of each production, if it has one.
+    labels: Vec<Option<String>>,
+    terminals: Vec<Terminal>,
+    non_terminals: Vec<NonTerminal>,
+    actions: Vec<HashMap<Option<Terminal>, Vec<Action>>>,
+    gotos: Vec<HashMap<NonTerminal, usize>>,
+}
+
+impl ParseTable {
+    pub fn new(grammar: &Grammar) -> Self {
+        let (non_terminals, terminals) = grammar.symbols_in_order();
+
+        let mut restrictions = vec![None];
+        let mut labels = vec![None];
+        let mut productions = vec![(
+            grammar.starting_symbol.clone(),
+            vec![Expression::NonTerminal(grammar.starting_symbol.clone())],
+        )];
+        for non_terminal in &non_terminals {
+            for (alternative, production) in grammar
+                .rules
+                .get(non_terminal)
+                .into_iter()
+                .flatten()
+                .enumerate()
+            {
+                restrictions.push(
+                    grammar
+                        .reduce_lookahead
+                        .get(&(non_terminal.clone(), alternative)),
+                );
+                labels.push(
+                    grammar
+                        .labels
+                        .get(&(non_terminal.clone(), alternative))
+                        .cloned(),
+                );
+                let production = production
+                    .iter()
+                    .map(|expr| match expr {
+                        Expression::Terminal(t) => Expression::Terminal(t.kind()),
+                        Expression::NonTerminal(_) => expr.clone(),
+                    })
+                    .collect();
+                productions.push((non_terminal.clone(), production));
+            }
+        }
+
+        let closure = |kernel: BTreeSet<Item>| -> BTreeSet<Item> {
+            let mut items = kernel;
+            let mut pending: Vec<Item> = items.iter().copied().collect();
+
+            while let Some((production, dot)) = pending.pop() {
+                let Some(Expression::NonTerminal(next)) = productions[production].1.get(dot) else {
+                    continue;
+                };
+
+                for (index, (lhs, _)) in productions.iter().enumerate().skip(1) {
+                    if lhs == next && items.insert((index, 0)) {
+                        pending.push((index, 0));
+                    }
+                }
+            }
+
+            items
+        };
+
+        let mut states = vec![closure(BTreeSet::from([(0, 0)]))];
+        let mut transitions: Vec<Vec<(Expression, usize)>> = vec![];
+
+        let mut index = 0;
+        while let Some(state) = states.get(index).cloned() {
+            let mut symbols: Vec<&Expression> = vec![];
+            for (production, dot) in &state {
+                if let Some(symbol) = productions[*production].1.get(*dot)
+                    && !symbols.contains(&symbol)
+                {
+                    symbols.push(symbol);
+                }
+            }
+
+            let mut edges = vec![];
+            for symbol in symbols {
+                let kernel = state
+                    .iter()
+                    .filter(|(production, dot)| {
+                        productions[*production].1.get(*dot) == Some(symbol)
+                    })
+                    .map(|(production, dot)| (*production, dot + 1))
+                    .collect();
+                let target = closure(kernel);
+
+                let target = match states.iter().position(|existing| *existing == target) {
+                    Some(position) => position,
+                    None => {
+                        states.push(target);
+                        states.len() - 1
+                    }
+                };
+
+                edges.push((symbol.clone(), target));
+            }
+
+            transitions.push(edges);
+            index += 1;
+        }
+
+        let follow = grammar.follow_sets();
+        let mut actions: Vec<HashMap<Option<Terminal>, Vec<Action>>> = vec![];
+        let mut gotos: Vec<HashMap<NonTerminal, usize>> = vec![];
+
+        for (state, edges) in states.iter().zip(&transitions) {
+            let mut row: HashMap<Option<Terminal>, Vec<Action>> = HashMap::new();
+            let mut goto_row = HashMap::new();
+
+            for (symbol, target) in edges {
+                match symbol {
+                    Expression::Terminal(t) => {
+                        row.entry(Some(t.clone()))
+                            .or_default()
+                            .push(Action::Shift(*target));
+                    }
+                    Expression::NonTerminal(nt) => {
+                        goto_row.insert(nt.clone(), *target);
+                    }
+                }
+            }
+
+            for (production, dot) in state {
+                let (lhs, rhs) = &productions[*production];
+                if *dot < rhs.len() {
+                    continue;
+                }
+
+                if *production == 0 {
+                    row.entry(None).or_default().push(Action::Accept);
+                    continue;
+                }
+
+                for lookahead in follow.get(lhs).into_iter().flatten() {
+                    if !restriction_allows(restrictions[*production], lookahead.as_ref()) {
+                        continue;
+                    }
+
+                    row.entry(lookahead.clone())
+                        .or_default()
+                        .push(Action::Reduce(*production));
+                }
+            }
+
+            actions.push(row);
+            gotos.push(goto_row);
+        }
+
+        ParseTable {
+            productions,
+            labels,
+            terminals,
+            non_terminals,
+            actions,
+            gotos,
+        }
+    }
+
+    /// Terminals of the grammar, in column order.
+    pub fn terminals(&self) -> &[Terminal] {
+        &self.terminals
+    }
+
+    /// Nonterminals of the grammar, in column order.
+    pub fn non_terminals(&self) -> &[NonTerminal] {
+        &self.non_terminals
+    }
+
+    pub fn state_count(&self) -> usize {
+        self.actions.len()
+    }
+
+    /// Actions for `lookahead` (`None` at the end of input) in `state`. More
+    /// than one action means the grammar has a conflict there. Terminals are
+    /// looked up by [`Terminal::kind`].
+    pub fn actions(&self, state: usize, lookahead: Option<&Terminal>) -> &[Action] {
+        self.actions
+            .get(state)
+            .and_then(|row| row.get(&lookahead.map(Terminal::kind)))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn goto(&self, state: usize, non_terminal: &NonTerminal) -> Option<usize> {
+        self.gotos.get(state)?.get(non_terminal).copied()
+    }
+
+    /// The left and right hand side of production number `index`.
+    pub fn production(&self, index: usize) -> Option<(&NonTerminal, &[Expression])> {
+        self.productions
+            .get(index)
+            .map(|(lhs, rhs)| (lhs, rhs.as_slice()))
+    }
+
+    /// The label production `index` was given in [`Grammar::labels`].
+    pub fn label(&self, index: usize) -> Option<&str> {
+        self.labels.get(index)?.as_deref()
+    }
+
+    /// Renders the table as a textbook grid: one row per state, action
+    /// columns for each terminal and the end of input (`$`), then goto
+    /// columns for each nonterminal. A legend of numbered productions
+    /// follows the grid, with the label of any that have one.
+    pub fn to_table_string(&self) -> String {
+        let mut header = vec!["state".to_string()];
+        header.extend(self.terminals.iter().map(Terminal::to_string));
+        header.push("$".to_string());
+        header.extend(self.non_terminals.iter().map(NonTerminal::to_string));
+
+        let mut rows = vec![header];
+        for state in 0..self.state_count() {
+            let mut row = vec![state.to_string()];
+
+            for lookahead in self.terminals.iter().map(Some).chain([None]) {
+                let cell = self
+                    .actions(state, lookahead)
+                    .iter()
+                    .map(Action::to_string)
+                    .collect::<Vec<String>>()
+                    .join("/");
+                row.push(cell);
+            }
+
+            for non_terminal in &self.non_terminals {
+                row.push(
+                    self.goto(state, non_terminal)
+                        .map(|target| target.to_string())
+                        .unwrap_or_default(),
+                );
+            }
+
+            rows.push(row);
+        }
+
+        let widths: Vec<usize> = (0..rows[0].len())
+            .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+            .collect();
+
+        let mut out = String::new();
+        for row in &rows {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect::<Vec<String>>()
+                .join(" | ");
+            writeln!(out, "{}", line.trim_end()).expect("Writing to a String cannot fail");
+        }
+
+        writeln!(out).expect("Writing to a String cannot fail");
+        for (index, (lhs, rhs)) in self.productions.iter().enumerate() {
+            let lhs = if index == 0 {
+                format!("{lhs}'")
+            } else {
+                lhs.to_string()
+            };
+
+            write!(out, "{index}: {lhs} ->").expect("Writing to a String cannot fail");
+            for expr in rhs {
+                match expr {
+                    Expression::Terminal(t) => write!(out, " '{t}'"),
+                    Expression::NonTerminal(nt) => write!(out, " {nt}"),
+                }
+                .expect("Writing to a String cannot fail");
+            }
+            if let Some(label) = self.label(index) {
+                write!(out, " ({label})").expect("Writing to a String cannot fail");
+            }
+            writeln!(out).expect("Writing to a String cannot fail");
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Shift(state) => write!(f, "s{state}"),
+            Action::Reduce(production) => write!(f, "r{production}"),
+            Action::Accept => write!(f, "acc"),
+        }
+    }
+}
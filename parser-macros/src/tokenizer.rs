@@ -1 +1,683 @@
+use std::{fmt, ops::RangeInclusive};
 
+use crate::{FloatLiteral, ParseError, Terminal};
+
+/// Byte range of a token in the input, or range of chars for
+/// [`Tokenizer::tokenize_chars`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// The smallest span covering both `self` and `other`, including
+    /// whatever lies between them.
+    pub fn merge(self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    /// Whether `offset` is in the span. The end is exclusive, so an empty
+    /// span contains nothing.
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    /// Whether the span covers nothing, as the span of a tree that derived
+    /// no tokens does.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Text between tokens that the grammar does not see.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trivia {
+    /// A run of whitespace.
+    Whitespace(String),
+    /// A `//` comment, up to but not including the end of its line.
+    Comment(String),
+}
+
+/// A terminal with where it came from in the input.
+///
+/// Trivia between two tokens is split at the first line break: up to and
+/// including it is the trailing trivia of the token before, the rest is
+/// the leading trivia of the token after. Trivia at the start of the input
+/// leads the first token and trivia at the end trails the last one, so
+/// writing out every token with its trivia reproduces the input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub terminal: Terminal,
+    pub span: Span,
+    pub leading_trivia: Vec<Trivia>,
+    pub trailing_trivia: Vec<Trivia>,
+}
+
+impl Token {
+    pub fn new(terminal: Terminal, span: Span) -> Self {
+        Token {
+            terminal,
+            span,
+            leading_trivia: vec![],
+            trailing_trivia: vec![],
+        }
+    }
+}
+
+/// A token with an empty span at the start of the input, for terminals
+/// that did not come from any input.
+impl From<Terminal> for Token {
+    fn from(terminal: Terminal) -> Self {
+        Token::new(terminal, Span { start: 0, end: 0 })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenizeError {
+    /// A whitespace separated word that does not spell any terminal.
+    InvalidToken { token: String, span: Span },
+    /// A string literal with no closing quote. The span covers the opening
+    /// quote.
+    UnterminatedString { span: Span },
+    /// A backslash in a string literal followed by something other than
+    /// `"`, `\` or `n`. The span covers the backslash and that character.
+    InvalidEscape { escape: char, span: Span },
+    /// A run of digits and dots that is no number, such as `3.14.15`, or
+    /// an integer too large for an `i64`. `TokenizerConfig` reports the
+    /// same for text its `Int` and `Float` patterns match that does not
+    /// parse.
+    MalformedNumber { number: String, span: Span },
+}
+
+/// Turns input into the terminals a [`Parser`](crate::Parser) reduces.
+/// [`Tokenizer`] is the built-in implementation; others can be plugged in
+/// with [`Parser::with_lexer`](crate::Parser::with_lexer).
+pub trait Lexer {
+    fn tokens(&self, input: &str) -> Result<Vec<Terminal>, ParseError>;
+
+    /// Tokens of `input` with their spans and trivia. By default every
+    /// token gets an empty span and no trivia.
+    fn lex(&self, input: &str) -> Result<Vec<Token>, ParseError> {
+        Ok(self.tokens(input)?.into_iter().map(Token::from).collect())
+    }
+
+    /// Tokens of `input` for error recovery, which carries on past failed
+    /// tokens. By default the first error ends the input.
+    fn tokens_recovering(&self, input: &str) -> Vec<Result<Token, ParseError>> {
+        match self.lex(input) {
+            Ok(tokens) => tokens.into_iter().map(Ok).collect(),
+            Err(error) => vec![Err(error)],
+        }
+    }
+
+    /// Every terminal kind the lexer can produce, as [`Terminal::kind`]
+    /// has them, for checks such as
+    /// [`Parser::unused_terminals`](crate::Parser::unused_terminals). By
+    /// default `None`, for a lexer that cannot list them.
+    fn terminal_kinds(&self) -> Option<Vec<Terminal>> {
+        None
+    }
+}
+
+/// Splits input into terminals.
+///
+/// Operators and `0` need no whitespace between them; where several
+/// operators could match, the longest wins. Double quoted strings
+/// become [`Terminal::StringLiteral`] holding their unescaped content, and
+/// may contain whitespace and the escapes `\"`, `\\` and `\n`. A run of
+/// letters, digits and `_` that starts with a letter or `_` becomes a
+/// [`Terminal::Identifier`]; digits cannot start one, so `0a` is `0` then
+/// `a`. Other numbers become [`Terminal::Int`], or [`Terminal::Float`] when
+/// they have a decimal point (`3.14`, `3.` and `.5` all do); a lone `0` is
+/// still [`Terminal::Zero`]. Any whitespace separates tokens, tabs and
+/// line breaks included, and a run of mixed whitespace separates them the
+/// same as a single space. Whitespace and `//` comments are kept as
+/// [`Trivia`] on the tokens around them, unless whitespace is asked for as
+/// tokens with [`Tokenizer::with_whitespace`].
+#[derive(Debug, Clone, Default)]
+pub struct Tokenizer {
+    /// Whether runs of whitespace become [`Terminal::Whitespace`] tokens
+    /// rather than trivia.
+    whitespace: bool,
+    /// Whether input that does not tokenize becomes [`Terminal::Error`]
+    /// tokens rather than errors.
+    lenient: bool,
+}
+
+impl Tokenizer {
+    pub fn new() -> Self {
+        Tokenizer::default()
+    }
+
+    /// A tokenizer that turns each run of whitespace into a
+    /// [`Terminal::Whitespace`] token, for grammars where spacing matters.
+    /// Grammars that still ignore it in places can skip it with
+    /// [`Parser::with_lexer_skipping`](crate::Parser::with_lexer_skipping).
+    pub fn with_whitespace() -> Self {
+        Tokenizer {
+            whitespace: true,
+            ..Tokenizer::default()
+        }
+    }
+
+    /// The same tokenizer, but turning input that would be a
+    /// [`TokenizeError`] into a [`Terminal::Error`] token holding it, so
+    /// parsing gets as far as the bad input and recovery can carry on past
+    /// it. The token spans all of the bad input, up to where tokenizing
+    /// resumes: a string literal with a bad escape up to its closing quote,
+    /// and an unterminated one up to the end of input.
+    pub fn lenient(self) -> Self {
+        Tokenizer {
+            lenient: true,
+            ..self
+        }
+    }
+
+    /// Every terminal kind this tokenizer can produce, as
+    /// [`Terminal::kind`] has them: the operators and `0`, longest first,
+    /// then literals and identifiers, and whitespace or error tokens if it
+    /// was asked for them.
+    pub fn terminals(&self) -> Vec<Terminal> {
+        let mut terminals: Vec<Terminal> = LEXEMES
+            .into_iter()
+            .filter_map(|lexeme| operator(&lexeme.chars().collect::<Vec<char>>()))
+            .map(|(_, terminal)| terminal)
+            .collect();
+        terminals.extend([
+            Terminal::StringLiteral(String::new()),
+            Terminal::Identifier(String::new()),
+            Terminal::Int(None),
+            Terminal::Float(None),
+        ]);
+        if self.whitespace {
+            terminals.push(Terminal::Whitespace(String::new()));
+        }
+        if self.lenient {
+            terminals.push(Terminal::Error(String::new()));
+        }
+        terminals
+    }
+
+    pub fn tokenize(&self, input: &str) -> Result<Vec<Token>, TokenizeError> {
+        self.tokens(input).into_iter().collect()
+    }
+
+    /// Tokens of input that is already split into chars. Spans, including
+    /// those of errors, count chars rather than bytes.
+    pub fn tokenize_chars(&self, input: &[char]) -> Result<Vec<Token>, TokenizeError> {
+        self.char_tokens(input).into_iter().collect()
+    }
+
+    /// Tokens of `input`, carrying on past errors. Tokenizing works on
+    /// chars, whose spans are then turned into byte offsets.
+    pub(crate) fn tokens(&self, input: &str) -> Vec<Result<Token, TokenizeError>> {
+        let chars: Vec<char> = input.chars().collect();
+        // The byte offset of each char, then of the end of input.
+        let offsets: Vec<usize> = input
+            .char_indices()
+            .map(|(index, _)| index)
+            .chain([input.len()])
+            .collect();
+        let to_bytes = |span: &mut Span| {
+            span.start = offsets[span.start];
+            span.end = offsets[span.end];
+        };
+
+        self.char_tokens(&chars)
+            .into_iter()
+            .map(|result| match result {
+                Ok(mut token) => {
+                    to_bytes(&mut token.span);
+                    Ok(token)
+                }
+                Err(mut error) => {
+                    match &mut error {
+                        TokenizeError::InvalidToken { span, .. }
+                        | TokenizeError::UnterminatedString { span }
+                        | TokenizeError::InvalidEscape { span, .. }
+                        | TokenizeError::MalformedNumber { span, .. } => to_bytes(span),
+                    }
+                    Err(error)
+                }
+            })
+            .collect()
+    }
+
+    /// Tokens of `input` as chars, carrying on past errors.
+    fn char_tokens(&self, input: &[char]) -> Vec<Result<Token, TokenizeError>> {
+        let mut results: Vec<Result<Token, TokenizeError>> = vec![];
+        let mut leading = vec![];
+        // Whether trivia still trails the last token, before a line break.
+        let mut trailing = false;
+
+        for piece in pieces(input, self.whitespace) {
+            match piece {
+                Piece::Trivia(trivia) => match results.last_mut() {
+                    Some(Ok(token)) if trailing => match trivia {
+                        Trivia::Whitespace(text) if text.contains('\n') => {
+                            let (before, after) = text.split_at(text.find('\n').unwrap_or(0) + 1);
+                            token
+                                .trailing_trivia
+                                .push(Trivia::Whitespace(before.to_string()));
+                            if !after.is_empty() {
+                                leading.push(Trivia::Whitespace(after.to_string()));
+                            }
+                            trailing = false;
+                        }
+                        trivia => token.trailing_trivia.push(trivia),
+                    },
+                    _ => leading.push(trivia),
+                },
+                Piece::Token(mut token) => {
+                    token.leading_trivia = std::mem::take(&mut leading);
+                    results.push(Ok(token));
+                    trailing = true;
+                }
+                Piece::Error(_, span) if self.lenient => {
+                    let text = input[span.start..span.end].iter().collect();
+                    let mut token = Token::new(Terminal::Error(text), span);
+                    token.leading_trivia = std::mem::take(&mut leading);
+                    results.push(Ok(token));
+                    trailing = true;
+                }
+                Piece::Error(error, _) => {
+                    results.push(Err(error));
+                    trailing = false;
+                }
+            }
+        }
+
+        if let Some(Ok(token)) = results.last_mut() {
+            token.trailing_trivia.append(&mut leading);
+        }
+
+        results
+    }
+}
+
+impl Lexer for Tokenizer {
+    fn terminal_kinds(&self) -> Option<Vec<Terminal>> {
+        Some(self.terminals())
+    }
+
+    fn tokens(&self, input: &str) -> Result<Vec<Terminal>, ParseError> {
+        Ok(self
+            .tokenize(input)?
+            .into_iter()
+            .map(|token| token.terminal)
+            .collect())
+    }
+
+    fn lex(&self, input: &str) -> Result<Vec<Token>, ParseError> {
+        Ok(self.tokenize(input)?)
+    }
+
+    fn tokens_recovering(&self, input: &str) -> Vec<Result<Token, ParseError>> {
+        Tokenizer::tokens(self, input)
+            .into_iter()
+            .map(|token| Ok(token?))
+            .collect()
+    }
+}
+
+/// A lexer for grammars that are their own lexer: every char of the input,
+/// whitespace included, is a [`Terminal::Char`] token of its own, so rules
+/// can spell out identifiers and the like by [`CharClass`](crate::CharClass).
+/// It never fails.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CharLexer;
+
+impl Lexer for CharLexer {
+    fn tokens(&self, input: &str) -> Result<Vec<Terminal>, ParseError> {
+        Ok(input.chars().map(Terminal::char).collect())
+    }
+
+    fn lex(&self, input: &str) -> Result<Vec<Token>, ParseError> {
+        Ok(input
+            .char_indices()
+            .map(|(start, c)| {
+                Token::new(
+                    Terminal::char(c),
+                    Span {
+                        start,
+                        end: start + c.len_utf8(),
+                    },
+                )
+            })
+            .collect())
+    }
+}
+
+/// A lexer for binary formats: every byte of the input is a
+/// [`Terminal::Byte`] token of its own, with spans in bytes. Bytes in a
+/// range added with [`ByteLexer::with_range`] match a rule naming
+/// [`Terminal::byte_range`] of it, the first such range if several
+/// overlap. It never fails.
+#[derive(Debug, Clone, Default)]
+pub struct ByteLexer {
+    ranges: Vec<RangeInclusive<u8>>,
+}
+
+impl ByteLexer {
+    pub fn new() -> Self {
+        ByteLexer { ranges: vec![] }
+    }
+
+    pub fn with_range(mut self, range: RangeInclusive<u8>) -> Self {
+        self.ranges.push(range);
+        self
+    }
+
+    /// The tokens of `input`, one per byte, ready for
+    /// [`Parser::parse_spanned`](crate::Parser::parse_spanned).
+    pub fn tokenize_bytes(&self, input: &[u8]) -> Vec<(Terminal, Span)> {
+        input
+            .iter()
+            .enumerate()
+            .map(|(start, &byte)| {
+                let terminal = match self.ranges.iter().find(|range| range.contains(&byte)) {
+                    Some(range) => Terminal::Byte {
+                        low: *range.start(),
+                        high: *range.end(),
+                        value: Some(byte),
+                    },
+                    None => Terminal::byte(byte),
+                };
+                (
+                    terminal,
+                    Span {
+                        start,
+                        end: start + 1,
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+impl Lexer for ByteLexer {
+    /// The ranges added, then each byte outside all of them.
+    fn terminal_kinds(&self) -> Option<Vec<Terminal>> {
+        let bytes: Vec<u8> = (0..=u8::MAX).collect();
+        let mut kinds: Vec<Terminal> = vec![];
+        for (terminal, _) in self.tokenize_bytes(&bytes) {
+            let kind = terminal.kind();
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+
+        Some(kinds)
+    }
+
+    fn tokens(&self, input: &str) -> Result<Vec<Terminal>, ParseError> {
+        Ok(self
+            .tokenize_bytes(input.as_bytes())
+            .into_iter()
+            .map(|(terminal, _)| terminal)
+            .collect())
+    }
+
+    fn lex(&self, input: &str) -> Result<Vec<Token>, ParseError> {
+        Ok(self
+            .tokenize_bytes(input.as_bytes())
+            .into_iter()
+            .map(|(terminal, span)| Token::new(terminal, span))
+            .collect())
+    }
+}
+
+enum Piece {
+    Trivia(Trivia),
+    Token(Token),
+    /// Input that does not tokenize, with the span of all that was consumed
+    /// of it, up to where tokenizing carries on.
+    Error(TokenizeError, Span),
+}
+
+/// Splits `input` into trivia and tokens, without attaching one to the
+/// other. Spans count chars. Whitespace is a token if `whitespace` is set.
+fn pieces(input: &[char], whitespace: bool) -> impl Iterator<Item = Piece> + '_ {
+    let mut start = 0;
+
+    std::iter::from_fn(move || {
+        let rest = input.get(start..).filter(|rest| !rest.is_empty())?;
+        let c = rest[0];
+        let text = |len: usize| rest[..len].iter().collect::<String>();
+        let span = |len: usize| Span {
+            start,
+            end: start + len,
+        };
+        let token = |len: usize, terminal| Piece::Token(Token::new(terminal, span(len)));
+        let lexed = |len: usize, result| match result {
+            Ok(token) => Piece::Token(token),
+            Err(error) => Piece::Error(error, span(len)),
+        };
+
+        let (len, piece) = if c.is_whitespace() {
+            let len = run(rest, char::is_whitespace);
+            if whitespace {
+                (len, token(len, Terminal::Whitespace(text(len))))
+            } else {
+                (len, Piece::Trivia(Trivia::Whitespace(text(len))))
+            }
+        } else if c == '"' {
+            let (len, literal) = string_literal(start, rest);
+            (len, lexed(len, literal))
+        } else if rest.starts_with(&['/', '/']) {
+            let len = run(rest, |c| c != '\n');
+            (len, Piece::Trivia(Trivia::Comment(text(len))))
+        } else if starts_number(rest) {
+            let len = run(rest, |c| c.is_ascii_digit() || c == '.');
+            (len, lexed(len, number(text(len), span(len))))
+        } else if let Some((lexeme, terminal)) = operator(rest) {
+            (lexeme.len(), token(lexeme.len(), terminal))
+        } else if c.is_alphabetic() || c == '_' {
+            let len = 1 + run(&rest[1..], |c| c.is_alphanumeric() || c == '_');
+            (len, token(len, Terminal::Identifier(text(len))))
+        } else {
+            // Anything else runs up to the next whitespace, string or terminal.
+            let len = 1
+                + (1..rest.len())
+                    .take_while(|&index| {
+                        let (c, rest) = (rest[index], &rest[index..]);
+                        !c.is_whitespace()
+                            && c != '"'
+                            && !rest.starts_with(&['/', '/'])
+                            && operator(rest).is_none()
+                    })
+                    .count();
+            let error = TokenizeError::InvalidToken {
+                token: text(len),
+                span: span(len),
+            };
+            (len, lexed(len, Err(error)))
+        };
+
+        start += len;
+        Some(piece)
+    })
+}
+
+/// Whether `rest` starts with a number literal other than a lone `0`: a
+/// digit from 1 to 9, `0.`, or a dot before a digit.
+fn starts_number(rest: &[char]) -> bool {
+    match rest {
+        ['1'..='9', ..] | ['0', '.', ..] => true,
+        ['.', next, ..] => next.is_ascii_digit(),
+        _ => false,
+    }
+}
+
+/// The terminal for the digits and dots of `text`.
+fn number(text: String, span: Span) -> Result<Token, TokenizeError> {
+    let terminal = match text.matches('.').count() {
+        0 => text.parse().ok().map(|value| Terminal::Int(Some(value))),
+        1 => text
+            .parse()
+            .ok()
+            .map(|value| Terminal::Float(Some(FloatLiteral(value)))),
+        _ => None,
+    };
+
+    match terminal {
+        Some(terminal) => Ok(Token::new(terminal, span)),
+        None => Err(TokenizeError::MalformedNumber { number: text, span }),
+    }
+}
+
+/// Length of the run of chars at the start of `chars` that satisfy `keep`.
+fn run(chars: &[char], keep: impl Fn(char) -> bool) -> usize {
+    chars.iter().take_while(|c| keep(**c)).count()
+}
+
+/// The operators and `0`, longest first.
+const LEXEMES: [&str; 17] = [
+    "==", "<=", ">=", "->", "+", "-", "*", "(", ")", "[", "]", ",", ";", "0", "=", "<", ">",
+];
+
+/// The operator `rest` starts with, preferring the longest lexeme, so `==`
+/// is one token rather than two `=`. Lexemes are ASCII, so their length
+/// in bytes is their length in chars.
+fn operator(rest: &[char]) -> Option<(&'static str, Terminal)> {
+    let lexeme = LEXEMES
+        .into_iter()
+        .find(|lexeme| lexeme.chars().eq(rest.iter().copied().take(lexeme.len())))?;
+    let terminal = match lexeme {
+        "==" => Terminal::DoubleEqual,
+        "<=" => Terminal::LessEqual,
+        ">=" => Terminal::GreaterEqual,
+        "->" => Terminal::Arrow,
+        "+" => Terminal::Plus,
+        "-" => Terminal::Minus,
+        "*" => Terminal::Star,
+        "(" => Terminal::LeftParen,
+        ")" => Terminal::RightParen,
+        "[" => Terminal::LeftBracket,
+        "]" => Terminal::RightBracket,
+        "," => Terminal::Comma,
+        ";" => Terminal::Semicolon,
+        "0" => Terminal::Zero,
+        "=" => Terminal::Equal,
+        "<" => Terminal::Less,
+        ">" => Terminal::Greater,
+        _ => unreachable!("Every lexeme has a terminal"),
+    };
+
+    Some((lexeme, terminal))
+}
+
+/// Reads the string literal `rest` starts with, whose opening quote is
+/// at `start`, returning its length in chars. On an error the rest of the
+/// literal is still consumed, so tokenizing can carry on after its closing
+/// quote.
+fn string_literal(start: usize, rest: &[char]) -> (usize, Result<Token, TokenizeError>) {
+    let unterminated = TokenizeError::UnterminatedString {
+        span: Span {
+            start,
+            end: start + 1,
+        },
+    };
+    let mut content = String::new();
+    let mut error = None;
+    let mut index = 1;
+
+    loop {
+        let Some(&c) = rest.get(index) else {
+            return (rest.len(), Err(unterminated));
+        };
+
+        match c {
+            '"' => {
+                let span = Span {
+                    start,
+                    end: start + index + 1,
+                };
+
+                let literal = match error {
+                    Some(error) => Err(error),
+                    None => Ok(Token::new(Terminal::StringLiteral(content), span)),
+                };
+                return (index + 1, literal);
+            }
+            '\\' => {
+                match rest.get(index + 1) {
+                    Some('"') => content.push('"'),
+                    Some('\\') => content.push('\\'),
+                    Some('n') => content.push('\n'),
+                    Some(&escape) => {
+                        error.get_or_insert(TokenizeError::InvalidEscape {
+                            escape,
+                            span: Span {
+                                start: start + index,
+                                end: start + index + 2,
+                            },
+                        });
+                    }
+                    None => return (rest.len(), Err(unterminated)),
+                }
+                index += 1;
+            }
+            c => content.push(c),
+        }
+        index += 1;
+    }
+}
+
+impl TokenizeError {
+    /// Where in the input the error is.
+    pub fn span(&self) -> Span {
+        match self {
+            TokenizeError::InvalidToken { span, .. }
+            | TokenizeError::UnterminatedString { span }
+            | TokenizeError::InvalidEscape { span, .. }
+            | TokenizeError::MalformedNumber { span, .. } => *span,
+        }
+    }
+}
+
+/// Gives the offset of the error in whatever its spans count: bytes from
+/// [`Tokenizer::tokenize`], chars from [`Tokenizer::tokenize_chars`].
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenizeError::InvalidToken { token, span } => {
+                write!(f, "Invalid token {token:?} at offset {}", span.start)
+            }
+            TokenizeError::UnterminatedString { span } => {
+                write!(f, "Unterminated string starting at offset {}", span.start)
+            }
+            TokenizeError::InvalidEscape { escape, span } => {
+                write!(f, "Invalid escape '\\{escape}' at offset {}", span.start)
+            }
+            TokenizeError::MalformedNumber { number, span } => {
+                write!(f, "Malformed number {number:?} at offset {}", span.start)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TokenizeError {}
+
+/// Writes the span as `start..end`.
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+impl fmt::Display for Trivia {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trivia::Whitespace(text) | Trivia::Comment(text) => write!(f, "{text}"),
+        }
+    }
+}
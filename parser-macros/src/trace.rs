@@ -0,0 +1,72 @@
+use std::fmt;
+
+use crate::{NonTerminal, ParseError, Parser, StackValue, Terminal};
+
+/// One line of the parse trace, as [`Parser::parse_with_trace`] collects
+/// it. Other parses keep no trace, so they pay nothing for it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEvent {
+    /// A token was pushed onto the stack.
+    Shift(Terminal),
+    /// The stack values shown, in their `Debug` form, were replaced by
+    /// `nt` using `production`, named by its label if it has one.
+    Reduce {
+        values: String,
+        nt: NonTerminal,
+        production: String,
+    },
+    /// The stack after a shift or reduction, in its `Debug` form.
+    Stack(String),
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceEvent::Shift(terminal) => write!(f, "Adding terminal: {terminal}"),
+            TraceEvent::Reduce {
+                values,
+                nt,
+                production,
+            } => write!(
+                f,
+                "Replacing stack values {values} with nonterminal {nt} by {production}"
+            ),
+            TraceEvent::Stack(stack) => write!(f, "Stack state: {stack}"),
+        }
+    }
+}
+
+impl Parser {
+    /// Like [`Parser::parse`], but collects the trace of shifts,
+    /// reductions and stack states. The trace is returned whether or not
+    /// the parse succeeds, up to where it stopped.
+    pub fn parse_with_trace(
+        &self,
+        input: &str,
+    ) -> (Result<StackValue, ParseError>, Vec<TraceEvent>) {
+        let tokens = match self.lexer.lex(input) {
+            Ok(tokens) => tokens,
+            Err(error) => return (Err(error), vec![]),
+        };
+        let parser = match self.dispatched(&tokens) {
+            Ok(parser) => parser,
+            Err(error) => return (Err(error), vec![]),
+        };
+
+        let tables = parser.tables();
+        let mut state = parser.start_state(tables);
+        state.trace = Some(vec![]);
+
+        let result = parser.run(&mut state, tables, tokens.into_iter(), None);
+        let trace = state.trace.take().unwrap_or_default();
+
+        let result = result.and_then(|()| {
+            state
+                .accept(&parser.grammar.starting_symbol)
+                .map_err(|stack| ParseError::BadStack { stack, next: None })
+                .and_then(|tree| parser.post_check(tree))
+        });
+
+        (result, trace)
+    }
+}
@@ -0,0 +1,370 @@
+//! Walks over [`StackValue`] trees. These use an explicit stack instead of
+//! recursion, so deeply nested input cannot overflow the call stack.
+
+use std::fmt;
+
+use crate::{NonTerminal, StackValue, Terminal, Token, Trivia};
+
+impl StackValue {
+    /// Number of trees on the longest path from here to a leaf, counting
+    /// this one. A terminal has depth 0.
+    pub fn depth(&self) -> usize {
+        let mut deepest = 0;
+        let mut pending = vec![(self, 0)];
+
+        while let Some((value, depth)) = pending.pop() {
+            if let StackValue::Tree { head: _, values } = value {
+                deepest = deepest.max(depth + 1);
+                pending.extend(values.iter().map(|child| (child, depth + 1)));
+            }
+        }
+
+        deepest
+    }
+
+    /// Number of values in the tree, trees and terminals alike, counting
+    /// this one.
+    pub fn node_count(&self) -> usize {
+        let mut count = 0;
+        let mut pending = vec![self];
+
+        while let Some(value) = pending.pop() {
+            count += 1;
+            if let StackValue::Tree { head: _, values } = value {
+                pending.extend(values.iter());
+            }
+        }
+
+        count
+    }
+
+    /// The terminals below this value, left to right, which for a parse
+    /// tree is the input it was parsed from.
+    pub fn leaves(&self) -> impl Iterator<Item = &Terminal> {
+        self.tokens().map(|token| &token.terminal)
+    }
+
+    /// A copy of this tree with every terminal replaced by `f` of it, left
+    /// to right. Heads, spans and trivia are kept as they are.
+    pub fn map_terminals<F>(&self, mut f: F) -> StackValue
+    where
+        F: FnMut(&Terminal) -> Terminal,
+    {
+        // Post-order walk, finishing each tree once its children are built.
+        let mut pending = vec![(self, false)];
+        let mut built: Vec<StackValue> = vec![];
+
+        while let Some((value, expanded)) = pending.pop() {
+            match value {
+                StackValue::Terminal(token) => built.push(StackValue::Terminal(Token {
+                    terminal: f(&token.terminal),
+                    ..token.clone()
+                })),
+                StackValue::Tree { head, values } if expanded => {
+                    let values = built.split_off(built.len() - values.len());
+                    built.push(StackValue::Tree {
+                        head: head.clone(),
+                        values,
+                    });
+                }
+                StackValue::Tree { head: _, values } => {
+                    pending.push((value, true));
+                    pending.extend(values.iter().rev().map(|child| (child, false)));
+                }
+            }
+        }
+
+        built.pop().expect("The root is always built")
+    }
+
+    /// Whether the two trees have the same shape: the same heads, and
+    /// terminals of the same [`Terminal::kind`] in the same places. Unlike
+    /// `==`, this ignores spans, trivia and the text of string literals and
+    /// identifiers, so trees parsed from different inputs can compare
+    /// equal.
+    pub fn structural_eq(&self, other: &StackValue) -> bool {
+        let mut pending = vec![(self, other)];
+
+        while let Some(pair) = pending.pop() {
+            match pair {
+                (StackValue::Terminal(left), StackValue::Terminal(right)) => {
+                    if left.terminal.kind() != right.terminal.kind() {
+                        return false;
+                    }
+                }
+                (
+                    StackValue::Tree { head, values },
+                    StackValue::Tree {
+                        head: other_head,
+                        values: other_values,
+                    },
+                ) => {
+                    if head != other_head || values.len() != other_values.len() {
+                        return false;
+                    }
+                    pending.extend(values.iter().zip(other_values));
+                }
+                _ => return false,
+            }
+        }
+
+        true
+    }
+
+    /// The first tree in pre-order, this one included, whose head is
+    /// `head`.
+    pub fn find(&self, head: &NonTerminal) -> Option<&StackValue> {
+        self.find_all(head).next()
+    }
+
+    /// Every tree, this one included, whose head is `head`, in pre-order:
+    /// an outer match comes before the matches nested inside it.
+    pub fn find_all(&self, head: &NonTerminal) -> impl Iterator<Item = &StackValue> + use<'_> {
+        self.find_all_within(head, usize::MAX)
+    }
+
+    /// Like [`StackValue::find`], but only looks `max_depth` trees down,
+    /// where this tree is at depth 0 and its children at depth 1.
+    pub fn find_within(&self, head: &NonTerminal, max_depth: usize) -> Option<&StackValue> {
+        self.find_all_within(head, max_depth).next()
+    }
+
+    /// Like [`StackValue::find_all`], but only looks `max_depth` trees
+    /// down, as with [`StackValue::find_within`].
+    pub fn find_all_within(
+        &self,
+        head: &NonTerminal,
+        max_depth: usize,
+    ) -> impl Iterator<Item = &StackValue> + use<'_> {
+        let head = head.clone();
+        let mut pending = vec![(self, 0)];
+
+        std::iter::from_fn(move || {
+            while let Some((value, depth)) = pending.pop() {
+                if let StackValue::Tree {
+                    head: value_head,
+                    values,
+                } = value
+                {
+                    if depth < max_depth {
+                        pending.extend(values.iter().rev().map(|child| (child, depth + 1)));
+                    }
+                    if *value_head == head {
+                        return Some(value);
+                    }
+                }
+            }
+
+            None
+        })
+    }
+
+    fn tokens(&self) -> impl Iterator<Item = &Token> {
+        let mut pending = vec![self];
+
+        std::iter::from_fn(move || {
+            while let Some(value) = pending.pop() {
+                match value {
+                    StackValue::Tree { head: _, values } => pending.extend(values.iter().rev()),
+                    StackValue::Terminal(token) => return Some(token),
+                }
+            }
+
+            None
+        })
+    }
+
+    /// Trivia before the first token of this value. Empty for a tree that
+    /// matched no tokens.
+    pub fn leading_trivia(&self) -> &[Trivia] {
+        self.first_token()
+            .map(|token| token.leading_trivia.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Trivia after the last token of this value. Empty for a tree that
+    /// matched no tokens.
+    pub fn trailing_trivia(&self) -> &[Trivia] {
+        self.last_token()
+            .map(|token| token.trailing_trivia.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn first_token(&self) -> Option<&Token> {
+        self.tokens().next()
+    }
+
+    fn last_token(&self) -> Option<&Token> {
+        let mut pending = vec![self];
+
+        while let Some(value) = pending.pop() {
+            match value {
+                StackValue::Tree { head: _, values } => pending.extend(values.iter()),
+                StackValue::Terminal(token) => return Some(token),
+            }
+        }
+
+        None
+    }
+}
+
+impl Clone for StackValue {
+    fn clone(&self) -> Self {
+        self.map_terminals(Terminal::clone)
+    }
+}
+
+impl PartialEq for StackValue {
+    fn eq(&self, other: &StackValue) -> bool {
+        let mut pending = vec![(self, other)];
+
+        while let Some(pair) = pending.pop() {
+            match pair {
+                (StackValue::Terminal(left), StackValue::Terminal(right)) => {
+                    if left != right {
+                        return false;
+                    }
+                }
+                (
+                    StackValue::Tree {
+                        head: left_head,
+                        values: left,
+                    },
+                    StackValue::Tree {
+                        head: right_head,
+                        values: right,
+                    },
+                ) => {
+                    if left_head != right_head || left.len() != right.len() {
+                        return false;
+                    }
+                    pending.extend(left.iter().zip(right));
+                }
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
+/// Writes the same as a derived `Debug` would, `{:#?}` included.
+impl fmt::Debug for StackValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        enum Step<'a> {
+            Value(&'a StackValue, usize),
+            Text(String),
+        }
+
+        let pretty = f.alternate();
+        let pad = |depth: usize| "    ".repeat(depth);
+        // A nested pretty `Debug`, with its later lines moved to `depth`.
+        let indent = |text: String, depth: usize| text.replace('\n', &format!("\n{}", pad(depth)));
+
+        let mut pending = vec![Step::Value(self, 0)];
+        while let Some(step) = pending.pop() {
+            match step {
+                Step::Text(text) => f.write_str(&text)?,
+                Step::Value(StackValue::Terminal(token), depth) if pretty => write!(
+                    f,
+                    "Terminal(\n{}{},\n{})",
+                    pad(depth + 1),
+                    indent(format!("{token:#?}"), depth + 1),
+                    pad(depth)
+                )?,
+                Step::Value(StackValue::Terminal(token), _) => write!(f, "Terminal({token:?})")?,
+                Step::Value(StackValue::Tree { head, values }, depth) if pretty => {
+                    write!(
+                        f,
+                        "Tree {{\n{}head: {},\n{}values: [",
+                        pad(depth + 1),
+                        indent(format!("{head:#?}"), depth + 1),
+                        pad(depth + 1)
+                    )?;
+                    if values.is_empty() {
+                        pending.push(Step::Text(format!("],\n{}}}", pad(depth))));
+                        continue;
+                    }
+
+                    f.write_str("\n")?;
+                    pending.push(Step::Text(format!(
+                        "{}],\n{}}}",
+                        pad(depth + 1),
+                        pad(depth)
+                    )));
+                    for child in values.iter().rev() {
+                        pending.push(Step::Text(",\n".to_string()));
+                        pending.push(Step::Value(child, depth + 2));
+                        pending.push(Step::Text(pad(depth + 2)));
+                    }
+                }
+                Step::Value(StackValue::Tree { head, values }, _) => {
+                    write!(f, "Tree {{ head: {head:?}, values: [")?;
+                    pending.push(Step::Text("] }".to_string()));
+                    for (index, child) in values.iter().enumerate().rev() {
+                        pending.push(Step::Value(child, 0));
+                        if index > 0 {
+                            pending.push(Step::Text(", ".to_string()));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for StackValue {
+    fn drop(&mut self) {
+        let StackValue::Tree { head: _, values } = self else {
+            return;
+        };
+
+        // Children are emptied before they are dropped, so each drop only
+        // goes one level deep.
+        let mut pending = std::mem::take(values);
+        while let Some(mut value) = pending.pop() {
+            if let StackValue::Tree { head: _, values } = &mut value {
+                pending.append(values);
+            }
+        }
+    }
+}
+
+/// Writes the tree as an s-expression, such as `(atom '(' (sum ...) ')')`.
+impl fmt::Display for StackValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        enum Step<'a> {
+            Value(&'a StackValue),
+            Close,
+        }
+
+        let mut pending = vec![Step::Value(self)];
+        let mut separate = false;
+
+        while let Some(step) = pending.pop() {
+            match step {
+                Step::Value(value) => {
+                    if separate {
+                        write!(f, " ")?;
+                    }
+
+                    match value {
+                        StackValue::Tree { head, values } => {
+                            write!(f, "({head}")?;
+                            pending.push(Step::Close);
+                            pending.extend(values.iter().rev().map(Step::Value));
+                        }
+                        StackValue::Terminal(token) => write!(f, "'{}'", token.terminal)?,
+                    }
+                }
+                Step::Close => write!(f, ")")?,
+            }
+
+            separate = true;
+        }
+
+        Ok(())
+    }
+}
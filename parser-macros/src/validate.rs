@@ -0,0 +1,87 @@
+use crate::{Expression, Grammar, GrammarError, NonTerminal, ParseTable};
+
+/// Every cell of `table` with more than one action, as
+/// [`GrammarError::Conflict`]s ordered by state, then by terminal column
+/// with the end of input last.
+pub(crate) fn conflicts(table: &ParseTable) -> Vec<GrammarError> {
+    let mut conflicts = vec![];
+
+    for state in 0..table.state_count() {
+        for lookahead in table.terminals().iter().map(Some).chain([None]) {
+            let actions = table.actions(state, lookahead);
+            if actions.len() > 1 {
+                conflicts.push(GrammarError::Conflict {
+                    state,
+                    lookahead: lookahead.cloned(),
+                    actions: actions.to_vec(),
+                });
+            }
+        }
+    }
+
+    conflicts
+}
+
+impl Grammar {
+    /// Checks that the grammar is usable, reporting the first problem found.
+    ///
+    /// In order, every nonterminal used must have rules, every nonterminal
+    /// with rules must be reachable from the starting symbol and able to
+    /// derive a sentence, and the parse table must be free of conflicts.
+    /// Symbols are listed in the order the rules reach them.
+    pub fn validate(&self) -> Result<(), GrammarError> {
+        let (order, _) = self.symbols_in_order();
+
+        if let Some(undefined) = order.iter().find(|nt| !self.rules.contains_key(nt)) {
+            return Err(GrammarError::Undefined(undefined.clone()));
+        }
+
+        let reachable = self.reachable();
+        let unreachable: Vec<NonTerminal> = order
+            .iter()
+            .filter(|nt| !reachable.contains(nt))
+            .cloned()
+            .collect();
+        if !unreachable.is_empty() {
+            return Err(GrammarError::Unreachable(unreachable));
+        }
+
+        let productive = self.shortest_sentences();
+        let non_productive: Vec<NonTerminal> = order
+            .iter()
+            .filter(|nt| !productive.contains_key(nt))
+            .cloned()
+            .collect();
+        if !non_productive.is_empty() {
+            return Err(GrammarError::NonProductive(non_productive));
+        }
+
+        match conflicts(&ParseTable::new(self)).into_iter().next() {
+            Some(conflict) => Err(conflict),
+            None => Ok(()),
+        }
+    }
+
+    /// Nonterminals the starting symbol reaches, itself included, in the
+    /// order the rules reach them.
+    pub(crate) fn reachable(&self) -> Vec<NonTerminal> {
+        let mut reached = vec![self.starting_symbol.clone()];
+        let mut index = 0;
+
+        while let Some(non_terminal) = reached.get(index).cloned() {
+            index += 1;
+
+            for production in self.rules.get(&non_terminal).into_iter().flatten() {
+                for expr in production {
+                    if let Expression::NonTerminal(nt) = expr
+                        && !reached.contains(nt)
+                    {
+                        reached.push(nt.clone());
+                    }
+                }
+            }
+        }
+
+        reached
+    }
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use parser_macros::{ParseError, Parser, Span, Terminal, Token};
+
+/// Identifiers are worth their length and trees the sum of their children,
+/// but nothing may be worth more than 5.
+fn eval(input: &str) -> Result<usize, ParseError> {
+    let check = |value: usize| {
+        if value > 5 {
+            Err(format!("Value {value} is above 5"))
+        } else {
+            Ok(value)
+        }
+    };
+
+    Parser::new(common::variable_grammar()).parse_with_actions(
+        input,
+        |token: &Token| match &token.terminal {
+            Terminal::Identifier(name) => check(name.len()),
+            _ => Ok(0),
+        },
+        |_, values| check(values.into_iter().sum()),
+    )
+}
+
+#[test]
+fn actions_compute_values() {
+    assert_eq!(eval("ab + cd"), Ok(4));
+    assert_eq!(eval("( a + b ) * 0 - abc"), Ok(5));
+    assert_eq!(eval("0"), Ok(0));
+}
+
+#[test]
+fn actions_can_reject() {
+    let error = eval("abc + def").unwrap_err();
+    assert_eq!(
+        error,
+        ParseError::Semantic {
+            message: "Value 6 is above 5".to_string(),
+            span: Some(Span { start: 0, end: 9 }),
+        }
+    );
+    assert_eq!(error.to_string(), "Value 6 is above 5 at 0..9");
+    assert_eq!(error.span(), Some(Span { start: 0, end: 9 }));
+
+    // A leaf can reject too, and rejection comes before the syntax error
+    // after it.
+    assert_eq!(
+        eval("a + abcdef )"),
+        Err(ParseError::Semantic {
+            message: "Value 6 is above 5".to_string(),
+            span: Some(Span { start: 4, end: 10 }),
+        })
+    );
+
+    // Syntax errors are the same as without actions.
+    let parser = Parser::new(common::variable_grammar());
+    assert_eq!(eval("a +"), Err(parser.parse("a +").unwrap_err()));
+    assert_eq!(eval("a )"), Err(parser.parse("a )").unwrap_err()));
+}
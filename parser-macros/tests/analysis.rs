@@ -0,0 +1,679 @@
+mod common;
+
+use parser_macros::{
+    ByteLexer, CharClass, CharLexer, Expression, Grammar, Lint, NonTerminal, Parser, Severity,
+    Terminal, Tokenizer,
+};
+use std::collections::{HashMap, HashSet};
+
+#[test]
+fn nullable_nonterminals() {
+    assert!(
+        common::arithmetic_grammar()
+            .nullable_nonterminals()
+            .is_empty()
+    );
+
+    let mut rules = HashMap::new();
+
+    rules.insert(
+        NonTerminal::Sum,
+        vec![vec![
+            Expression::NonTerminal(NonTerminal::Sub),
+            Expression::NonTerminal(NonTerminal::Atom),
+        ]],
+    );
+    rules.insert(NonTerminal::Sub, vec![vec![]]);
+    rules.insert(
+        NonTerminal::Atom,
+        vec![vec![Expression::NonTerminal(NonTerminal::Number)], vec![]],
+    );
+    rules.insert(
+        NonTerminal::Number,
+        vec![vec![Expression::Terminal(Terminal::Zero)]],
+    );
+    rules.insert(
+        NonTerminal::Mult,
+        vec![vec![
+            Expression::NonTerminal(NonTerminal::Sub),
+            Expression::Terminal(Terminal::Star),
+        ]],
+    );
+
+    let g = Grammar::new(NonTerminal::Sum, rules);
+
+    assert_eq!(
+        g.nullable_nonterminals(),
+        HashSet::from([NonTerminal::Sum, NonTerminal::Sub, NonTerminal::Atom])
+    );
+}
+
+#[test]
+fn shortest_sentences() {
+    let shortest = common::arithmetic_grammar().shortest_sentences();
+
+    for nt in [
+        NonTerminal::Sum,
+        NonTerminal::Sub,
+        NonTerminal::Mult,
+        NonTerminal::Atom,
+        NonTerminal::Number,
+    ] {
+        assert_eq!(shortest.get(&nt), Some(&vec![Terminal::Zero]));
+    }
+
+    let shortest = common::list_grammar().shortest_sentences();
+
+    assert_eq!(
+        shortest.get(&NonTerminal::List),
+        Some(&vec![Terminal::LeftBracket, Terminal::RightBracket])
+    );
+    assert_eq!(shortest.get(&NonTerminal::Elements), Some(&vec![]));
+}
+
+#[test]
+fn shortest_sentences_skip_non_productive() {
+    let mut rules = HashMap::new();
+
+    rules.insert(
+        NonTerminal::Sum,
+        vec![
+            vec![
+                Expression::NonTerminal(NonTerminal::Mult),
+                Expression::Terminal(Terminal::Plus),
+            ],
+            vec![
+                Expression::Terminal(Terminal::LeftParen),
+                Expression::NonTerminal(NonTerminal::Atom),
+                Expression::Terminal(Terminal::RightParen),
+            ],
+            vec![
+                Expression::Terminal(Terminal::Minus),
+                Expression::Terminal(Terminal::Minus),
+                Expression::Terminal(Terminal::Minus),
+            ],
+        ],
+    );
+    rules.insert(
+        NonTerminal::Mult,
+        vec![vec![
+            Expression::NonTerminal(NonTerminal::Mult),
+            Expression::Terminal(Terminal::Star),
+        ]],
+    );
+    rules.insert(
+        NonTerminal::Atom,
+        vec![vec![Expression::Terminal(Terminal::Zero)]],
+    );
+
+    let g = Grammar::new(NonTerminal::Sum, rules);
+
+    let shortest = g.shortest_sentences();
+
+    assert_eq!(shortest.get(&NonTerminal::Mult), None);
+    assert_eq!(
+        shortest.get(&NonTerminal::Sum),
+        Some(&vec![
+            Terminal::LeftParen,
+            Terminal::Zero,
+            Terminal::RightParen
+        ])
+    );
+}
+
+#[test]
+fn equivalent_up_to() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    // Right recursive sums of zeros describe the same language as the left
+    // recursive arithmetic grammar restricted to `+`.
+    let left = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(
+            NonTerminal::Sum,
+            vec![
+                vec![N(NonTerminal::Sum), T(Terminal::Plus), T(Terminal::Zero)],
+                vec![T(Terminal::Zero)],
+            ],
+        )],
+    );
+    let right = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(
+            NonTerminal::Sum,
+            vec![
+                vec![T(Terminal::Zero), T(Terminal::Plus), N(NonTerminal::Sum)],
+                vec![T(Terminal::Zero)],
+            ],
+        )],
+    );
+
+    assert_eq!(left.equivalent_up_to(&right, 7), Ok(()));
+    assert_eq!(
+        left.equivalent_up_to(&common::arithmetic_grammar(), 7),
+        Err(vec![
+            Terminal::LeftParen,
+            Terminal::Zero,
+            Terminal::RightParen
+        ])
+    );
+    assert_eq!(
+        common::list_grammar().equivalent_up_to(&common::list_grammar(), 6),
+        Ok(())
+    );
+}
+
+#[test]
+fn unit_cycles() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    assert!(!common::arithmetic_grammar().has_cycle());
+
+    let grammar = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![vec![N(NonTerminal::Sub)], vec![N(NonTerminal::Atom)]],
+            ),
+            (
+                NonTerminal::Sub,
+                vec![vec![N(NonTerminal::Sum)], vec![T(Terminal::Zero)]],
+            ),
+            (
+                NonTerminal::Atom,
+                vec![
+                    vec![N(NonTerminal::Sub)],
+                    vec![N(NonTerminal::Atom)],
+                    vec![N(NonTerminal::Atom), T(Terminal::Plus)],
+                ],
+            ),
+        ],
+    );
+
+    assert!(grammar.has_cycle());
+    assert_eq!(
+        grammar.unit_cycles(),
+        vec![
+            vec![NonTerminal::Sum, NonTerminal::Sub],
+            vec![NonTerminal::Sum, NonTerminal::Atom, NonTerminal::Sub],
+            vec![NonTerminal::Atom],
+        ]
+    );
+}
+
+#[test]
+fn is_regular() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    assert!(!common::arithmetic_grammar().is_regular());
+    assert!(!common::list_grammar().is_regular());
+
+    // `sum -> '0' '+' sum | '0'`
+    let right = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(
+            NonTerminal::Sum,
+            vec![
+                vec![T(Terminal::Zero), T(Terminal::Plus), N(NonTerminal::Sum)],
+                vec![T(Terminal::Zero)],
+            ],
+        )],
+    );
+    assert!(right.is_regular());
+
+    // `sum -> sum '+' '0' | number`, `number -> '0'`
+    let left = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![
+                    vec![N(NonTerminal::Sum), T(Terminal::Plus), T(Terminal::Zero)],
+                    vec![N(NonTerminal::Number)],
+                ],
+            ),
+            (NonTerminal::Number, vec![vec![T(Terminal::Zero)], vec![]]),
+        ],
+    );
+    assert!(left.is_regular());
+
+    // Both forms at once is not accepted.
+    let mut mixed = right.clone();
+    mixed.rules.insert(
+        NonTerminal::Number,
+        vec![vec![N(NonTerminal::Sum), T(Terminal::Minus)]],
+    );
+    assert!(!mixed.is_regular());
+}
+
+#[test]
+fn common_prefixes() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    assert!(common::arithmetic_grammar().common_prefixes().is_empty());
+
+    // `elements -> number ',' elements | number | ε`
+    assert_eq!(
+        common::list_grammar().common_prefixes(),
+        vec![(NonTerminal::Elements, vec![N(NonTerminal::Number)])]
+    );
+
+    // `sum -> '(' sum ')' '+' | '(' sum ')' '-' | '0' '+' | '0'`
+    let grammar = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(
+            NonTerminal::Sum,
+            vec![
+                vec![
+                    T(Terminal::LeftParen),
+                    N(NonTerminal::Sum),
+                    T(Terminal::RightParen),
+                    T(Terminal::Plus),
+                ],
+                vec![
+                    T(Terminal::LeftParen),
+                    N(NonTerminal::Sum),
+                    T(Terminal::RightParen),
+                    T(Terminal::Minus),
+                ],
+                vec![T(Terminal::Zero), T(Terminal::Plus)],
+                vec![T(Terminal::Zero)],
+            ],
+        )],
+    );
+    assert_eq!(
+        grammar.common_prefixes(),
+        vec![
+            (
+                NonTerminal::Sum,
+                vec![
+                    T(Terminal::LeftParen),
+                    N(NonTerminal::Sum),
+                    T(Terminal::RightParen),
+                ]
+            ),
+            (NonTerminal::Sum, vec![T(Terminal::Zero)]),
+        ]
+    );
+}
+
+#[test]
+fn enumerate() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let sentences: Vec<String> = common::arithmetic_grammar()
+        .enumerate(5)
+        .iter()
+        .map(|sentence| {
+            sentence
+                .iter()
+                .map(Terminal::to_string)
+                .collect::<Vec<String>>()
+                .join(" ")
+        })
+        .collect();
+    assert_eq!(sentences, ["0", "( 0 )", "0 * 0", "0 + 0", "0 - 0"]);
+
+    // `sum -> number number | number`, `number -> '0' | ε`, which has only
+    // three sentences.
+    let finite = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![
+                    vec![N(NonTerminal::Number), N(NonTerminal::Number)],
+                    vec![N(NonTerminal::Number)],
+                ],
+            ),
+            (NonTerminal::Number, vec![vec![T(Terminal::Zero)], vec![]]),
+        ],
+    );
+    assert_eq!(
+        finite.enumerate(100),
+        vec![
+            vec![],
+            vec![Terminal::Zero],
+            vec![Terminal::Zero, Terminal::Zero]
+        ]
+    );
+
+    assert_eq!(
+        common::list_grammar().enumerate(0),
+        Vec::<Vec<Terminal>>::new()
+    );
+    assert_eq!(common::list_grammar().enumerate(40).len(), 40);
+}
+
+#[test]
+fn first_2() {
+    let first = common::arithmetic_grammar().first_k(2);
+
+    let set = |sequences: &[&[Terminal]]| -> HashSet<Vec<Terminal>> {
+        sequences.iter().map(|sequence| sequence.to_vec()).collect()
+    };
+    use Terminal::{LeftParen as L, Minus, Plus, Star, Zero};
+
+    assert_eq!(first[&NonTerminal::Number], set(&[&[Zero]]));
+    assert_eq!(
+        first[&NonTerminal::Atom],
+        set(&[&[Zero], &[L, L], &[L, Zero]])
+    );
+    assert_eq!(
+        first[&NonTerminal::Sum],
+        set(&[
+            &[Zero],
+            &[L, L],
+            &[L, Zero],
+            &[Zero, Star],
+            &[Zero, Minus],
+            &[Zero, Plus],
+        ])
+    );
+
+    assert_eq!(
+        common::list_grammar().first_k(1)[&NonTerminal::Elements],
+        set(&[&[], &[Zero]])
+    );
+}
+
+#[test]
+fn lint() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let lint = |severity, message: &str| Lint {
+        severity,
+        message: message.to_string(),
+    };
+
+    assert_eq!(
+        common::arithmetic_grammar().lint(),
+        vec![
+            lint(Severity::Note, "Nonterminal sum is left recursive"),
+            lint(Severity::Note, "Nonterminal sub is left recursive"),
+            lint(Severity::Note, "Nonterminal mult is left recursive"),
+        ]
+    );
+
+    let grammar = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![
+                    vec![N(NonTerminal::Sub), T(Terminal::Plus), N(NonTerminal::Atom)],
+                    vec![N(NonTerminal::Sub)],
+                    vec![N(NonTerminal::Sub)],
+                ],
+            ),
+            (
+                NonTerminal::Sub,
+                vec![vec![N(NonTerminal::Sub)], vec![T(Terminal::Zero)]],
+            ),
+            (NonTerminal::Atom, vec![vec![N(NonTerminal::List)]]),
+            (NonTerminal::Number, vec![vec![T(Terminal::Zero)]]),
+        ],
+    );
+
+    let lints = grammar.lint();
+    assert_eq!(
+        lints,
+        vec![
+            lint(Severity::Error, "Nonterminal list is used but has no rules"),
+            lint(
+                Severity::Warning,
+                "Nonterminals not reachable from the starting symbol: number"
+            ),
+            lint(
+                Severity::Warning,
+                "Nonterminals that cannot derive any sentence: atom"
+            ),
+            lint(Severity::Error, "Unit productions form a cycle: sub -> sub"),
+            lint(
+                Severity::Error,
+                "Production sum -> sub is listed more than once"
+            ),
+            lint(Severity::Note, "Nonterminal sub is left recursive"),
+            lint(Severity::Note, "Alternatives of sum share the prefix sub"),
+        ]
+    );
+    assert_eq!(
+        lints[1].to_string(),
+        "warning: Nonterminals not reachable from the starting symbol: number"
+    );
+}
+
+#[test]
+fn count_derivations() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let arithmetic = common::arithmetic_grammar();
+    for input in ["0", "0 + 0 * 0", "( 0 - 0 ) * ( 0 + 0 )"] {
+        assert_eq!(arithmetic.count_derivations(input, 10), Ok(1), "{input}");
+    }
+    assert_eq!(arithmetic.count_derivations("0 +", 10), Ok(0));
+
+    let ambiguous = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(
+            NonTerminal::Sum,
+            vec![
+                vec![N(NonTerminal::Sum), T(Terminal::Plus), N(NonTerminal::Sum)],
+                vec![T(Terminal::Zero)],
+            ],
+        )],
+    );
+    assert_eq!(ambiguous.count_derivations("0 + 0 + 0", 10), Ok(2));
+    assert_eq!(ambiguous.count_derivations("0 + 0 + 0 + 0", 10), Ok(5));
+    assert_eq!(ambiguous.count_derivations("0 + 0 + 0 + 0", 3), Ok(3));
+    assert_eq!(ambiguous.is_ambiguous("0 + 0 + 0"), Ok(true));
+    assert_eq!(ambiguous.is_ambiguous("0 + 0"), Ok(false));
+    assert_eq!(ambiguous.is_ambiguous("0 +"), Ok(false));
+    assert_eq!(arithmetic.is_ambiguous("0 + 0 + 0"), Ok(false));
+
+    // `sum -> sub -> sum` can repeat any number of times.
+    let cyclic = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![vec![N(NonTerminal::Sub)], vec![T(Terminal::Zero)]],
+            ),
+            (NonTerminal::Sub, vec![vec![N(NonTerminal::Sum)]]),
+        ],
+    );
+    assert_eq!(cyclic.count_derivations("0", 10), Ok(10));
+    assert_eq!(cyclic.count_derivations("0 0", 10), Ok(0));
+
+    assert_eq!(
+        common::list_grammar().count_derivations("[ 0 , 0 ]", 10),
+        Ok(1)
+    );
+}
+
+#[test]
+fn derivation_chart() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let ambiguous = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(
+            NonTerminal::Sum,
+            vec![
+                vec![N(NonTerminal::Sum), T(Terminal::Plus), N(NonTerminal::Sum)],
+                vec![T(Terminal::Zero)],
+            ],
+        )],
+    );
+    let input = "0 + 0 + 0 + 0";
+    let chart = ambiguous
+        .derivation_chart(input, 10)
+        .expect("Should be able to tokenize");
+
+    assert_eq!(chart.tokens().len(), 7);
+    assert_eq!(chart.count(&NonTerminal::Sum, 0, 7), 5);
+    assert_eq!(
+        chart.count(&NonTerminal::Sum, 0, 7),
+        ambiguous.count_derivations(input, 10).unwrap()
+    );
+    assert_eq!(chart.count(&NonTerminal::Sum, 0, 5), 2);
+    assert_eq!(chart.count(&NonTerminal::Sum, 2, 5), 1);
+    assert_eq!(chart.count(&NonTerminal::Sum, 1, 2), 0);
+    assert_eq!(chart.count(&NonTerminal::Sum, 0, 8), 0);
+    assert_eq!(chart.count(&NonTerminal::Atom, 0, 1), 0);
+}
+
+#[test]
+fn ambiguity_report() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let ambiguous = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(
+            NonTerminal::Sum,
+            vec![
+                vec![N(NonTerminal::Sum), T(Terminal::Plus), N(NonTerminal::Sum)],
+                vec![T(Terminal::Zero)],
+            ],
+        )],
+    );
+    assert_eq!(
+        ambiguous.ambiguity_report().as_deref(),
+        Some(
+            "The grammar is ambiguous: `0 + 0 + 0` parses as a sum in two ways.\n  \
+             first:  (sum (sum '0') '+' (sum (sum '0') '+' (sum '0')))\n  \
+             second: (sum (sum (sum '0') '+' (sum '0')) '+' (sum '0'))\n"
+        )
+    );
+
+    let cyclic = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![vec![N(NonTerminal::Sub)], vec![T(Terminal::Zero)]],
+            ),
+            (NonTerminal::Sub, vec![vec![N(NonTerminal::Sum)]]),
+        ],
+    );
+    let report = cyclic.ambiguity_report().expect("Should be ambiguous");
+    assert!(report.contains("(sum (sub (sum '0')))"), "{report}");
+
+    assert_eq!(common::arithmetic_grammar().ambiguity_report(), None);
+    assert_eq!(common::list_grammar().ambiguity_report(), None);
+}
+
+#[test]
+fn symbols_in_order() {
+    let grammar = common::arithmetic_grammar();
+    assert_eq!(
+        grammar.symbols_in_order(),
+        (
+            vec![
+                NonTerminal::Sum,
+                NonTerminal::Sub,
+                NonTerminal::Mult,
+                NonTerminal::Atom,
+                NonTerminal::Number,
+            ],
+            vec![
+                Terminal::Plus,
+                Terminal::Minus,
+                Terminal::Star,
+                Terminal::LeftParen,
+                Terminal::RightParen,
+                Terminal::Zero,
+            ],
+        )
+    );
+
+    // Unreached rules come last, and rebuilding the rules, which rehashes
+    // them, changes nothing.
+    let mut with_list = grammar.clone();
+    with_list.rules.extend(common::list_grammar().rules);
+    let order = with_list.symbols_in_order();
+    for _ in 0..10 {
+        let rebuilt = Grammar::from_rules(
+            NonTerminal::Sum,
+            with_list
+                .rules
+                .iter()
+                .map(|(nt, alternatives)| (nt.clone(), alternatives.clone()))
+                .collect::<HashMap<_, _>>(),
+        );
+        assert_eq!(rebuilt.symbols_in_order(), order);
+    }
+    assert_eq!(order.0[5..], [NonTerminal::Elements, NonTerminal::List]);
+    assert_eq!(order.1[..6], grammar.symbols_in_order().1);
+}
+
+#[test]
+fn unused_and_undeclared_terminals() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let unused = parser.unused_terminals().unwrap();
+    assert!(unused.contains(&Terminal::Comma));
+    assert!(!unused.contains(&Terminal::Star));
+    assert_eq!(parser.undeclared_terminals(), Some(vec![]));
+
+    // The grammar alone is checked against the default tokenizer, as
+    // a parser using it is.
+    let grammar = common::arithmetic_grammar();
+    assert_eq!(grammar.unused_terminals(), unused);
+    assert_eq!(grammar.undeclared_terminals(), vec![]);
+
+    // Products dropped, so nothing uses `Star` any more.
+    let mut grammar = common::arithmetic_grammar();
+    grammar.rules.insert(
+        NonTerminal::Mult,
+        vec![vec![Expression::NonTerminal(NonTerminal::Atom)]],
+    );
+    let without_star = Parser::new(grammar.clone()).unused_terminals().unwrap();
+    assert!(without_star.contains(&Terminal::Star));
+    assert_eq!(without_star.len(), unused.len() + 1);
+
+    grammar.rules.insert(
+        NonTerminal::Number,
+        vec![
+            vec![Expression::Terminal(Terminal::Zero)],
+            vec![Expression::Terminal(Terminal::any(CharClass::Digit))],
+            vec![Expression::Terminal(Terminal::Identifier("x".to_string()))],
+        ],
+    );
+    let parser = Parser::new(grammar.clone());
+    assert_eq!(
+        parser.undeclared_terminals(),
+        Some(vec![Terminal::any(CharClass::Digit)])
+    );
+    assert_eq!(
+        grammar.undeclared_terminals(),
+        vec![Terminal::any(CharClass::Digit)]
+    );
+    assert!(
+        !parser
+            .unused_terminals()
+            .unwrap()
+            .contains(&Terminal::Identifier(String::new()))
+    );
+
+    // The terminals come from the lexer the parser actually uses.
+    let parser = Parser::with_lexer(common::arithmetic_grammar(), Tokenizer::with_whitespace());
+    let with_whitespace = parser.unused_terminals().unwrap();
+    assert!(with_whitespace.contains(&Terminal::Whitespace(String::new())));
+    assert_eq!(with_whitespace.len(), unused.len() + 1);
+    assert_eq!(common::arithmetic_grammar().unused_terminals(), unused);
+
+    let parser = Parser::with_lexer(common::arithmetic_grammar(), ByteLexer::new());
+    assert_eq!(
+        parser.unused_terminals().map(|unused| unused.len()),
+        Some(256)
+    );
+    assert_eq!(
+        parser.undeclared_terminals().unwrap()[..2],
+        [Terminal::Plus, Terminal::Minus]
+    );
+    assert_eq!(
+        Parser::with_lexer(grammar, CharLexer).unused_terminals(),
+        None
+    );
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use parser_macros::{Node, NonTerminal, Parser, Terminal};
+
+/// Counts allocations made by the current thread, so tests running in
+/// parallel do not disturb each other.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
+#[test]
+fn arena_matches_owned_tree() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let (arena, root) = parser
+        .parse_into_arena("( 0 + 0 ) * 0")
+        .expect("Should be able to parse");
+    let tree = parser
+        .parse("( 0 + 0 ) * 0")
+        .expect("Should be able to parse");
+
+    assert_eq!(arena.to_tree(root).to_string(), tree.to_string());
+
+    assert!(matches!(
+        arena[root],
+        Node::Tree {
+            head: NonTerminal::Sum,
+            ..
+        }
+    ));
+
+    // mult -> mult '*' atom
+    let mult = arena.children(arena.children(root)[0])[0];
+    let children = arena.children(mult);
+    assert_eq!(children.len(), 3);
+    assert!(matches!(
+        &arena[children[1]],
+        Node::Terminal(token) if token.terminal == Terminal::Star
+    ));
+}
+
+#[test]
+fn arena_allocates_less() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let input = vec!["( 0 + 0 * 0 ) - 0"; 50].join(" + ");
+
+    let (tree, owned) = allocations(|| parser.parse(&input).expect("Should be able to parse"));
+    let ((arena, root), flat) = allocations(|| {
+        parser
+            .parse_into_arena(&input)
+            .expect("Should be able to parse")
+    });
+
+    assert!(flat < owned);
+    assert_eq!(arena.to_tree(root).to_string(), tree.to_string());
+}
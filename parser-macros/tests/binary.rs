@@ -0,0 +1,137 @@
+#![cfg(feature = "binary")]
+
+mod common;
+
+use std::collections::HashSet;
+
+use parser_macros::{
+    CharClass, DebugState, DecodeError, FloatLiteral, Grammar, NonTerminal, ParseError, Parser,
+    Span, StepResult, Terminal, Tokenizer,
+};
+
+#[test]
+fn round_trips() {
+    let mut grammar = common::variable_grammar();
+    grammar.reduce_lookahead.insert(
+        (NonTerminal::Atom, 1),
+        HashSet::from([
+            Terminal::Plus,
+            Terminal::Semicolon,
+            Terminal::StringLiteral("é\"".to_string()),
+            Terminal::any(CharClass::Letter),
+            Terminal::char('é'),
+            Terminal::byte(0xff),
+            Terminal::byte_range(0x00..=0x7f),
+            Terminal::Int(Some(-7)),
+            Terminal::Float(Some(FloatLiteral(2.5))),
+            Terminal::Float(None),
+            Terminal::Error("#".to_string()),
+        ]),
+    );
+    grammar
+        .labels
+        .insert((NonTerminal::Sum, 0), "addition ✓".to_string());
+
+    for grammar in [
+        grammar,
+        common::list_grammar(),
+        common::arithmetic_grammar().as_chomsky_normal_form(),
+    ] {
+        let bytes = grammar.to_bytes();
+        assert_eq!(Grammar::from_bytes(&bytes), Ok(grammar.clone()));
+        assert_eq!(bytes, grammar.clone().to_bytes(), "Encoding is stable");
+    }
+}
+
+#[test]
+fn rejects_bad_input() {
+    let bytes = common::arithmetic_grammar().to_bytes();
+
+    assert_eq!(
+        Grammar::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(DecodeError::UnexpectedEnd)
+    );
+    assert_eq!(
+        Grammar::from_bytes(&[1]),
+        Err(DecodeError::UnsupportedVersion(1))
+    );
+    assert_eq!(
+        Grammar::from_bytes(&[2, 42]),
+        Err(DecodeError::InvalidTag { tag: 42, offset: 1 })
+    );
+    assert_eq!(
+        Grammar::from_bytes(&[bytes.as_slice(), &[0]].concat()),
+        Err(DecodeError::TrailingBytes {
+            offset: bytes.len()
+        })
+    );
+}
+
+#[test]
+fn resumes_a_checkpointed_parse() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let input = "( 0 + 0 // first\n ) * ( 0 - 0 )";
+    let tokens = Tokenizer::new()
+        .tokenize(input)
+        .expect("Should be able to tokenize");
+    let (first, second) = tokens.split_at(5);
+
+    let mut state = parser.debug_start("");
+    state.extend(first.iter().cloned());
+    while state.next_token().is_some() {
+        parser.debug_step(&mut state);
+    }
+
+    let bytes = state.to_bytes();
+    let mut restored = DebugState::from_bytes(&bytes).expect("Should be able to decode");
+    assert_eq!(restored.stack(), state.stack());
+    assert_eq!(restored.position(), 5);
+    assert_eq!(restored.to_bytes(), bytes);
+
+    restored.extend(second.iter().cloned());
+    let mut last = parser.debug_step(&mut restored);
+    while !restored.is_finished() {
+        last = parser.debug_step(&mut restored);
+    }
+    assert_eq!(last, StepResult::Accepted(parser.parse(input).unwrap()));
+
+    assert_eq!(
+        DebugState::from_bytes(&bytes[..bytes.len() - 1]).err(),
+        Some(DecodeError::UnexpectedEnd)
+    );
+    assert_eq!(
+        DebugState::from_bytes(&[1]).err(),
+        Some(DecodeError::UnsupportedVersion(1))
+    );
+}
+
+#[test]
+fn resumed_parses_keep_to_the_nesting_limit() {
+    let mut parser = Parser::new(common::arithmetic_grammar());
+    parser.set_max_nesting_depth(Some(1));
+
+    let mut state = parser.debug_start("( ( 0");
+    while state.next_token().is_some() {
+        parser.debug_step(&mut state);
+    }
+
+    let mut restored = DebugState::from_bytes(&state.to_bytes()).expect("Should be able to decode");
+    restored.extend(
+        Tokenizer::new()
+            .tokenize("( ( 0 ) )")
+            .expect("Should be able to tokenize")
+            .into_iter()
+            .skip(3),
+    );
+    let mut last = parser.debug_step(&mut restored);
+    while !restored.is_finished() {
+        last = parser.debug_step(&mut restored);
+    }
+    assert_eq!(
+        last,
+        StepResult::Error(ParseError::NestingTooDeep {
+            limit: 1,
+            span: Span { start: 2, end: 3 },
+        })
+    );
+}
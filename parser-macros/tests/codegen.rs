@@ -0,0 +1,89 @@
+mod common;
+
+use parser_macros::{Expression, Grammar, GrammarError, LlConflict, NonTerminal, Terminal};
+use std::collections::HashMap;
+
+/// Sums of zeros and parenthesized sums, with the recursion on the right
+/// so one token always picks the alternative.
+fn ll1_grammar() -> Grammar {
+    let mut rules = HashMap::new();
+    rules.insert(
+        NonTerminal::Sum,
+        vec![vec![
+            Expression::NonTerminal(NonTerminal::Atom),
+            Expression::NonTerminal(NonTerminal::Sub),
+        ]],
+    );
+    rules.insert(
+        NonTerminal::Sub,
+        vec![
+            vec![
+                Expression::Terminal(Terminal::Plus),
+                Expression::NonTerminal(NonTerminal::Atom),
+                Expression::NonTerminal(NonTerminal::Sub),
+            ],
+            vec![],
+        ],
+    );
+    rules.insert(
+        NonTerminal::Atom,
+        vec![
+            vec![
+                Expression::Terminal(Terminal::LeftParen),
+                Expression::NonTerminal(NonTerminal::Sum),
+                Expression::Terminal(Terminal::RightParen),
+            ],
+            vec![Expression::Terminal(Terminal::Zero)],
+        ],
+    );
+    Grammar::new(NonTerminal::Sum, rules)
+}
+
+#[test]
+fn generate_rust() {
+    let source = ll1_grammar().generate_rust().unwrap();
+
+    assert!(source.contains("pub fn parse(tokens: &[&str]) -> Result<Tree, String>"));
+    assert!(source.contains("let tree = parser.parse_sum()?;"));
+    for nt in ["sum", "sub", "atom"] {
+        assert_eq!(
+            source.matches(&format!("fn parse_{nt}(&mut self)")).count(),
+            1
+        );
+    }
+    assert!(source.contains(
+        r#"Some("+") => Ok(Tree::Node("sub", vec![self.expect("+")?, self.parse_atom()?, self.parse_sub()?])),"#
+    ));
+    assert!(source.contains(r#"None | Some(")") => Ok(Tree::Node("sub", vec![])),"#));
+    assert_eq!(source, ll1_grammar().generate_rust().unwrap());
+}
+
+#[test]
+fn generate_rust_rejects_conflicts() {
+    let conflicts = |nt: NonTerminal, lookaheads: &[Terminal]| {
+        lookaheads
+            .iter()
+            .map(|t| LlConflict {
+                non_terminal: nt.clone(),
+                lookahead: Some(t.clone()),
+                alternatives: vec![0, 1],
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let Err(GrammarError::NotLl1(found)) = common::list_grammar().generate_rust() else {
+        panic!("the list grammar is not LL(1)");
+    };
+    assert_eq!(found, conflicts(NonTerminal::Elements, &[Terminal::Zero]));
+    assert_eq!(
+        GrammarError::NotLl1(found).to_string(),
+        "The grammar is not LL(1): elements on '0': alternatives 0/1"
+    );
+
+    // Left recursion puts the first alternative's FIRST set in the second's.
+    let Err(GrammarError::NotLl1(found)) = common::arithmetic_grammar().generate_rust() else {
+        panic!("the arithmetic grammar is not LL(1)");
+    };
+    let lookaheads = [Terminal::LeftParen, Terminal::Zero];
+    assert!(found.starts_with(&conflicts(NonTerminal::Sum, &lookaheads)));
+}
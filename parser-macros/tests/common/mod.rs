@@ -0,0 +1,108 @@
+#![allow(dead_code)]
+
+use parser_macros::{Expression, Grammar, NonTerminal, Terminal};
+use std::collections::HashMap;
+
+/// The sample arithmetic grammar, with `+` binding looser than `-`, which
+/// binds looser than `*`.
+pub fn arithmetic_grammar() -> Grammar {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![
+                    vec![N(NonTerminal::Sum), T(Terminal::Plus), N(NonTerminal::Sub)],
+                    vec![N(NonTerminal::Sub)],
+                ],
+            ),
+            (
+                NonTerminal::Sub,
+                vec![
+                    vec![
+                        N(NonTerminal::Sub),
+                        T(Terminal::Minus),
+                        N(NonTerminal::Mult),
+                    ],
+                    vec![N(NonTerminal::Mult)],
+                ],
+            ),
+            (
+                NonTerminal::Mult,
+                vec![
+                    vec![
+                        N(NonTerminal::Mult),
+                        T(Terminal::Star),
+                        N(NonTerminal::Atom),
+                    ],
+                    vec![N(NonTerminal::Atom)],
+                ],
+            ),
+            (
+                NonTerminal::Atom,
+                vec![
+                    vec![
+                        T(Terminal::LeftParen),
+                        N(NonTerminal::Sum),
+                        T(Terminal::RightParen),
+                    ],
+                    vec![N(NonTerminal::Number)],
+                ],
+            ),
+            (NonTerminal::Number, vec![vec![T(Terminal::Zero)]]),
+        ],
+    )
+}
+
+/// The arithmetic grammar with identifiers as atoms, so `a + b * c`
+/// parses.
+pub fn variable_grammar() -> Grammar {
+    let mut grammar = arithmetic_grammar();
+    grammar
+        .rules
+        .get_mut(&NonTerminal::Atom)
+        .expect("The arithmetic grammar has atoms")
+        .push(vec![Expression::Terminal(Terminal::Identifier(
+            String::new(),
+        ))]);
+    grammar
+}
+
+/// Comma separated lists of zeros in brackets, allowing empty lists.
+pub fn list_grammar() -> Grammar {
+    let mut rules = HashMap::new();
+
+    // List rules
+    rules.insert(
+        NonTerminal::List,
+        vec![vec![
+            Expression::Terminal(Terminal::LeftBracket),
+            Expression::NonTerminal(NonTerminal::Elements),
+            Expression::Terminal(Terminal::RightBracket),
+        ]],
+    );
+
+    // Elements rules
+    rules.insert(
+        NonTerminal::Elements,
+        vec![
+            vec![
+                Expression::NonTerminal(NonTerminal::Number),
+                Expression::Terminal(Terminal::Comma),
+                Expression::NonTerminal(NonTerminal::Elements),
+            ],
+            vec![Expression::NonTerminal(NonTerminal::Number)],
+            vec![],
+        ],
+    );
+
+    // Number rules
+    rules.insert(
+        NonTerminal::Number,
+        vec![vec![Expression::Terminal(Terminal::Zero)]],
+    );
+
+    Grammar::new(NonTerminal::List, rules)
+}
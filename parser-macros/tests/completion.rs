@@ -0,0 +1,66 @@
+mod common;
+
+use std::collections::HashSet;
+
+use parser_macros::{ParseError, Parser, Terminal};
+
+#[test]
+fn completes_after_operator() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    assert_eq!(
+        parser.completions("0 +"),
+        Ok(HashSet::from([Terminal::LeftParen, Terminal::Zero]))
+    );
+    assert_eq!(
+        parser.completions("( 0"),
+        Ok(HashSet::from([
+            Terminal::Plus,
+            Terminal::Minus,
+            Terminal::Star,
+            Terminal::RightParen,
+        ]))
+    );
+}
+
+#[test]
+fn empty_prefix_completes_first_of_start() {
+    assert_eq!(
+        Parser::new(common::arithmetic_grammar()).completions(""),
+        Ok(HashSet::from([Terminal::LeftParen, Terminal::Zero]))
+    );
+    assert_eq!(
+        Parser::new(common::list_grammar()).completions(""),
+        Ok(HashSet::from([Terminal::LeftBracket]))
+    );
+}
+
+#[test]
+fn invalid_prefix_is_an_error() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    assert!(matches!(
+        parser.completions("0 0"),
+        Err(ParseError::UnexpectedToken {
+            found: Terminal::Zero,
+            position: 1,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn viable_prefixes() {
+    use Terminal::{LeftParen, Plus, RightParen, Star, Zero};
+
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    assert!(!parser.is_viable_prefix(&[Zero, RightParen]));
+    assert!(!parser.is_viable_prefix(&[RightParen]));
+    assert!(!parser.is_viable_prefix(&[Zero, Zero]));
+    assert!(!parser.is_viable_prefix(&[LeftParen, Zero, Plus, Star]));
+
+    assert!(parser.is_viable_prefix(&[]));
+    assert!(parser.is_viable_prefix(&[LeftParen, LeftParen, Zero, Plus]));
+    assert!(parser.is_viable_prefix(&[Zero, Star, Zero]));
+}
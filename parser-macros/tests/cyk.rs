@@ -0,0 +1,146 @@
+mod common;
+
+use parser_macros::{Expression, Grammar, NonTerminal, ParseError, Parser, StackValue, Terminal};
+
+#[test]
+fn parses_in_original_nonterminals() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let tree = parser
+        .parse_cyk("0 + 0 * 0")
+        .expect("Should be able to parse");
+    assert_eq!(
+        tree.to_string(),
+        "(sum (sum '0') '+' (sub (mult '0') '*' (atom '0')))"
+    );
+    assert_eq!(
+        parser.parse_cyk("( 0 )").unwrap().to_string(),
+        "(sum '(' (sum '0') ')')"
+    );
+
+    assert_eq!(parser.parse_cyk("0 +"), Err(ParseError::Rejected));
+    assert_eq!(parser.parse_cyk(""), Err(ParseError::Rejected));
+}
+
+/// `sum -> sum '+' sum | '0'`, which has a conflict the shift-reduce
+/// engine cannot resolve.
+fn ambiguous_sums() -> Grammar {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    Grammar::from_rules(
+        NonTerminal::Sum,
+        [(
+            NonTerminal::Sum,
+            vec![
+                vec![N(NonTerminal::Sum), T(Terminal::Plus), N(NonTerminal::Sum)],
+                vec![T(Terminal::Zero)],
+            ],
+        )],
+    )
+}
+
+#[test]
+fn accepts_ambiguous_grammars() {
+    let parser = Parser::new(ambiguous_sums());
+
+    let tree = parser
+        .parse_cyk("0 + 0 + 0")
+        .expect("Should be able to parse");
+    assert_eq!(
+        tree.leaves().map(Terminal::to_string).collect::<Vec<_>>(),
+        ["0", "+", "0", "+", "0"]
+    );
+    let StackValue::Tree { head, values } = &tree else {
+        panic!("Expected a tree");
+    };
+    assert_eq!(*head, NonTerminal::Sum);
+    assert_eq!(values.len(), 3);
+}
+
+#[test]
+fn keeps_generated_nonterminals_of_the_grammar() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let operand = NonTerminal::Generated(0);
+    let parser = Parser::new(Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![vec![
+                    N(operand.clone()),
+                    T(Terminal::Plus),
+                    N(operand.clone()),
+                ]],
+            ),
+            (operand.clone(), vec![vec![T(Terminal::Zero)]]),
+        ],
+    ));
+
+    let tree = parser.parse_cyk("0 + 0").expect("Should be able to parse");
+    assert_eq!(tree, parser.parse("0 + 0").unwrap());
+    assert_eq!(tree.find_all(&operand).count(), 2);
+}
+
+#[test]
+fn empty_sentences() {
+    let parser = Parser::new(common::list_grammar());
+
+    assert_eq!(
+        parser.parse_cyk("[ ]").unwrap().to_string(),
+        "(list '[' ']')"
+    );
+    assert_eq!(
+        parser.parse_cyk("[ 0 , 0 ]").unwrap().to_string(),
+        "(list '[' (elements (number '0') ',' (elements '0')) ']')"
+    );
+}
+
+#[test]
+fn limits_parallel_parses() {
+    let mut parser = Parser::new(ambiguous_sums());
+
+    // Four sums can be grouped in five ways.
+    let input = "0 + 0 + 0 + 0";
+    assert!(parser.parse_cyk(input).is_ok());
+
+    parser.set_max_parallel_parses(4);
+    assert_eq!(
+        parser.parse_cyk(input),
+        Err(ParseError::TooAmbiguous { limit: 4 })
+    );
+    assert_eq!(
+        ParseError::TooAmbiguous { limit: 4 }
+            .with_source(input)
+            .to_string(),
+        "Input has more than 4 parses"
+    );
+    assert!(parser.parse_cyk("0 + 0 + 0").is_ok());
+
+    // The limit is checked while the chart is filled, so the five parses
+    // of the first seven tokens fail the input before the stray `)` would
+    // reject it.
+    assert_eq!(
+        parser.parse_cyk("0 + 0 + 0 + 0 )"),
+        Err(ParseError::TooAmbiguous { limit: 4 })
+    );
+
+    parser.set_max_parallel_parses(5);
+    assert_eq!(
+        parser.parse_cyk("0 + 0 + 0 + 0 )"),
+        Err(ParseError::Rejected)
+    );
+    assert!(parser.parse_cyk(input).is_ok());
+
+    parser.set_max_parallel_parses(1);
+    assert_eq!(
+        parser.parse_cyk("0 + 0 + 0"),
+        Err(ParseError::TooAmbiguous { limit: 1 })
+    );
+    assert!(parser.parse_cyk("0 + 0").is_ok());
+
+    // An unambiguous grammar has one parse of every sentence.
+    let mut parser = Parser::new(common::arithmetic_grammar());
+    parser.set_max_parallel_parses(1);
+    assert!(parser.parse_cyk("( 0 + 0 ) * 0 - 0").is_ok());
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use parser_macros::{
+    ByteLexer, Expression, Grammar, LspDiagnostic, LspPosition, LspRange, NonTerminal, ParseError,
+    Parser, Severity, Span, Terminal,
+};
+
+#[test]
+fn caret_under_unexpected_token() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let input = "0 + + 0";
+
+    let error = parser.parse(input).unwrap_err();
+    assert_eq!(error.span(), Some(Span { start: 4, end: 5 }));
+    assert_eq!(
+        error.with_source(input).to_string(),
+        "Unexpected '+' at position 2, expected one of: '(', '0'\n \
+         --> line 1, column 5\n  \
+           |\n\
+         1 | 0 + + 0\n  \
+           |     ^"
+    );
+}
+
+#[test]
+fn shows_only_the_offending_line() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let input = "0 +\n\t( 0 ##\n)";
+
+    let diagnostic = parser.parse(input).unwrap_err().with_source(input);
+    assert_eq!(
+        diagnostic.to_string(),
+        "Invalid token \"##\" at offset 9\n \
+         --> line 2, column 6\n  \
+           |\n\
+         2 | \t( 0 ##\n  \
+           | \t    ^^"
+    );
+}
+
+#[test]
+fn end_of_input_points_past_the_last_character() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let input = "( 0 + 0\n";
+
+    let diagnostic = parser.parse(input).unwrap_err().with_source(input);
+    assert!(
+        diagnostic
+            .to_string()
+            .ends_with("1 | ( 0 + 0\n  |        ^"),
+        "{diagnostic}"
+    );
+
+    assert_eq!(
+        ParseError::Cancelled.with_source(input).to_string(),
+        "Parse cancelled"
+    );
+}
+
+#[test]
+fn spans_inside_a_char() {
+    use Expression::Terminal as T;
+
+    // `é` is two bytes, and the byte lexer makes a token of each.
+    let grammar = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(NonTerminal::Sum, vec![vec![T(Terminal::byte(0xc3))]])],
+    );
+    let parser = Parser::with_lexer(grammar, ByteLexer::new());
+    let input = "é";
+
+    let error = parser.parse(input).unwrap_err();
+    assert_eq!(error.span(), Some(Span { start: 1, end: 2 }));
+    assert_eq!(
+        error.with_source(input).to_string(),
+        "Unexpected '0xa9' at position 1\n \
+         --> line 1, column 1\n  \
+           |\n\
+         1 | é\n  \
+           | ^"
+    );
+}
+
+#[test]
+fn lsp_diagnostics() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let position = |line, character| LspPosition { line, character };
+
+    // The emoji is four bytes, but two UTF-16 code units.
+    let input = "0 +\n\"😀\" + 0";
+    let (_, diagnostics) = parser.parse_lsp(input);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].range,
+        LspRange {
+            start: position(1, 0),
+            end: position(1, 4),
+        }
+    );
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(
+        diagnostics[0].message,
+        parser.parse(input).unwrap_err().to_string()
+    );
+
+    let (tree, diagnostics) = parser.parse_lsp("0 + 0");
+    assert!(tree.is_some());
+    assert!(diagnostics.is_empty());
+
+    let input = "( 0 \n";
+    let error = ParseError::UnexpectedEnd {
+        expected: vec![Terminal::RightParen],
+    };
+    assert_eq!(
+        LspDiagnostic::from_error(input, &error).range,
+        LspRange {
+            start: position(0, 3),
+            end: position(0, 3),
+        }
+    );
+
+    assert_eq!(LspPosition::from_offset("a😀b", 5), position(0, 3));
+    assert_eq!(LspPosition::from_offset("a😀b", 2), position(0, 1));
+    assert_eq!(LspPosition::from_offset("a\r\nb", 99), position(1, 1));
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use parser_macros::{NonTerminal, Parser};
+
+#[test]
+fn explains_unexpected_token() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    assert_eq!(
+        parser.explain_failure("0 + + 0"),
+        "Parsing stopped at '+' (token 2).\n\
+         Stack: sum '+'\n\
+         Expected one of: '(', '0'\n\
+         With '(' next, these productions could have continued:\n  \
+           atom -> • '(' sum ')'\n\
+         With '0' next, these productions could have continued:\n  \
+           number -> • '0'\n"
+    );
+}
+
+#[test]
+fn explains_unfinished_input() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let explanation = parser.explain_failure("( 0 + 0");
+
+    assert!(explanation.starts_with("The input ended before it formed a complete sum.\n"));
+    assert!(explanation.contains("Stack: '(' sum\n"));
+    assert!(explanation.contains("  atom -> '(' sum • ')'\n"));
+    assert!(explanation.contains("  sum -> sum • '+' sub\n"));
+}
+
+#[test]
+fn valid_input_has_nothing_to_explain() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    assert_eq!(
+        parser.explain_failure("0 * 0"),
+        "The input parses as a sum, there is no failure to explain.\n"
+    );
+    assert!(
+        parser
+            .explain_failure("0 #")
+            .contains("Invalid token \"#\"")
+    );
+}
+
+#[test]
+fn names_labelled_productions() {
+    let mut grammar = common::arithmetic_grammar();
+    grammar
+        .labels
+        .insert((NonTerminal::Atom, 0), "parenthesised".to_string());
+    let parser = Parser::new(grammar);
+
+    let explanation = parser.explain_failure("0 + + 0");
+
+    assert!(explanation.contains("  atom -> • '(' sum ')' (parenthesised)\n"));
+    assert!(explanation.contains("  number -> • '0'\n"));
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use std::{error::Error, fs, path::PathBuf};
+
+use parser_macros::{FileError, ParseError, Parser};
+
+/// A path in the temporary directory for this test alone.
+fn scratch(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("parser-macros-{}-{name}", std::process::id()))
+}
+
+#[test]
+fn parses_files() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let path = scratch("sum.txt");
+    fs::write(&path, "( 0 + 0 ) * 0\n").unwrap();
+
+    let tree = parser.parse_file(&path).expect("Should be able to parse");
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        tree.to_string(),
+        parser.parse("( 0 + 0 ) * 0").unwrap().to_string()
+    );
+}
+
+#[test]
+fn file_errors_name_the_file() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let missing = scratch("missing.txt");
+    let error = parser.parse_file(&missing).unwrap_err();
+    assert!(matches!(error, FileError::Io { .. }), "{error:?}");
+    assert_eq!(error.path(), missing);
+    assert!(error.to_string().starts_with("Could not read "));
+    assert!(error.to_string().contains("missing.txt"));
+
+    let path = scratch("bad.txt");
+    fs::write(&path, "0 + )").unwrap();
+    let error = parser.parse_file(&path).unwrap_err();
+    fs::remove_file(&path).unwrap();
+
+    let FileError::Parse { error: inner, .. } = &error else {
+        panic!("Expected a parse error, got {error:?}");
+    };
+    assert!(
+        matches!(inner, ParseError::UnexpectedToken { .. }),
+        "{inner:?}"
+    );
+    assert_eq!(error.path(), path);
+    assert_eq!(error.to_string(), format!("In {}: {inner}", path.display()));
+    assert!(error.source().is_some());
+}
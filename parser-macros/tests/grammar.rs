@@ -0,0 +1,657 @@
+mod common;
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use parser_macros::{
+    Expression, FloatLiteral, Grammar, GrammarBuilder, GrammarError, NonTerminal, Override,
+    ParseTable, Parser, SymbolRegistry, Terminal,
+};
+
+#[test]
+fn from_rules_merges_duplicate_nonterminals() {
+    let grammar = Grammar::from_rules(
+        NonTerminal::Number,
+        [
+            (
+                NonTerminal::Number,
+                vec![vec![Expression::Terminal(Terminal::Zero)]],
+            ),
+            (NonTerminal::Atom, vec![vec![]]),
+            (
+                NonTerminal::Number,
+                vec![
+                    vec![Expression::NonTerminal(NonTerminal::Atom)],
+                    vec![Expression::Terminal(Terminal::Plus)],
+                ],
+            ),
+        ],
+    );
+
+    assert_eq!(grammar.starting_symbol, NonTerminal::Number);
+    assert_eq!(grammar.rules.len(), 2);
+    assert_eq!(
+        grammar.rules[&NonTerminal::Number],
+        vec![
+            vec![Expression::Terminal(Terminal::Zero)],
+            vec![Expression::NonTerminal(NonTerminal::Atom)],
+            vec![Expression::Terminal(Terminal::Plus)],
+        ]
+    );
+}
+
+#[test]
+fn sample_grammars_are_valid() {
+    assert_eq!(common::arithmetic_grammar().validate(), Ok(()));
+    assert_eq!(common::list_grammar().validate(), Ok(()));
+}
+
+#[test]
+fn validation_errors() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let undefined = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(NonTerminal::Sum, vec![vec![N(NonTerminal::Atom)]])],
+    );
+    assert_eq!(
+        undefined.validate(),
+        Err(GrammarError::Undefined(NonTerminal::Atom))
+    );
+
+    let unreachable = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (NonTerminal::Sum, vec![vec![T(Terminal::Zero)]]),
+            (NonTerminal::Atom, vec![vec![T(Terminal::Zero)]]),
+        ],
+    );
+    assert_eq!(
+        unreachable.validate(),
+        Err(GrammarError::Unreachable(vec![NonTerminal::Atom]))
+    );
+
+    let non_productive = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![vec![N(NonTerminal::Atom)], vec![T(Terminal::Zero)]],
+            ),
+            (
+                NonTerminal::Atom,
+                vec![vec![T(Terminal::LeftParen), N(NonTerminal::Atom)]],
+            ),
+        ],
+    );
+    assert_eq!(
+        non_productive.validate(),
+        Err(GrammarError::NonProductive(vec![NonTerminal::Atom]))
+    );
+
+    let ambiguous = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(
+            NonTerminal::Sum,
+            vec![
+                vec![N(NonTerminal::Sum), T(Terminal::Plus), N(NonTerminal::Sum)],
+                vec![T(Terminal::Zero)],
+            ],
+        )],
+    );
+    let error = ambiguous.validate().expect_err("Sum + Sum is ambiguous");
+    assert!(matches!(
+        error,
+        GrammarError::Conflict {
+            lookahead: Some(Terminal::Plus),
+            ..
+        }
+    ));
+    assert_eq!(error.to_string(), "Conflict in state 4 on '+': s3/r1");
+}
+
+#[test]
+fn builder() {
+    let grammar = GrammarBuilder::new(NonTerminal::List)
+        .production(
+            NonTerminal::List,
+            vec![
+                Expression::Terminal(Terminal::LeftBracket),
+                Expression::Terminal(Terminal::RightBracket),
+            ],
+        )
+        .build()
+        .expect("The grammar is valid");
+    assert_eq!(grammar.rules[&NonTerminal::List].len(), 1);
+
+    let error = GrammarBuilder::new(NonTerminal::List)
+        .production(
+            NonTerminal::List,
+            vec![Expression::NonTerminal(NonTerminal::Elements)],
+        )
+        .build()
+        .expect_err("Elements has no rules");
+    assert_eq!(
+        error.to_string(),
+        "Nonterminal elements is used but has no rules"
+    );
+}
+
+#[test]
+fn rename_preserves_language() {
+    let mut grammar = common::arithmetic_grammar();
+    grammar
+        .rename_nonterminal(NonTerminal::Sum, NonTerminal::Expr)
+        .expect("Expr is unused");
+
+    assert_eq!(grammar.starting_symbol, NonTerminal::Expr);
+    assert!(!grammar.rules.contains_key(&NonTerminal::Sum));
+    assert_eq!(
+        grammar.equivalent_up_to(&common::arithmetic_grammar(), 7),
+        Ok(())
+    );
+
+    assert_eq!(
+        grammar.rename_nonterminal(NonTerminal::Atom, NonTerminal::Mult),
+        Err(GrammarError::AlreadyDefined(NonTerminal::Mult))
+    );
+}
+
+#[test]
+fn replace_terminal_changes_language() {
+    let mut grammar = common::arithmetic_grammar();
+    grammar.reduce_lookahead.insert(
+        (NonTerminal::Mult, 0),
+        [Terminal::Star, Terminal::Plus, Terminal::RightParen].into(),
+    );
+    grammar.replace_terminal(Terminal::Star, Terminal::Arrow);
+
+    assert_eq!(
+        grammar.reduce_lookahead[&(NonTerminal::Mult, 0)],
+        [Terminal::Arrow, Terminal::Plus, Terminal::RightParen].into()
+    );
+    assert!(
+        grammar
+            .rules
+            .values()
+            .flatten()
+            .flatten()
+            .all(|expr| *expr != Expression::Terminal(Terminal::Star))
+    );
+
+    let parser = Parser::new(grammar);
+    assert!(parser.parse("0 -> ( 0 + 0 ) -> 0").is_ok());
+    assert!(parser.parse("0 * 0").is_err());
+}
+
+#[test]
+fn merge_appends_alternatives() {
+    let mut grammar = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![vec![
+                    Expression::NonTerminal(NonTerminal::Atom),
+                    Expression::NonTerminal(NonTerminal::Number),
+                ]],
+            ),
+            (
+                NonTerminal::Atom,
+                vec![vec![Expression::Terminal(Terminal::Plus)]],
+            ),
+            (
+                NonTerminal::Number,
+                vec![vec![Expression::Terminal(Terminal::Zero)]],
+            ),
+        ],
+    );
+    grammar.reduce_lookahead.insert(
+        (NonTerminal::Number, 0),
+        [Terminal::Zero].into_iter().collect(),
+    );
+
+    grammar.merge_nonterminal(NonTerminal::Number, NonTerminal::Atom);
+
+    assert_eq!(
+        grammar.rules[&NonTerminal::Sum],
+        vec![vec![
+            Expression::NonTerminal(NonTerminal::Atom),
+            Expression::NonTerminal(NonTerminal::Atom),
+        ]]
+    );
+    assert_eq!(
+        grammar.rules[&NonTerminal::Atom],
+        vec![
+            vec![Expression::Terminal(Terminal::Plus)],
+            vec![Expression::Terminal(Terminal::Zero)],
+        ]
+    );
+    assert!(
+        grammar
+            .reduce_lookahead
+            .contains_key(&(NonTerminal::Atom, 1))
+    );
+    assert_eq!(grammar.reduce_lookahead.len(), 1);
+}
+
+#[test]
+fn strict_parsers_refuse_conflicts() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let parser = Parser::new_strict(common::arithmetic_grammar())
+        .expect("The arithmetic grammar has no conflicts");
+    assert!(parser.parse("0 + 0 * 0").is_ok());
+
+    let ambiguous = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(
+            NonTerminal::Sum,
+            vec![
+                vec![N(NonTerminal::Sum), T(Terminal::Plus), N(NonTerminal::Sum)],
+                vec![N(NonTerminal::Sum), T(Terminal::Star), N(NonTerminal::Sum)],
+                vec![T(Terminal::Zero)],
+            ],
+        )],
+    );
+    let Err(conflicts) = Parser::new_strict(ambiguous.clone()) else {
+        panic!("Sum + Sum is ambiguous");
+    };
+    assert_eq!(conflicts.len(), 4);
+    assert_eq!(
+        ambiguous.validate().err().as_ref(),
+        conflicts.first(),
+        "validate reports the first conflict"
+    );
+    assert!(
+        conflicts
+            .iter()
+            .all(|conflict| matches!(conflict, GrammarError::Conflict { .. }))
+    );
+}
+
+#[test]
+fn chomsky_normal_form() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    // `sum -> number sum | ε`, `number -> '0' | '(' sum ')'`, whose language
+    // includes the empty sentence.
+    let nullable = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![vec![N(NonTerminal::Number), N(NonTerminal::Sum)], vec![]],
+            ),
+            (
+                NonTerminal::Number,
+                vec![
+                    vec![T(Terminal::Zero)],
+                    vec![
+                        T(Terminal::LeftParen),
+                        N(NonTerminal::Sum),
+                        T(Terminal::RightParen),
+                    ],
+                ],
+            ),
+        ],
+    );
+
+    for grammar in [
+        common::arithmetic_grammar(),
+        common::list_grammar(),
+        nullable,
+    ] {
+        let normal = grammar.as_chomsky_normal_form();
+
+        for (non_terminal, alternatives) in &normal.rules {
+            for production in alternatives {
+                match production.as_slice() {
+                    [N(first), N(second)] => {
+                        assert_ne!(*first, normal.starting_symbol);
+                        assert_ne!(*second, normal.starting_symbol);
+                    }
+                    [T(_)] => {}
+                    [] => assert_eq!(*non_terminal, normal.starting_symbol),
+                    other => panic!("{non_terminal} -> {other:?} is not in normal form"),
+                }
+            }
+        }
+
+        assert_eq!(normal.equivalent_up_to(&grammar, 6), Ok(()));
+    }
+}
+
+#[test]
+fn minimize_inlines_single_use_nonterminals() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    // `atom` and `number` are each used once, with one alternative.
+    let grammar = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![
+                    vec![N(NonTerminal::Sum), T(Terminal::Plus), N(NonTerminal::Atom)],
+                    vec![T(Terminal::Zero)],
+                ],
+            ),
+            (
+                NonTerminal::Atom,
+                vec![vec![T(Terminal::LeftParen), N(NonTerminal::Number)]],
+            ),
+            (
+                NonTerminal::Number,
+                vec![vec![T(Terminal::Zero), T(Terminal::RightParen)]],
+            ),
+        ],
+    );
+    let minimized = grammar.minimize();
+    assert_eq!(
+        minimized.rules,
+        HashMap::from([(
+            NonTerminal::Sum,
+            vec![
+                vec![
+                    N(NonTerminal::Sum),
+                    T(Terminal::Plus),
+                    T(Terminal::LeftParen),
+                    T(Terminal::Zero),
+                    T(Terminal::RightParen),
+                ],
+                vec![T(Terminal::Zero)],
+            ],
+        )])
+    );
+
+    // A label keeps its nonterminal.
+    let mut labelled = grammar.clone();
+    labelled
+        .labels
+        .insert((NonTerminal::Number, 0), "closing".to_string());
+    assert_eq!(labelled.minimize().rules.len(), 2);
+
+    // Normal forms shrink but keep their language.
+    for grammar in [common::arithmetic_grammar(), common::list_grammar()] {
+        let normal = grammar.as_chomsky_normal_form();
+        let minimized = normal.minimize();
+        assert!(minimized.rules.len() < normal.rules.len());
+        assert_eq!(minimized.equivalent_up_to(&grammar, 6), Ok(()));
+    }
+}
+
+#[test]
+fn parsers_give_back_their_grammar() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    assert_eq!(parser.grammar(), &common::arithmetic_grammar());
+    assert!(parser.parse("a").is_err());
+
+    let mut grammar = parser.into_grammar();
+    grammar
+        .rules
+        .get_mut(&NonTerminal::Atom)
+        .expect("The arithmetic grammar has atoms")
+        .push(vec![Expression::Terminal(Terminal::Identifier(
+            String::new(),
+        ))]);
+
+    assert!(Parser::new(grammar).parse("a").is_ok());
+}
+
+#[test]
+fn production_labels() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let grammar = GrammarBuilder::new(NonTerminal::Sum)
+        .production_labelled(
+            NonTerminal::Sum,
+            vec![
+                N(NonTerminal::Sum),
+                T(Terminal::Plus),
+                N(NonTerminal::Number),
+            ],
+            "addition",
+        )
+        .production(NonTerminal::Sum, vec![N(NonTerminal::Number)])
+        .production(NonTerminal::Number, vec![T(Terminal::Zero)])
+        .build()
+        .expect("The grammar is valid");
+
+    assert_eq!(
+        grammar.production_label(&NonTerminal::Sum, 0),
+        Some("addition".to_string())
+    );
+    assert_eq!(
+        grammar.production_label(&NonTerminal::Sum, 1),
+        Some("sum -> number".to_string())
+    );
+    assert_eq!(grammar.production_label(&NonTerminal::Sum, 2), None);
+
+    let table = ParseTable::new(&grammar);
+    assert_eq!(table.label(1), Some("addition"));
+    assert_eq!(table.label(2), None);
+    assert!(
+        table
+            .to_table_string()
+            .contains("1: sum -> sum '+' number (addition)\n")
+    );
+
+    let mut merged = grammar.clone();
+    merged.merge_nonterminal(NonTerminal::Sum, NonTerminal::Number);
+    assert_eq!(
+        merged.production_label(&NonTerminal::Number, 1),
+        Some("addition".to_string())
+    );
+}
+
+#[test]
+fn extend_appends_alternatives() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let mut grammar = Grammar::new(NonTerminal::Sum, Default::default());
+    grammar.extend([
+        (NonTerminal::Sum, vec![N(NonTerminal::Number)]),
+        (NonTerminal::Number, vec![T(Terminal::Zero)]),
+    ]);
+    grammar.extend([Terminal::Plus, Terminal::Minus].map(|operator| {
+        (
+            NonTerminal::Sum,
+            vec![N(NonTerminal::Sum), T(operator), N(NonTerminal::Number)],
+        )
+    }));
+
+    assert_eq!(
+        grammar.rules[&NonTerminal::Sum],
+        vec![
+            vec![N(NonTerminal::Number)],
+            vec![
+                N(NonTerminal::Sum),
+                T(Terminal::Plus),
+                N(NonTerminal::Number)
+            ],
+            vec![
+                N(NonTerminal::Sum),
+                T(Terminal::Minus),
+                N(NonTerminal::Number)
+            ],
+        ]
+    );
+    assert!(Parser::new(grammar).parse("0 + 0 - 0").is_ok());
+}
+
+#[test]
+fn extend_with_appends_or_replaces() {
+    use Expression::Terminal as T;
+
+    let mut base = common::arithmetic_grammar();
+    base.labels
+        .insert((NonTerminal::Atom, 0), "parenthesized".to_string());
+    base.labels
+        .insert((NonTerminal::Number, 0), "zero".to_string());
+
+    let variant = base.extend_with([
+        (
+            NonTerminal::Atom,
+            Override::Append(vec![vec![T(Terminal::Identifier(String::new()))]]),
+        ),
+        (
+            NonTerminal::Number,
+            Override::Replace(vec![vec![T(Terminal::Int(None))]]),
+        ),
+    ]);
+
+    assert_eq!(variant.rules[&NonTerminal::Atom].len(), 3);
+    assert_eq!(
+        variant.production_label(&NonTerminal::Atom, 0).as_deref(),
+        Some("parenthesized")
+    );
+    assert_eq!(
+        variant.production_label(&NonTerminal::Number, 0).as_deref(),
+        Some("number -> 'integer'")
+    );
+    assert_eq!(base.rules, common::arithmetic_grammar().rules);
+
+    let parser = Parser::new(variant);
+    assert!(parser.parse("a * 42 + ( b - 7 )").is_ok());
+    assert!(parser.parse("0").is_err());
+}
+
+#[test]
+fn symbol_registry_keeps_generated_symbols_apart() {
+    let generated = |grammar: &Grammar| -> HashSet<NonTerminal> {
+        grammar
+            .symbols_in_order()
+            .0
+            .into_iter()
+            .filter(|nt| matches!(nt, NonTerminal::Generated(_)))
+            .collect()
+    };
+
+    // Built independently, both normal forms number from zero.
+    let arithmetic = common::arithmetic_grammar().as_chomsky_normal_form();
+    let list = common::list_grammar().as_chomsky_normal_form();
+    assert!(!generated(&arithmetic).is_disjoint(&generated(&list)));
+
+    let mut registry = SymbolRegistry::new();
+    let arithmetic = common::arithmetic_grammar().as_chomsky_normal_form_in(&mut registry);
+    let list = common::list_grammar().as_chomsky_normal_form_in(&mut registry);
+    assert!(generated(&arithmetic).is_disjoint(&generated(&list)));
+
+    // A grammar built elsewhere is renumbered, and still parses the same.
+    let elsewhere = common::list_grammar().as_chomsky_normal_form();
+    let adopted = registry.adopt(&elsewhere);
+    assert!(generated(&adopted).is_disjoint(&generated(&arithmetic)));
+    assert!(generated(&adopted).is_disjoint(&generated(&list)));
+    assert_eq!(generated(&adopted).len(), generated(&elsewhere).len());
+    assert!(Parser::new(adopted).parse_cyk("[ 0 , 0 ]").is_ok());
+
+    let fresh = registry.fresh();
+    assert!(!generated(&arithmetic).contains(&fresh));
+    assert!(!generated(&list).contains(&fresh));
+}
+
+#[test]
+fn counts_productions() {
+    let grammar = common::arithmetic_grammar();
+
+    assert_eq!(grammar.alternatives(&NonTerminal::Sum), 2);
+    assert_eq!(grammar.alternatives(&NonTerminal::Number), 1);
+    assert_eq!(grammar.alternatives(&NonTerminal::List), 0);
+    assert_eq!(grammar.total_productions(), 9);
+    assert_eq!(common::list_grammar().total_productions(), 5);
+}
+
+#[test]
+fn symbols_are_ordered() {
+    let mut non_terminals = vec![
+        NonTerminal::Generated(1),
+        NonTerminal::Atom,
+        NonTerminal::Generated(0),
+        NonTerminal::Sum,
+    ];
+    non_terminals.sort();
+    assert_eq!(
+        non_terminals,
+        vec![
+            NonTerminal::Sum,
+            NonTerminal::Atom,
+            NonTerminal::Generated(0),
+            NonTerminal::Generated(1),
+        ]
+    );
+
+    let terminals = BTreeSet::from([
+        Terminal::Float(Some(FloatLiteral(0.5))),
+        Terminal::Identifier("b".to_string()),
+        Terminal::Float(Some(FloatLiteral(-1.0))),
+        Terminal::Identifier("a".to_string()),
+        Terminal::Float(None),
+        Terminal::Zero,
+        Terminal::Plus,
+    ]);
+    assert_eq!(
+        terminals.into_iter().collect::<Vec<_>>(),
+        vec![
+            Terminal::Plus,
+            Terminal::Zero,
+            Terminal::Identifier("a".to_string()),
+            Terminal::Identifier("b".to_string()),
+            Terminal::Float(None),
+            Terminal::Float(Some(FloatLiteral(-1.0))),
+            Terminal::Float(Some(FloatLiteral(0.5))),
+        ]
+    );
+}
+
+#[test]
+fn terminals_are_keys() {
+    let terminals = HashSet::from([
+        Terminal::Plus,
+        Terminal::Int(Some(1)),
+        Terminal::Int(Some(1)),
+        Terminal::Int(None),
+        Terminal::Float(Some(FloatLiteral(0.5))),
+        Terminal::Float(Some(FloatLiteral(0.5))),
+        Terminal::Identifier("a".to_string()),
+    ]);
+    assert_eq!(terminals.len(), 5);
+    assert!(terminals.contains(&Terminal::Float(Some(FloatLiteral(0.5)))));
+    assert!(!terminals.contains(&Terminal::Identifier("b".to_string())));
+
+    let names = HashMap::from([
+        (Terminal::Plus, "plus"),
+        (Terminal::Int(Some(1)), "one"),
+        (Terminal::Int(None), "int"),
+    ]);
+    assert_eq!(names[&Terminal::Int(Some(1))], "one");
+    assert_eq!(names[&Terminal::Int(None)], "int");
+    assert_eq!(names.get(&Terminal::Minus), None);
+}
+
+#[test]
+fn update_grammar_rebuilds_the_tables() {
+    let mut parser = Parser::new(common::arithmetic_grammar());
+    parser.enable_coverage();
+    assert!(parser.parse("[ 0 ]").is_err());
+    assert!(parser.parse_cyk("[ 0 ]").is_err());
+    assert!(parser.parse("0 + 0").is_ok());
+
+    parser.update_grammar(|grammar| {
+        grammar
+            .rules
+            .get_mut(&NonTerminal::Atom)
+            .unwrap()
+            .push(vec![
+                Expression::Terminal(Terminal::LeftBracket),
+                Expression::NonTerminal(NonTerminal::Sum),
+                Expression::Terminal(Terminal::RightBracket),
+            ]);
+    });
+
+    assert!(parser.parse("[ 0 ] * ( 0 )").is_ok());
+    assert!(parser.parse_cyk("[ 0 ]").is_ok());
+    let coverage = parser.production_coverage();
+    assert_eq!(coverage.len(), parser.grammar().total_productions());
+    assert_eq!(coverage[&(NonTerminal::Atom, 2)], 1);
+    assert_eq!(coverage[&(NonTerminal::Sum, 0)], 0);
+}
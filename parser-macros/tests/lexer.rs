@@ -0,0 +1,112 @@
+mod common;
+
+use parser_macros::{
+    Expression, Grammar, Lexer, NonTerminal, ParseError, Parser, Span, Terminal, TokenizeError,
+};
+
+/// Reads each character as a terminal, so no whitespace is needed.
+struct CharLexer;
+
+impl Lexer for CharLexer {
+    fn tokens(&self, input: &str) -> Result<Vec<Terminal>, ParseError> {
+        input
+            .char_indices()
+            .map(|(index, c)| match c {
+                '0' => Ok(Terminal::Zero),
+                '+' => Ok(Terminal::Plus),
+                '*' => Ok(Terminal::Star),
+                '(' => Ok(Terminal::LeftParen),
+                ')' => Ok(Terminal::RightParen),
+                c => Err(ParseError::Tokenize(TokenizeError::InvalidToken {
+                    token: c.to_string(),
+                    span: Span {
+                        start: index,
+                        end: index + c.len_utf8(),
+                    },
+                })),
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn custom_lexer() {
+    let parser = Parser::with_lexer(common::arithmetic_grammar(), CharLexer);
+
+    let tree = parser.parse("(0+0)*0").expect("Should be able to parse");
+    let default = Parser::new(common::arithmetic_grammar())
+        .parse("( 0 + 0 ) * 0")
+        .expect("Should be able to parse");
+
+    assert_eq!(tree.to_string(), default.to_string());
+}
+
+#[test]
+fn custom_lexer_errors() {
+    let parser = Parser::with_lexer(common::arithmetic_grammar(), CharLexer);
+
+    let error = ParseError::Tokenize(TokenizeError::InvalidToken {
+        token: "x".to_string(),
+        span: Span { start: 2, end: 3 },
+    });
+
+    assert_eq!(parser.parse("0+x").err(), Some(error.clone()));
+
+    let (_, errors) = parser.parse_recovering("0+x");
+    assert_eq!(errors.first(), Some(&error));
+}
+
+#[test]
+fn terminal_classes() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let operator = || T(Terminal::class("operator"));
+    let grammar = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(
+            NonTerminal::Sum,
+            vec![
+                vec![N(NonTerminal::Sum), operator(), T(Terminal::Zero)],
+                vec![T(Terminal::Zero)],
+            ],
+        )],
+    );
+    let mut parser = Parser::new(grammar);
+    parser.add_terminal_class("operator", |terminal| {
+        matches!(terminal, Terminal::Plus | Terminal::Minus | Terminal::Star)
+    });
+
+    let tree = parser
+        .parse("0 + 0 * 0 - 0")
+        .expect("Should be able to parse");
+    assert_eq!(
+        tree.leaves().map(Terminal::to_string).collect::<Vec<_>>(),
+        ["0", "+", "0", "*", "0", "-", "0"]
+    );
+    assert_eq!(
+        tree.leaves().nth(1),
+        Some(&Terminal::Class(
+            "operator".to_string(),
+            Some(Box::new(Terminal::Plus))
+        ))
+    );
+    assert!(parser.parse("0 ( 0").is_err());
+
+    // Terminals the grammar names are left alone, so `0` never becomes an
+    // operator even though the predicate accepts it.
+    parser.add_terminal_class("operator", |_| true);
+    assert!(parser.parse("0 + 0").is_ok());
+
+    // That follows the grammar as it changes: once a rule names `*`, it is
+    // no longer an operator.
+    parser.update_grammar(|grammar| {
+        grammar.rules.get_mut(&NonTerminal::Sum).unwrap().push(vec![
+            N(NonTerminal::Sum),
+            T(Terminal::Star),
+            T(Terminal::Zero),
+        ]);
+    });
+    let tree = parser.parse("0 * 0 + 0").expect("Should be able to parse");
+    assert_eq!(tree.leaves().nth(1), Some(&Terminal::Star));
+    assert!(matches!(tree.leaves().nth(3), Some(Terminal::Class(..))));
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use parser_macros::{NonTerminal, Parser, StackValue};
+
+/// Counts the `Number` trees below `value`.
+fn count_numbers(value: &StackValue) -> usize {
+    match value {
+        StackValue::Tree {
+            head: NonTerminal::Number,
+            values: _,
+        } => 1,
+        StackValue::Tree { head: _, values } => values.iter().map(count_numbers).sum(),
+        StackValue::Terminal(_) => 0,
+    }
+}
+
+#[test]
+fn empty_list() {
+    let parser = Parser::new(common::list_grammar());
+
+    let tree = parser.parse("[ ]").expect("Should be able to parse");
+
+    assert_eq!(count_numbers(&tree), 0);
+    assert!(matches!(
+        tree,
+        StackValue::Tree { head: NonTerminal::List, ref values } if values.len() == 3
+    ));
+}
+
+#[test]
+fn single_element() {
+    let parser = Parser::new(common::list_grammar());
+
+    let tree = parser.parse("[ 0 ]").expect("Should be able to parse");
+
+    assert_eq!(count_numbers(&tree), 1);
+}
+
+#[test]
+fn separated_elements() {
+    let parser = Parser::new(common::list_grammar());
+
+    let tree = parser
+        .parse("[ 0 , 0 , 0 ]")
+        .expect("Should be able to parse");
+
+    assert_eq!(count_numbers(&tree), 3);
+}
+
+#[test]
+fn rejects_missing_separator() {
+    let parser = Parser::new(common::list_grammar());
+
+    parser
+        .parse("[ 0 0 ]")
+        .expect_err("Elements need a separator");
+    parser
+        .parse("[ , ]")
+        .expect_err("Separator needs an element before it");
+}
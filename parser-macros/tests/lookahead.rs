@@ -0,0 +1,191 @@
+mod common;
+
+use std::collections::{HashMap, HashSet};
+
+use parser_macros::{
+    Expression, Grammar, NonTerminal, Parser, ReduceStrategy, StackValue, Terminal,
+};
+
+/// `sum -> atom '+' | number '+' | number '-'` with `atom -> '0'` and
+/// `number -> '0'`, so after a `0` followed by `+` both `atom` and `number`
+/// could be reduced.
+fn ambiguous_grammar() -> Grammar {
+    let mut rules = HashMap::new();
+
+    rules.insert(
+        NonTerminal::Sum,
+        vec![
+            vec![
+                Expression::NonTerminal(NonTerminal::Atom),
+                Expression::Terminal(Terminal::Plus),
+            ],
+            vec![
+                Expression::NonTerminal(NonTerminal::Number),
+                Expression::Terminal(Terminal::Plus),
+            ],
+            vec![
+                Expression::NonTerminal(NonTerminal::Number),
+                Expression::Terminal(Terminal::Minus),
+            ],
+        ],
+    );
+    rules.insert(
+        NonTerminal::Atom,
+        vec![vec![Expression::Terminal(Terminal::Zero)]],
+    );
+    rules.insert(
+        NonTerminal::Number,
+        vec![vec![Expression::Terminal(Terminal::Zero)]],
+    );
+
+    Grammar::new(NonTerminal::Sum, rules)
+}
+
+fn first_child(tree: &StackValue) -> &NonTerminal {
+    match tree {
+        StackValue::Tree { head: _, values } => match &values[0] {
+            StackValue::Tree { head, values: _ } => head,
+            StackValue::Terminal(t) => panic!("Expected a tree, found {}", t.terminal),
+        },
+        StackValue::Terminal(t) => panic!("Expected a tree, found {}", t.terminal),
+    }
+}
+
+#[test]
+#[should_panic(expected = "Ambiguous grammar")]
+fn unrestricted_reduction_is_ambiguous() {
+    let _ = Parser::new(ambiguous_grammar()).parse("0 +");
+}
+
+#[test]
+fn restriction_resolves_ambiguity() {
+    let mut grammar = ambiguous_grammar();
+    grammar
+        .reduce_lookahead
+        .insert((NonTerminal::Number, 0), HashSet::from([Terminal::Minus]));
+    let parser = Parser::new(grammar);
+
+    let tree = parser.parse("0 +").expect("Should be able to parse");
+    assert_eq!(first_child(&tree), &NonTerminal::Atom);
+
+    let tree = parser.parse("0 -").expect("Should be able to parse");
+    assert_eq!(first_child(&tree), &NonTerminal::Number);
+}
+
+#[test]
+fn restriction_does_not_apply_at_end_of_input() {
+    let mut grammar = common::arithmetic_grammar();
+    grammar
+        .reduce_lookahead
+        .insert((NonTerminal::Number, 0), HashSet::from([Terminal::Plus]));
+    let parser = Parser::new(grammar);
+
+    assert!(parser.parse("0 + 0").is_ok());
+    assert!(parser.parse("0 * 0").is_err());
+}
+
+#[test]
+fn reductions_are_deterministic() {
+    let parses: HashSet<String> = (0..20)
+        .map(|_| {
+            Parser::new(common::arithmetic_grammar())
+                .parse("( 0 + 0 ) * 0 - 0")
+                .expect("Should be able to parse")
+                .to_string()
+        })
+        .collect();
+    assert_eq!(parses.len(), 1);
+
+    let panics: HashSet<String> = (0..20)
+        .map(|_| {
+            let payload = std::panic::catch_unwind(|| {
+                let _ = Parser::new(ambiguous_grammar()).parse("0 +");
+            })
+            .expect_err("Should be ambiguous");
+            payload
+                .downcast_ref::<String>()
+                .expect("Panic message should be a String")
+                .clone()
+        })
+        .collect();
+    assert_eq!(panics.len(), 1);
+
+    let message = panics.into_iter().next().expect("Just checked");
+    let atom = message.find("atom =>").expect("Should list atom");
+    let number = message.find("number =>").expect("Should list number");
+    assert!(atom < number, "Candidates are sorted by name: {message}");
+}
+
+#[test]
+fn ambiguity_candidates_are_sorted_by_name() {
+    let message = |grammar: Grammar| -> String {
+        let payload = std::panic::catch_unwind(|| {
+            let _ = Parser::new(grammar).parse("0 +");
+        })
+        .expect_err("Should be ambiguous");
+        payload
+            .downcast_ref::<String>()
+            .expect("Panic message should be a String")
+            .clone()
+    };
+
+    // The same grammar with `number` reached before `atom`.
+    let mut reversed = ambiguous_grammar();
+    reversed
+        .rules
+        .get_mut(&NonTerminal::Sum)
+        .expect("Sum has rules")
+        .reverse();
+
+    let reversed = message(reversed);
+    assert_eq!(reversed, message(ambiguous_grammar()));
+    assert!(
+        reversed.find("atom =>") < reversed.find("number =>"),
+        "{reversed}"
+    );
+}
+
+#[test]
+fn reduce_strategy_decides_shift_reduce_conflicts() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let ambiguous = || {
+        Grammar::from_rules(
+            NonTerminal::Sum,
+            [(
+                NonTerminal::Sum,
+                vec![
+                    vec![N(NonTerminal::Sum), T(Terminal::Plus), N(NonTerminal::Sum)],
+                    vec![T(Terminal::Zero)],
+                ],
+            )],
+        )
+    };
+
+    let mut parser = Parser::new(ambiguous());
+    let left = parser.parse("0 + 0 + 0").expect("Should be able to parse");
+    assert_eq!(
+        left.to_string(),
+        "(sum (sum (sum '0') '+' (sum '0')) '+' (sum '0'))"
+    );
+
+    parser.set_reduce_strategy(ReduceStrategy::PreferShift);
+    let right = parser.parse("0 + 0 + 0").expect("Should be able to parse");
+    assert_eq!(
+        right.to_string(),
+        "(sum (sum '0') '+' (sum (sum '0') '+' (sum '0')))"
+    );
+
+    // Without a conflict both strategies agree.
+    let mut arithmetic = Parser::new(common::arithmetic_grammar());
+    let longest = arithmetic.parse("0 + 0 * ( 0 - 0 )").unwrap();
+    arithmetic.set_reduce_strategy(ReduceStrategy::PreferShift);
+    assert_eq!(arithmetic.parse("0 + 0 * ( 0 - 0 )").unwrap(), longest);
+
+    // Dispatch parsers built by an earlier parse follow the change too.
+    let mut dispatching = Parser::new(ambiguous());
+    dispatching.set_dispatch(HashMap::from([(Terminal::Zero, NonTerminal::Sum)]));
+    assert_eq!(dispatching.parse("0 + 0 + 0").unwrap(), left);
+    dispatching.set_reduce_strategy(ReduceStrategy::PreferShift);
+    assert_eq!(dispatching.parse("0 + 0 + 0").unwrap(), right);
+}
@@ -1,71 +1,945 @@
-use parser_macros::{Expression, Grammar, NonTerminal, Parser, Terminal};
-use std::collections::HashMap;
+mod common;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use parser_macros::{
+    Expression, Grammar, NonTerminal, ParseError, ParseMetrics, Parser, Span, StackValue,
+    StepResult, Terminal, Token, Tokenizer, TraceEvent,
+};
 
 #[test]
 fn tests() {
-    let mut rules = HashMap::new();
+    let g = common::arithmetic_grammar();
+
+    println!("{}", g);
+
+    let parser = Parser::new(g);
+
+    parser.parse("0 + 0 * 0").expect("Should be able to parse");
+
+    parser.parse("0 * 0 + 0").expect("Should be able to parse");
 
-    // Sum rules
-    rules.insert(
+    parser.parse("( 0 * 0 )").expect("Should be able to parse");
+}
+
+/// Both `atom -> ( sum )` and `atom -> number` have to survive into the
+/// same parser, rather than one alternative shadowing the other.
+#[test]
+fn every_atom_alternative_is_usable() {
+    let grammar = common::arithmetic_grammar();
+    assert_eq!(grammar.rules[&NonTerminal::Atom].len(), 2);
+
+    let parser = Parser::new(grammar);
+    let atom = |input| {
+        let tree = parser.parse(input).expect("Should be able to parse");
+        tree.find(&NonTerminal::Atom)
+            .expect("Every sum has an atom")
+            .to_string()
+    };
+
+    assert_eq!(atom("0"), "(atom (number '0'))");
+    assert_eq!(
+        atom("( 0 )"),
+        "(atom '(' (sum (sub (mult (atom (number '0'))))) ')')"
+    );
+}
+
+#[test]
+fn rejects_incomplete_input() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    parser.parse("0 +").expect_err("Dangling operator");
+    parser.parse("( 0").expect_err("Unclosed paren");
+}
+
+#[test]
+fn empty_input() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let parser = Parser::new(common::arithmetic_grammar());
+    for input in ["", "   ", "\n\t", "// nothing here"] {
+        assert_eq!(
+            parser.parse(input),
+            Err(ParseError::UnexpectedEnd {
+                expected: vec![Terminal::LeftParen, Terminal::Zero],
+            }),
+            "{input:?}"
+        );
+    }
+    assert_eq!(
+        parser.parse("").unwrap_err().to_string(),
+        "Unexpected end of input, expected one of: '(', '0'"
+    );
+    assert_eq!(
+        parser.parse_into_arena(" ").unwrap_err(),
+        parser.parse(" ").unwrap_err()
+    );
+    let mut state = parser.debug_start("");
+    assert_eq!(
+        parser.debug_step(&mut state),
+        StepResult::Error(parser.parse("").unwrap_err())
+    );
+
+    // A nullable starting symbol accepts it.
+    let zeros = Grammar::from_rules(
         NonTerminal::Sum,
-        vec![vec![
-            Expression::NonTerminal(NonTerminal::Sum),
-            Expression::Terminal(Terminal::Plus),
-            Expression::NonTerminal(NonTerminal::Sub),
-        ]],
-    );
-
-    // Sub rules
-    rules.insert(
-        NonTerminal::Sub,
-        vec![vec![
-            Expression::NonTerminal(NonTerminal::Sub),
-            Expression::Terminal(Terminal::Minus),
-            Expression::NonTerminal(NonTerminal::Mult),
-        ]],
-    );
-
-    // Mult rules
-    rules.insert(
-        NonTerminal::Mult,
-        vec![vec![
-            Expression::NonTerminal(NonTerminal::Mult),
-            Expression::Terminal(Terminal::Star),
-            Expression::NonTerminal(NonTerminal::Atom),
-        ]],
-    );
-
-    // Atom rules
-    rules.insert(
-        NonTerminal::Atom,
-        vec![
-            vec![
-                Expression::Terminal(Terminal::LeftParen),
-                Expression::NonTerminal(NonTerminal::Sum),
-                Expression::Terminal(Terminal::RightParen),
-            ],
-            vec![Expression::NonTerminal(NonTerminal::Number)],
+        [(
+            NonTerminal::Sum,
+            vec![vec![T(Terminal::Zero), N(NonTerminal::Sum)], vec![]],
+        )],
+    );
+    let parser = Parser::new(zeros);
+    for input in ["", "  "] {
+        let tree = parser.parse(input).expect("Should accept empty input");
+        assert_eq!(
+            tree,
+            StackValue::Tree {
+                head: NonTerminal::Sum,
+                values: vec![],
+            }
+        );
+    }
+}
+
+#[test]
+fn children_are_in_source_order() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let tree = parser.parse("0 - 0").expect("Should be able to parse");
+
+    // sum -> sub, where sub -> sub '-' mult
+    let StackValue::Tree {
+        head: NonTerminal::Sum,
+        values,
+    } = &tree
+    else {
+        panic!("Expected a sum, found {tree}");
+    };
+    let [
+        StackValue::Tree {
+            head: NonTerminal::Sub,
+            values,
+        },
+    ] = values.as_slice()
+    else {
+        panic!("Expected a single sub below the sum, found {tree}");
+    };
+
+    match values.as_slice() {
+        [
+            StackValue::Tree {
+                head: NonTerminal::Sub,
+                values: _,
+            },
+            StackValue::Terminal(Token {
+                terminal: Terminal::Minus,
+                ..
+            }),
+            StackValue::Tree {
+                head: NonTerminal::Mult,
+                values: _,
+            },
+        ] => {}
+        _ => panic!("Expected left operand, minus, right operand, found {tree}"),
+    }
+}
+
+#[test]
+fn parse_all() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let results = parser.parse_all(["0 + 0", "0 +", "( 0 * 0 )"]);
+
+    assert_eq!(results.len(), 3);
+    assert_eq!(
+        results[0].as_ref().map(ToString::to_string).ok(),
+        parser.parse("0 + 0").map(|tree| tree.to_string()).ok()
+    );
+    assert!(results[1].is_err());
+    assert!(results[2].is_ok());
+}
+
+#[test]
+fn parse_with_cancel() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let cancel = AtomicBool::new(false);
+    assert!(parser.parse_with_cancel("0 + 0", &cancel).is_ok());
+
+    cancel.store(true, Ordering::Relaxed);
+    assert_eq!(
+        parser.parse_with_cancel("0 + 0", &cancel).err(),
+        Some(ParseError::Cancelled)
+    );
+}
+
+#[test]
+fn max_parallel_parses() {
+    let mut parser = Parser::new(common::arithmetic_grammar());
+    assert_eq!(parser.max_parallel_parses(), 1024);
+
+    // The shift-reduce engine follows a single parse, so even a limit of
+    // one leaves it alone.
+    parser.set_max_parallel_parses(1);
+    assert_eq!(parser.max_parallel_parses(), 1);
+    assert!(parser.parse("0 + 0 * 0").is_ok());
+
+    assert_eq!(
+        ParseError::TooAmbiguous { limit: 1 }.to_string(),
+        "Input has more than 1 parses"
+    );
+}
+
+#[test]
+fn parse_spanned() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let span = |start, end| Span { start, end };
+
+    let tree = parser
+        .parse_spanned(vec![
+            (Terminal::Zero, span(10, 11)),
+            (Terminal::Star, span(20, 21)),
+            (Terminal::Zero, span(30, 31)),
+        ])
+        .expect("Should be able to parse");
+    assert_eq!(tree.to_string(), parser.parse("0 * 0").unwrap().to_string());
+
+    let StackValue::Tree { values, .. } = &tree else {
+        panic!("Expected a tree, found {tree}");
+    };
+    let StackValue::Tree { values, .. } = &values[0] else {
+        panic!("Expected a tree, found {tree}");
+    };
+    assert!(matches!(
+        &values[0],
+        StackValue::Tree { values, .. }
+            if matches!(&values[1], StackValue::Terminal(token) if token.span == span(20, 21))
+    ));
+
+    assert_eq!(
+        parser
+            .parse_spanned(vec![
+                (Terminal::Zero, span(0, 1)),
+                (Terminal::Zero, span(5, 6)),
+            ])
+            .err(),
+        Some(ParseError::UnexpectedToken {
+            found: Terminal::Zero,
+            position: 1,
+            span: span(5, 6),
+            expected: vec![Terminal::Plus, Terminal::Minus, Terminal::Star],
+        })
+    );
+}
+
+#[test]
+fn metrics() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let (tree, metrics) = parser
+        .parse_with_metrics("0 + 0")
+        .expect("Should be able to parse");
+
+    assert_eq!(tree.to_string(), parser.parse("0 + 0").unwrap().to_string());
+    assert_eq!(
+        metrics,
+        ParseMetrics {
+            shifts: 3,
+            reductions: 10,
+            peak_stack_depth: 3,
+            tokens: 3,
+        }
+    );
+
+    let (_, nested) = parser
+        .parse_with_metrics("( ( 0 ) )")
+        .expect("Should be able to parse");
+    assert_eq!(nested.shifts, 5);
+    assert_eq!(nested.peak_stack_depth, 4);
+}
+
+#[test]
+fn parse_prefix_reports_consumed_tokens() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let spanned = |terminals: Vec<Terminal>| -> Vec<(Terminal, Span)> {
+        terminals
+            .into_iter()
+            .enumerate()
+            .map(|(index, terminal)| {
+                (
+                    terminal,
+                    Span {
+                        start: index,
+                        end: index + 1,
+                    },
+                )
+            })
+            .collect()
+    };
+
+    let (tree, consumed) = parser
+        .parse_prefix(spanned(vec![
+            Terminal::Zero,
+            Terminal::Plus,
+            Terminal::Zero,
+            Terminal::RightParen,
+            Terminal::Star,
+        ]))
+        .expect("Should be able to parse a prefix");
+    assert_eq!(consumed, 3);
+    assert_eq!(tree.to_string(), parser.parse("0 + 0").unwrap().to_string());
+
+    // The trailing `+` is shifted but never completed, so the parse backs
+    // up to the last complete prefix.
+    let (_, consumed) = parser
+        .parse_prefix(spanned(vec![
+            Terminal::Zero,
+            Terminal::Star,
+            Terminal::Zero,
+            Terminal::Plus,
+            Terminal::RightParen,
+        ]))
+        .expect("Should be able to parse a prefix");
+    assert_eq!(consumed, 3);
+
+    assert_eq!(
+        parser
+            .parse_prefix(spanned(vec![Terminal::Plus]))
+            .unwrap_err(),
+        ParseError::UnexpectedToken {
+            found: Terminal::Plus,
+            position: 0,
+            span: Span { start: 0, end: 1 },
+            expected: vec![Terminal::LeftParen, Terminal::Zero],
+        }
+    );
+}
+
+#[test]
+fn parses_every_prefix() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let prefixes = parser.parse_prefixes("( 0 ) * 0 + ( 0 ) )");
+    assert_eq!(
+        prefixes
+            .iter()
+            .map(|(consumed, _)| *consumed)
+            .collect::<Vec<_>>(),
+        [3, 5, 9]
+    );
+    for (consumed, tree) in &prefixes {
+        let prefix: Vec<&str> = "( 0 ) * 0 + ( 0 )".split(' ').take(*consumed).collect();
+        assert_eq!(
+            tree.to_string(),
+            parser.parse(&prefix.join(" ")).unwrap().to_string()
+        );
+    }
+
+    assert!(parser.parse_prefixes("+ 0").is_empty());
+    assert_eq!(parser.parse_prefixes("0 # 0").len(), 1);
+}
+
+#[test]
+fn accepts_only_the_starting_symbol() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let parser = Parser::new(common::arithmetic_grammar());
+    for input in ["0", "0 + 0", "( 0 )", "0 * 0 - 0"] {
+        let tree = parser.parse(input).expect("Should be able to parse");
+        assert!(matches!(
+            tree,
+            StackValue::Tree {
+                head: NonTerminal::Sum,
+                ..
+            }
+        ));
+    }
+
+    // `0` reduces to a single `atom` at the end of input, which is one
+    // tree but not the starting symbol.
+    let grammar = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![vec![N(NonTerminal::Atom), N(NonTerminal::Atom)]],
+            ),
+            (NonTerminal::Atom, vec![vec![T(Terminal::Zero)]]),
         ],
     );
+    let parser = Parser::new(grammar);
+    assert!(parser.parse("0 0").is_ok());
+    let error = parser.parse("0").unwrap_err();
+    let ParseError::BadStack { stack, next: None } = &error else {
+        panic!("Expected a bad stack, found {error}");
+    };
+    assert_eq!(
+        stack.iter().map(StackValue::to_string).collect::<Vec<_>>(),
+        ["(atom '0')"]
+    );
+}
+
+#[test]
+fn debug_steps() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let reduced = |nt, production| StepResult::Reduced { nt, production };
+
+    let mut state = parser.debug_start("0 + 0");
+    let mut steps = vec![];
+    while !state.is_finished() {
+        steps.push(parser.debug_step(&mut state));
+    }
+
+    let tree = parser.parse("0 + 0").unwrap();
+    assert_eq!(
+        steps,
+        [
+            StepResult::Shifted(Terminal::Zero),
+            reduced(NonTerminal::Number, 0),
+            reduced(NonTerminal::Atom, 1),
+            reduced(NonTerminal::Mult, 1),
+            reduced(NonTerminal::Sub, 1),
+            reduced(NonTerminal::Sum, 1),
+            StepResult::Shifted(Terminal::Plus),
+            StepResult::Shifted(Terminal::Zero),
+            reduced(NonTerminal::Number, 0),
+            reduced(NonTerminal::Atom, 1),
+            reduced(NonTerminal::Mult, 1),
+            reduced(NonTerminal::Sub, 1),
+            reduced(NonTerminal::Sum, 0),
+            StepResult::Accepted(tree.clone()),
+        ]
+    );
+    assert_eq!(state.position(), 3);
+    assert_eq!(parser.debug_step(&mut state), StepResult::Accepted(tree));
+
+    let mut state = parser.debug_start("0 )");
+    assert_eq!(
+        parser.debug_step(&mut state),
+        StepResult::Shifted(Terminal::Zero)
+    );
+    assert_eq!(
+        state.next_token().map(|token| &token.terminal),
+        Some(&Terminal::RightParen)
+    );
+    let mut last = parser.debug_step(&mut state);
+    while !state.is_finished() {
+        last = parser.debug_step(&mut state);
+    }
+    assert_eq!(last, StepResult::Error(parser.parse("0 )").unwrap_err()));
+
+    let mut state = parser.debug_start("0 #");
+    assert!(state.is_finished());
+    assert!(matches!(
+        parser.debug_step(&mut state),
+        StepResult::Error(ParseError::Tokenize(_))
+    ));
+}
+
+#[test]
+fn bad_stack_keeps_the_stack() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let error = parser.parse("( 0 + 0").unwrap_err();
+    let ParseError::BadStack { stack, next } = &error else {
+        panic!("Expected a bad stack, found {error}");
+    };
+
+    assert_eq!(*next, None);
+    assert_eq!(
+        stack.iter().map(StackValue::to_string).collect::<Vec<_>>(),
+        [
+            "'('",
+            "(sum (sum (sub (mult (atom (number '0'))))) '+' (sub (mult (atom (number '0')))))",
+        ]
+    );
+    assert_eq!(
+        error.to_string(),
+        format!("Bad stack: {} {} at the end of input", stack[0], stack[1])
+    );
+
+    let (_, arena_error) = (
+        (),
+        parser
+            .parse_into_arena("( 0 + 0")
+            .expect_err("Input is unfinished"),
+    );
+    assert_eq!(arena_error, error);
+}
+
+#[test]
+fn production_coverage() {
+    let mut parser = Parser::new(common::arithmetic_grammar());
+    assert!(parser.production_coverage().is_empty());
+
+    parser.enable_coverage();
+    parser.parse("0 + 0").expect("Should be able to parse");
+    parser.parse("0 * 0").expect("Should be able to parse");
+    assert!(parser.parse("0 +").is_err());
+    assert!(parser.parse("0 0").is_err(), "Fails before the end");
+
+    let coverage = parser.production_coverage();
+    assert_eq!(coverage.len(), 9);
+    assert_eq!(coverage[&(NonTerminal::Sum, 0)], 1);
+    assert_eq!(coverage[&(NonTerminal::Mult, 0)], 1);
+    assert_eq!(coverage[&(NonTerminal::Number, 0)], 5);
+    let never: HashSet<_> = coverage
+        .iter()
+        .filter(|(_, count)| **count == 0)
+        .map(|(production, _)| production.clone())
+        .collect();
+    assert_eq!(
+        never,
+        HashSet::from([(NonTerminal::Sub, 0), (NonTerminal::Atom, 0)])
+    );
+
+    parser.reset_coverage();
+    assert!(
+        parser
+            .production_coverage()
+            .values()
+            .all(|count| *count == 0)
+    );
+}
+
+#[test]
+fn parse_multiple_segments() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let trees = parser
+        .parse_multiple("0 + 0 , ( 0 ) , 0 * 0", Terminal::Comma)
+        .expect("Should be able to parse");
+    assert_eq!(
+        trees.iter().map(StackValue::to_string).collect::<Vec<_>>(),
+        ["0 + 0", "( 0 )", "0 * 0"].map(|input| {
+            parser
+                .parse(input)
+                .expect("Should be able to parse")
+                .to_string()
+        })
+    );
+
+    let error = parser
+        .parse_multiple("0 , 0 ) 0 , 0", Terminal::Comma)
+        .expect_err("Second segment is invalid");
+    assert!(
+        matches!(
+            &error,
+            ParseError::Segment { index: 1, error }
+                if matches!(
+                    error.as_ref(),
+                    ParseError::UnexpectedToken {
+                        found: Terminal::RightParen,
+                        position: 1,
+                        span: Span { start: 6, end: 7 },
+                        ..
+                    }
+                )
+        ),
+        "{error:?}"
+    );
+    assert!(
+        error
+            .to_string()
+            .starts_with("In segment 1: Unexpected ')'")
+    );
+
+    assert!(matches!(
+        parser.parse_multiple("0 ,", Terminal::Comma),
+        Err(ParseError::Segment { index: 1, .. })
+    ));
+}
+
+#[test]
+fn recognize() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    for input in ["0", "( 0 + 0 ) * 0", "0 - 0 - 0", "0 +", "( 0", "0 0", ""] {
+        let tokens: Vec<Terminal> = Tokenizer::new()
+            .tokenize(input)
+            .expect("Should be able to tokenize")
+            .into_iter()
+            .map(|token| token.terminal)
+            .collect();
+        assert_eq!(
+            parser.recognize(&tokens),
+            parser.parse(input).is_ok(),
+            "{input:?}"
+        );
+    }
+    assert!(parser.recognize(&[Terminal::Zero, Terminal::Star, Terminal::Zero]));
+}
+
+#[test]
+fn parse_with_trace() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let (result, trace) = parser.parse_with_trace("0 * 0");
+    assert_eq!(result, parser.parse("0 * 0"));
+
+    assert_eq!(trace[0], TraceEvent::Shift(Terminal::Zero));
+    assert_eq!(trace[0].to_string(), "Adding terminal: 0");
+    assert!(trace[1].to_string().starts_with("Stack state: [Terminal("));
+
+    let productions: Vec<&str> = trace
+        .iter()
+        .filter_map(|event| match event {
+            TraceEvent::Reduce { production, .. } => Some(production.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        productions,
+        [
+            "number -> '0'",
+            "atom -> number",
+            "mult -> atom",
+            "number -> '0'",
+            "atom -> number",
+            "mult -> mult '*' atom",
+            "sub -> mult",
+            "sum -> sub",
+        ]
+    );
+
+    // A failed parse keeps the trace up to where it stopped.
+    let (result, trace) = parser.parse_with_trace("0 )");
+    assert!(result.is_err());
+    assert_eq!(trace.first(), Some(&TraceEvent::Shift(Terminal::Zero)));
+    assert!(!trace.contains(&TraceEvent::Shift(Terminal::RightParen)));
+}
+
+#[test]
+fn dispatch_on_the_first_token() {
+    let mut grammar = common::arithmetic_grammar();
+    grammar.rules.extend(common::list_grammar().rules);
+    let mut parser = Parser::new(grammar);
+    parser.set_dispatch(HashMap::from([
+        (Terminal::LeftBracket, NonTerminal::List),
+        (Terminal::LeftParen, NonTerminal::Sum),
+        (Terminal::Zero, NonTerminal::Sum),
+    ]));
+
+    let list = parser.parse("[ 0 , 0 ]").expect("Should be able to parse");
+    assert!(matches!(
+        list,
+        StackValue::Tree {
+            head: NonTerminal::List,
+            ..
+        }
+    ));
+    let sum = parser.parse("( 0 ) + 0").expect("Should be able to parse");
+    assert!(matches!(
+        sum,
+        StackValue::Tree {
+            head: NonTerminal::Sum,
+            ..
+        }
+    ));
+    assert!(parser.parse("[ 0 + 0 ]").is_err());
+
+    let expected = vec![Terminal::LeftParen, Terminal::Zero, Terminal::LeftBracket];
+    assert_eq!(
+        parser.parse(", 0"),
+        Err(ParseError::UnexpectedToken {
+            expected: expected.clone(),
+            found: Terminal::Comma,
+            position: 0,
+            span: Span { start: 0, end: 1 },
+        })
+    );
+    assert_eq!(
+        parser.parse(""),
+        Err(ParseError::UnexpectedEnd { expected })
+    );
+
+    parser.set_dispatch(HashMap::new());
+    assert!(parser.parse("[ 0 ]").is_err());
+    assert!(parser.parse("0 + 0").is_ok());
+}
+
+#[test]
+fn dispatch_follows_later_settings() {
+    let mut grammar = common::arithmetic_grammar();
+    grammar.rules.extend(common::list_grammar().rules);
+    let mut parser = Parser::new(grammar.clone());
+    parser.set_dispatch(HashMap::from([
+        (Terminal::LeftBracket, NonTerminal::List),
+        (Terminal::Zero, NonTerminal::Sum),
+    ]));
+    let mut plain = Parser::new(grammar);
+
+    // Parse once first, so the dispatch parsers are already built.
+    assert_eq!(parser.parse("0 + 0"), plain.parse("0 + 0"));
+
+    parser.collapse_unit_productions(true);
+    plain.collapse_unit_productions(true);
+    assert_eq!(parser.parse("0 + 0"), plain.parse("0 + 0"));
+    assert_eq!(parser.parse("0").unwrap().to_string(), "(sum '0')");
+
+    parser.enable_coverage();
+    parser.parse("[ 0 ]").expect("Should be able to parse");
+    parser.parse("0 * 0").expect("Should be able to parse");
+    let coverage = parser.production_coverage();
+    assert_eq!(coverage[&(NonTerminal::List, 0)], 1);
+    assert_eq!(coverage[&(NonTerminal::Mult, 0)], 1);
+    assert_eq!(coverage.values().sum::<u64>(), 11);
+}
+
+#[test]
+fn dispatch_in_every_entry_point() {
+    let mut grammar = common::arithmetic_grammar();
+    grammar.rules.extend(common::list_grammar().rules);
+    let mut parser = Parser::new(grammar);
+    parser.set_dispatch(HashMap::from([
+        (Terminal::LeftBracket, NonTerminal::List),
+        (Terminal::Zero, NonTerminal::Sum),
+    ]));
+    let list = "(list '[' (elements (number '0')) ']')";
+    let unexpected = Err(ParseError::UnexpectedToken {
+        expected: vec![Terminal::Zero, Terminal::LeftBracket],
+        found: Terminal::Comma,
+        position: 0,
+        span: Span { start: 0, end: 1 },
+    });
+
+    let (tree, _) = parser.parse_with_metrics("[ 0 ]").unwrap();
+    assert_eq!(tree.to_string(), list);
+    assert_eq!(
+        parser.parse_with_metrics(", 0").map(|(tree, _)| tree),
+        unexpected
+    );
+
+    let cancel = AtomicBool::new(false);
+    assert_eq!(
+        parser
+            .parse_with_cancel("[ 0 ]", &cancel)
+            .unwrap()
+            .to_string(),
+        list
+    );
+    assert_eq!(parser.parse_with_cancel(", 0", &cancel), unexpected);
 
-    // Number rules
-    rules.insert(
-        NonTerminal::Number,
-        vec![vec![Expression::Terminal(Terminal::Zero)]],
+    let (result, trace) = parser.parse_with_trace("[ 0 ]");
+    assert_eq!(result.unwrap().to_string(), list);
+    assert_eq!(
+        trace.first(),
+        Some(&TraceEvent::Shift(Terminal::LeftBracket))
     );
+    assert_eq!(parser.parse_with_trace(", 0"), (unexpected, vec![]));
 
-    let g = Grammar {
-        starting_symbol: NonTerminal::Sum,
-        rules,
+    assert!(parser.recognize(&[
+        Terminal::LeftBracket,
+        Terminal::Zero,
+        Terminal::RightBracket
+    ]));
+    assert!(parser.recognize(&[Terminal::Zero, Terminal::Plus, Terminal::Zero]));
+    assert!(!parser.recognize(&[Terminal::Comma, Terminal::Zero]));
+    assert!(!parser.recognize(&[]));
+
+    let trees = parser
+        .parse_multiple("[ 0 ] ; 0", Terminal::Semicolon)
+        .expect("Should be able to parse");
+    assert_eq!(trees[0].to_string(), list);
+    assert_eq!(
+        trees[1].to_string(),
+        "(sum (sub (mult (atom (number '0')))))"
+    );
+    assert_eq!(
+        parser.parse_multiple("0 ; , 0", Terminal::Semicolon),
+        Err(ParseError::Segment {
+            index: 1,
+            error: Box::new(ParseError::UnexpectedToken {
+                expected: vec![Terminal::Zero, Terminal::LeftBracket],
+                found: Terminal::Comma,
+                position: 0,
+                span: Span { start: 4, end: 5 },
+            }),
+        })
+    );
+}
+
+/// Rejects `0 * 0 * 0`: a product whose left operand is itself a product,
+/// so two `Star`s follow each other in the tree without parentheses.
+fn no_chained_products(value: &StackValue) -> Result<(), ParseError> {
+    let StackValue::Tree { head, values } = value else {
+        return Ok(());
     };
 
-    println!("{}", g);
+    if *head == NonTerminal::Mult
+        && let [StackValue::Tree { head, values: left }, ..] = values.as_slice()
+        && *head == NonTerminal::Mult
+        && left.len() == 3
+    {
+        return Err(ParseError::Semantic {
+            message: "Parenthesize chained products".to_string(),
+            span: None,
+        });
+    }
 
-    let parser = Parser::new(g);
+    values.iter().try_for_each(no_chained_products)
+}
 
-    parser.parse("0 + 0 * 0").expect("Should be able to parse");
+#[test]
+fn post_check() {
+    let parser =
+        Parser::with_post_check(common::arithmetic_grammar(), Box::new(no_chained_products));
 
-    parser.parse("0 * 0 + 0").expect("Should be able to parse");
+    assert!(parser.parse("0 * 0").is_ok());
+    assert!(parser.parse("( 0 * 0 ) * 0").is_ok());
+    assert_eq!(
+        parser.parse("0 * 0 * 0"),
+        Err(ParseError::Semantic {
+            message: "Parenthesize chained products".to_string(),
+            span: None,
+        })
+    );
+    assert!(parser.parse_with_trace("0 * 0 * 0").0.is_err());
+    assert!(
+        Parser::new(common::arithmetic_grammar())
+            .parse("0 * 0 * 0")
+            .is_ok()
+    );
+}
 
-    parser.parse("( 0 * 0 )").expect("Should be able to parse");
+#[test]
+fn max_nesting_depth() {
+    let nested = |depth: usize| format!("{}0{}", "( ".repeat(depth), " )".repeat(depth));
+    let mut parser = Parser::new(common::arithmetic_grammar());
+    parser.set_max_nesting_depth(Some(4));
+
+    assert!(parser.parse(&nested(4)).is_ok());
+    assert!(parser.parse("( ( 0 ) ) * ( ( ( 0 ) + ( 0 ) ) )").is_ok());
+    let error = parser.parse(&nested(5)).unwrap_err();
+    assert_eq!(
+        error,
+        ParseError::NestingTooDeep {
+            limit: 4,
+            span: Span { start: 8, end: 9 },
+        }
+    );
+    assert_eq!(error.to_string(), "Nesting deeper than 4 at 8..9");
+
+    parser.set_max_nesting_depth(None);
+    assert!(parser.parse(&nested(50)).is_ok());
+
+    let mut lists = Parser::new(common::list_grammar());
+    lists.set_max_nesting_depth(Some(0));
+    assert_eq!(
+        lists.parse("[ 0 ]"),
+        Err(ParseError::NestingTooDeep {
+            limit: 0,
+            span: Span { start: 0, end: 1 },
+        })
+    );
+}
+
+#[test]
+fn max_nesting_depth_is_checked_at_reduction() {
+    let mut parser = Parser::new(common::arithmetic_grammar());
+    parser.set_max_nesting_depth(Some(1));
+
+    // Groups that are never closed are never reduced, so they do not count.
+    assert!(matches!(
+        parser.parse("( ( 0"),
+        Err(ParseError::BadStack { .. })
+    ));
+    assert!(!parser.recognize(&[
+        Terminal::LeftParen,
+        Terminal::LeftParen,
+        Terminal::Zero,
+        Terminal::RightParen,
+        Terminal::RightParen,
+    ]));
+    assert_eq!(
+        parser.explain_failure("( ( 0 ) )"),
+        "Parsing stopped before ')' (token 4): Nesting deeper than 1 at 2..3.\n"
+    );
+
+    let too_deep = ParseError::NestingTooDeep {
+        limit: 1,
+        span: Span { start: 2, end: 3 },
+    };
+    let mut debug = parser.debug_start("( ( 0 ) )");
+    let mut last = parser.debug_step(&mut debug);
+    while !debug.is_finished() {
+        last = parser.debug_step(&mut debug);
+    }
+    assert_eq!(last, StepResult::Error(too_deep));
+}
+
+#[test]
+fn max_nesting_depth_in_recovering_parses() {
+    let mut parser = Parser::new(common::arithmetic_grammar());
+    parser.set_max_nesting_depth(Some(2));
+    let too_deep = ParseError::NestingTooDeep {
+        limit: 2,
+        span: Span { start: 8, end: 9 },
+    };
+
+    assert_eq!(parser.parse_recovering("( ( 0 ) )"), {
+        let tree = parser.parse("( ( 0 ) )").unwrap();
+        (Some(tree), vec![])
+    });
+    assert_eq!(
+        parser.parse_recovering("0 + ( ( ( 0 ) ) )"),
+        (None, vec![too_deep.clone()])
+    );
+    let sync = HashSet::from([Terminal::RightParen]);
+    assert_eq!(
+        parser.parse_with_recovery_points("0 + ( ( ( 0 ) ) )", &sync),
+        (None, vec![too_deep.clone()])
+    );
+
+    let (islands, errors) = parser.parse_best_effort("0 + + ( ( ( 0 ) ) )");
+    assert_eq!(islands.len(), 1);
+    assert_eq!(
+        islands[0].to_string(),
+        parser.parse("0").unwrap().to_string()
+    );
+    assert_eq!(
+        errors,
+        vec![
+            ParseError::UnexpectedToken {
+                expected: vec![Terminal::LeftParen, Terminal::Zero],
+                found: Terminal::Plus,
+                position: 2,
+                span: Span { start: 4, end: 5 },
+            },
+            ParseError::NestingTooDeep {
+                limit: 2,
+                span: Span { start: 10, end: 11 },
+            },
+        ]
+    );
+}
+
+#[test]
+fn max_nesting_depth_after_dispatch() {
+    let mut grammar = common::arithmetic_grammar();
+    grammar.rules.extend(common::list_grammar().rules);
+    let mut parser = Parser::new(grammar);
+    parser.set_dispatch(HashMap::from([
+        (Terminal::LeftBracket, NonTerminal::List),
+        (Terminal::LeftParen, NonTerminal::Sum),
+    ]));
+
+    // Parse once first, so the dispatch parsers are already built.
+    assert!(parser.parse("( ( 0 ) )").is_ok());
+
+    parser.set_max_nesting_depth(Some(1));
+    assert_eq!(
+        parser.parse("( ( 0 ) )"),
+        Err(ParseError::NestingTooDeep {
+            limit: 1,
+            span: Span { start: 2, end: 3 },
+        })
+    );
+    assert!(parser.parse("[ 0 ]").is_ok());
+
+    parser.set_max_nesting_depth(None);
+    assert!(parser.parse("( ( 0 ) )").is_ok());
 }
@@ -0,0 +1,234 @@
+#![cfg(feature = "regex")]
+
+mod common;
+
+use parser_macros::{
+    Expression, FloatLiteral, Grammar, NonTerminal, ParseError, Parser, PatternError, Span,
+    Terminal, Token, TokenizeError, Tokenizer, TokenizerConfig,
+};
+
+fn arithmetic_patterns() -> TokenizerConfig {
+    [
+        (Terminal::Plus, r"\+"),
+        (Terminal::Minus, "-"),
+        (Terminal::Star, r"\*"),
+        (Terminal::LeftParen, r"\("),
+        (Terminal::RightParen, r"\)"),
+        (Terminal::Zero, "0"),
+        (Terminal::Identifier(String::new()), r"[a-zA-Z_]\w*"),
+    ]
+    .into_iter()
+    .try_fold(TokenizerConfig::new(), |config, (kind, pattern)| {
+        config.with_pattern(kind, pattern)
+    })
+    .expect("Patterns should be valid")
+}
+
+#[test]
+fn parses_with_patterns() {
+    let parser = Parser::with_lexer(common::variable_grammar(), arithmetic_patterns());
+
+    let tree = parser
+        .parse("rate*(0+x_1)")
+        .expect("Should be able to parse");
+    let default = Parser::new(common::variable_grammar())
+        .parse("rate * ( 0 + x_1 )")
+        .expect("Should be able to parse");
+
+    assert_eq!(tree.to_string(), default.to_string());
+
+    // The patterns are the terminals the parser can see.
+    assert_eq!(parser.unused_terminals(), Some(vec![]));
+    let parser = Parser::with_lexer(common::list_grammar(), arithmetic_patterns());
+    assert_eq!(
+        parser.undeclared_terminals(),
+        Some(vec![
+            Terminal::LeftBracket,
+            Terminal::RightBracket,
+            Terminal::Comma
+        ])
+    );
+}
+
+#[test]
+fn longest_match_wins() {
+    let config = TokenizerConfig::new()
+        .with_pattern(Terminal::Equal, "=")
+        .and_then(|config| config.with_pattern(Terminal::DoubleEqual, "=="))
+        .and_then(|config| config.with_pattern(Terminal::Identifier(String::new()), "[a-z]+"))
+        .and_then(|config| config.with_pattern(Terminal::Arrow, "let"))
+        .and_then(|config| {
+            config.with_pattern(Terminal::StringLiteral(String::new()), r#""([^"\\]|\\.)*""#)
+        })
+        .expect("Patterns should be valid");
+
+    let tokens = config
+        .tokenize("a==b = let\t\"x \\\" y\"")
+        .expect("Should be able to tokenize");
+
+    assert_eq!(
+        tokens,
+        [
+            (Terminal::Identifier("a".to_string()), 0, 1),
+            (Terminal::DoubleEqual, 1, 3),
+            (Terminal::Identifier("b".to_string()), 3, 4),
+            (Terminal::Equal, 5, 6),
+            // Ties go to the pattern added first.
+            (Terminal::Identifier("let".to_string()), 7, 10),
+            (Terminal::StringLiteral("\"x \\\" y\"".to_string()), 11, 19),
+        ]
+        .map(|(terminal, start, end)| Token::new(terminal, Span { start, end }))
+    );
+}
+
+#[test]
+fn reports_text_no_pattern_matches() {
+    let parser = Parser::with_lexer(common::variable_grammar(), arithmetic_patterns());
+
+    assert_eq!(
+        parser.parse("a + #b c"),
+        Err(ParseError::Tokenize(TokenizeError::InvalidToken {
+            token: "#b".to_string(),
+            span: Span { start: 4, end: 6 },
+        }))
+    );
+}
+
+#[test]
+fn number_patterns_hold_their_value() {
+    let config = TokenizerConfig::new()
+        .with_pattern(Terminal::Float(None), r"\d+\.\d*")
+        .and_then(|config| config.with_pattern(Terminal::Int(None), r"-?\d+"))
+        .expect("Patterns should be valid");
+
+    let terminals: Vec<Terminal> = config
+        .tokenize("-12 3.25 7.")
+        .expect("Should be able to tokenize")
+        .into_iter()
+        .map(|token| token.terminal)
+        .collect();
+    assert_eq!(
+        terminals,
+        vec![
+            Terminal::Int(Some(-12)),
+            Terminal::Float(Some(FloatLiteral(3.25))),
+            Terminal::Float(Some(FloatLiteral(7.0))),
+        ]
+    );
+
+    assert_eq!(
+        config.tokenize("1 99999999999999999999"),
+        Err(TokenizeError::MalformedNumber {
+            number: "99999999999999999999".to_string(),
+            span: Span { start: 2, end: 22 },
+        })
+    );
+}
+
+#[test]
+fn number_patterns_agree_with_the_tokenizer() {
+    use Expression::Terminal as T;
+
+    let config = [
+        (Terminal::Float(None), r"\d+\.\d*|\.\d+"),
+        (Terminal::Int(None), r"[1-9]\d*"),
+        (Terminal::Zero, "0"),
+        (Terminal::Plus, r"\+"),
+        (Terminal::Minus, "-"),
+        (Terminal::Star, r"\*"),
+    ]
+    .into_iter()
+    .try_fold(TokenizerConfig::new(), |config, (kind, pattern)| {
+        config.with_pattern(kind, pattern)
+    })
+    .expect("Patterns should be valid");
+
+    let input = "42 + 2.75 * 3. - .5 0";
+    let terminals = |tokens: Vec<Token>| -> Vec<Terminal> {
+        tokens.into_iter().map(|token| token.terminal).collect()
+    };
+    assert_eq!(
+        terminals(config.tokenize(input).expect("Should be able to tokenize")),
+        terminals(
+            Tokenizer::new()
+                .tokenize(input)
+                .expect("Should be able to tokenize")
+        )
+    );
+
+    let grammar = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(
+            NonTerminal::Sum,
+            vec![vec![T(Terminal::Int(None))], vec![T(Terminal::Float(None))]],
+        )],
+    );
+    let parser = Parser::with_lexer(grammar, config);
+    let tree = parser.parse("12").expect("Should be able to parse");
+    assert_eq!(tree.leaves().next(), Some(&Terminal::Int(Some(12))));
+    let tree = parser.parse(".5").expect("Should be able to parse");
+    assert_eq!(
+        tree.leaves().next(),
+        Some(&Terminal::Float(Some(FloatLiteral(0.5))))
+    );
+}
+
+#[test]
+fn rejects_invalid_patterns() {
+    for (pattern, error) in [
+        ("(a", PatternError::UnbalancedGroup { offset: 0 }),
+        ("a)", PatternError::UnbalancedGroup { offset: 1 }),
+        ("a[bc", PatternError::UnterminatedClass { offset: 1 }),
+        ("*a", PatternError::MissingOperand { offset: 0 }),
+        ("a|+", PatternError::MissingOperand { offset: 2 }),
+        ("[0z-a]", PatternError::InvalidRange { offset: 2 }),
+        (r"a\", PatternError::DanglingEscape { offset: 1 }),
+        ("a*", PatternError::MatchesEmpty),
+        ("a|(b?)", PatternError::MatchesEmpty),
+    ] {
+        assert_eq!(
+            TokenizerConfig::new()
+                .with_pattern(Terminal::Zero, pattern)
+                .err(),
+            Some(error),
+            "{pattern}"
+        );
+    }
+}
+
+#[test]
+fn regular_constraints() {
+    let grammar = common::arithmetic_grammar();
+    // An odd number of tokens.
+    let odd = "[^ ]+( [^ ]+ [^ ]+)*";
+
+    assert_eq!(grammar.accepts_both(odd, "0 + 0"), Ok(true));
+    assert_eq!(grammar.accepts_both(odd, "( 0 )"), Ok(true));
+    assert_eq!(grammar.accepts_both(odd, "0 +"), Ok(false));
+    assert_eq!(grammar.accepts_both(r"[^*]*", "0 * 0"), Ok(false));
+    assert_eq!(grammar.accepts_both("", ""), Ok(false));
+    assert_eq!(
+        grammar.accepts_both("(0", "0"),
+        Err(PatternError::UnbalancedGroup { offset: 0 })
+    );
+
+    let sentences = grammar
+        .intersect_regular(r"[^+\-]*\*[^+\-]*", 5)
+        .expect("Should be a valid pattern");
+    let written: Vec<String> = sentences
+        .iter()
+        .map(|sentence| {
+            sentence
+                .iter()
+                .map(Terminal::to_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect();
+    assert_eq!(
+        written,
+        ["0 * 0", "( 0 ) * 0", "( 0 * 0 )", "0 * ( 0 )", "0 * 0 * 0"]
+    );
+
+    assert_eq!(grammar.intersect_regular("0 0", 6), Ok(vec![]));
+}
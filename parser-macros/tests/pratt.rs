@@ -0,0 +1,102 @@
+mod common;
+
+use parser_macros::{NonTerminal, ParseError, Parser, PrecedenceTable, Span, StackValue, Terminal};
+
+fn table() -> PrecedenceTable {
+    PrecedenceTable::new(NonTerminal::Expr)
+        .atom(Terminal::Zero)
+        .group(Terminal::LeftParen, Terminal::RightParen)
+        .infix(Terminal::Plus, 1, 2)
+        .infix(Terminal::Minus, 1, 2)
+        .infix(Terminal::Star, 3, 4)
+        .infix(Terminal::Arrow, 6, 5)
+        .prefix(Terminal::Minus, 7)
+}
+
+/// The tree's bracketing, with single-child trees collapsed so that trees
+/// from a precedence hierarchy and from a single nonterminal compare equal.
+fn shape(value: &StackValue) -> String {
+    match value {
+        StackValue::Terminal(token) => token.terminal.to_string(),
+        StackValue::Tree { values, .. } if values.len() == 1 => shape(&values[0]),
+        StackValue::Tree { values, .. } => format!(
+            "({})",
+            values.iter().map(shape).collect::<Vec<String>>().join(" ")
+        ),
+    }
+}
+
+#[test]
+fn matches_the_grammar() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    for input in ["0 + 0 * 0", "0 * 0 + 0", "( 0 + 0 ) * 0", "0 - 0 - 0"] {
+        let grammar = parser.parse(input).expect("Should be able to parse");
+        let pratt = parser
+            .parse_pratt(input, &table())
+            .expect("Should be able to parse");
+
+        assert_eq!(shape(&pratt), shape(&grammar), "{input}");
+    }
+
+    let tree = parser
+        .parse_pratt("0 + 0 * 0", &table())
+        .expect("Should be able to parse");
+    assert_eq!(shape(&tree), "(0 + (0 * 0))");
+    let StackValue::Tree { head, .. } = &tree else {
+        panic!("Expected a tree");
+    };
+    assert_eq!(*head, NonTerminal::Expr);
+}
+
+#[test]
+fn associativity_and_prefix() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let shape_of = |input| {
+        shape(
+            &parser
+                .parse_pratt(input, &table())
+                .expect("Should be able to parse"),
+        )
+    };
+
+    assert_eq!(shape_of("0 - 0 - 0"), "((0 - 0) - 0)");
+    assert_eq!(shape_of("0 -> 0 -> 0"), "(0 -> (0 -> 0))");
+    assert_eq!(shape_of("- 0 * 0"), "((- 0) * 0)");
+    assert_eq!(shape_of("0 - - 0"), "(0 - (- 0))");
+}
+
+#[test]
+fn errors() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    assert_eq!(
+        parser.parse_pratt("0 + * 0", &table()).unwrap_err(),
+        ParseError::UnexpectedToken {
+            found: Terminal::Star,
+            position: 2,
+            span: Span { start: 4, end: 5 },
+            expected: vec![Terminal::Zero, Terminal::LeftParen, Terminal::Minus],
+        }
+    );
+    assert_eq!(
+        parser.parse_pratt("( 0", &table()).unwrap_err(),
+        ParseError::UnexpectedEnd {
+            expected: vec![
+                Terminal::Plus,
+                Terminal::Minus,
+                Terminal::Star,
+                Terminal::Arrow,
+                Terminal::RightParen,
+            ],
+        }
+    );
+    assert!(matches!(
+        parser.parse_pratt("0 )", &table()),
+        Err(ParseError::UnexpectedToken {
+            found: Terminal::RightParen,
+            position: 1,
+            ..
+        })
+    ));
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use parser_macros::{Expression, Grammar, NonTerminal, Terminal};
+
+#[test]
+fn railroad_diagram() {
+    let grammar = common::arithmetic_grammar();
+    let svg = grammar.to_railroad_diagram();
+
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(svg.ends_with("</svg>\n"));
+    assert_eq!(svg, grammar.clone().to_railroad_diagram());
+
+    // One diagram per nonterminal, in order, with a box per symbol.
+    assert_eq!(svg.matches("<g>").count(), 5);
+    let titles: Vec<usize> = ["sum:", "sub:", "mult:", "atom:", "number:"]
+        .iter()
+        .map(|title| svg.find(&format!(">{title}<")).expect("Has a title"))
+        .collect();
+    assert!(titles.is_sorted());
+    assert_eq!(svg.matches("<rect").count(), 17);
+    assert_eq!(svg.matches(r#"rx="12""#).count(), 6);
+    assert!(svg.contains(">'('<"));
+    assert!(svg.contains(">mult<"));
+}
+
+#[test]
+fn railroad_diagram_escapes_text() {
+    let grammar = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(
+            NonTerminal::Sum,
+            vec![
+                vec![
+                    Expression::Terminal(Terminal::Less),
+                    Expression::Terminal(Terminal::StringLiteral("a&b".to_string())),
+                ],
+                vec![],
+            ],
+        )],
+    );
+    let svg = grammar.to_railroad_diagram();
+
+    assert!(svg.contains(">'&lt;'<"));
+    assert!(svg.contains(">'&quot;a&amp;b&quot;'<"));
+    assert_eq!(svg.matches("<rect").count(), 2);
+}
@@ -0,0 +1,246 @@
+mod common;
+
+use std::collections::HashSet;
+
+use parser_macros::{
+    Expression, Grammar, NonTerminal, ParseError, Parser, Span, StackValue, Terminal, TokenizeError,
+};
+
+#[test]
+fn valid_input_has_no_errors() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let (tree, errors) = parser.parse_recovering("0 + 0 * 0");
+
+    assert!(errors.is_empty());
+    assert_eq!(
+        format!("{tree:?}"),
+        format!("{:?}", parser.parse("0 + 0 * 0").ok())
+    );
+}
+
+#[test]
+fn resumes_after_an_unexpected_token() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let (tree, errors) = parser.parse_recovering("0 + + 0");
+
+    assert!(tree.is_some());
+    assert_eq!(
+        errors,
+        vec![ParseError::UnexpectedToken {
+            found: Terminal::Plus,
+            position: 2,
+            span: Span { start: 4, end: 5 },
+            expected: vec![Terminal::LeftParen, Terminal::Zero],
+        }]
+    );
+}
+
+#[test]
+fn collects_several_errors() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let (tree, errors) = parser.parse_recovering("0 + # * 0 + 0 + 0 ) 0 - 0");
+
+    assert!(tree.is_some());
+    assert_eq!(
+        errors,
+        vec![
+            ParseError::Tokenize(TokenizeError::InvalidToken {
+                token: "#".to_string(),
+                span: Span { start: 4, end: 5 },
+            }),
+            ParseError::UnexpectedToken {
+                found: Terminal::Star,
+                position: 3,
+                span: Span { start: 6, end: 7 },
+                expected: vec![Terminal::LeftParen, Terminal::Zero],
+            },
+            ParseError::UnexpectedToken {
+                found: Terminal::RightParen,
+                position: 9,
+                span: Span { start: 18, end: 19 },
+                expected: vec![Terminal::Plus],
+            },
+        ]
+    );
+}
+
+#[test]
+fn wraps_unfinished_input() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let (tree, errors) = parser.parse_recovering("( 0 + 0 * ( 0 ) 0");
+
+    assert!(tree.is_some());
+    assert_eq!(
+        errors.first(),
+        Some(&ParseError::UnexpectedToken {
+            found: Terminal::Zero,
+            position: 8,
+            span: Span { start: 16, end: 17 },
+            expected: vec![
+                Terminal::Plus,
+                Terminal::Minus,
+                Terminal::Star,
+                Terminal::RightParen
+            ],
+        })
+    );
+}
+
+#[test]
+fn suppresses_errors_right_after_a_recovery() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    // The first `)` comes two tokens after recovering from the `*`. The
+    // `#` is found first, by the tokenizer, but is listed where it is.
+    let (tree, errors) = parser.parse_recovering("0 + * 0 ) 0 - 0 - 0 - 0 ) 0 #");
+
+    assert!(tree.is_some());
+    assert_eq!(
+        errors.iter().map(ParseError::span).collect::<Vec<_>>(),
+        [
+            Some(Span { start: 4, end: 5 }),
+            Some(Span { start: 24, end: 25 }),
+            Some(Span { start: 28, end: 29 }),
+        ]
+    );
+}
+
+#[test]
+fn best_effort_returns_valid_islands() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let tree = |input| parser.parse(input).expect("Should be able to parse");
+
+    let (islands, errors) = parser.parse_best_effort("0 + 0 ) ) 0 * ( 0 - 0 )");
+    assert_eq!(
+        islands
+            .iter()
+            .map(StackValue::to_string)
+            .collect::<Vec<_>>(),
+        [tree("0 + 0").to_string(), tree("0 * ( 0 - 0 )").to_string()]
+    );
+    // The second `)` only fails because the first did.
+    assert_eq!(
+        errors,
+        vec![ParseError::UnexpectedToken {
+            found: Terminal::RightParen,
+            position: 3,
+            span: Span { start: 6, end: 7 },
+            expected: vec![Terminal::Plus],
+        }]
+    );
+
+    // An unfinished suffix keeps the trees it had built.
+    let (islands, errors) = parser.parse_best_effort("0 # 0 * ( 0 + 0");
+    assert_eq!(
+        islands
+            .iter()
+            .map(StackValue::to_string)
+            .collect::<Vec<_>>(),
+        [
+            tree("0").to_string(),
+            "(mult (atom (number '0')))".to_string(),
+            tree("0 + 0").to_string(),
+        ]
+    );
+    assert!(matches!(errors[0], ParseError::Tokenize(_)));
+    assert!(matches!(errors[1], ParseError::UnexpectedToken { .. }));
+    assert!(matches!(errors[2], ParseError::UnexpectedEnd { .. }));
+
+    let (islands, errors) = parser.parse_best_effort("( 0 )");
+    assert_eq!(islands, vec![tree("( 0 )")]);
+    assert!(errors.is_empty());
+}
+
+/// Lists of sums, so the commas between elements can be recovery points.
+fn sum_list_grammar() -> Grammar {
+    let mut grammar = common::arithmetic_grammar();
+    grammar.rules.extend(common::list_grammar().rules);
+    grammar.rules.insert(
+        NonTerminal::Number,
+        vec![vec![Expression::Terminal(Terminal::Zero)]],
+    );
+    grammar
+        .rules
+        .get_mut(&NonTerminal::Elements)
+        .unwrap()
+        .iter_mut()
+        .flatten()
+        .filter(|expr| **expr == Expression::NonTerminal(NonTerminal::Number))
+        .for_each(|expr| *expr = Expression::NonTerminal(NonTerminal::Sum));
+    grammar.starting_symbol = NonTerminal::List;
+    grammar
+}
+
+#[test]
+fn resumes_only_at_recovery_points() {
+    let parser = Parser::new(sum_list_grammar());
+    let sync = HashSet::from([Terminal::Comma]);
+    let input = "[ 0 + + 0 , 0 ]";
+    let error = ParseError::UnexpectedToken {
+        found: Terminal::Plus,
+        position: 3,
+        span: Span { start: 6, end: 7 },
+        expected: vec![Terminal::LeftParen, Terminal::Zero],
+    };
+
+    // FOLLOW sets let recovery resume at the second `+`, but with only
+    // commas to synchronize on, everything up to the comma is discarded.
+    let (tree, errors) = parser.parse_recovering(input);
+    assert_eq!(errors, vec![error.clone()]);
+    assert!(!tree.unwrap().to_string().contains("(sub '+' '0')"));
+
+    let (tree, errors) = parser.parse_with_recovery_points(input, &sync);
+    assert_eq!(errors, vec![error]);
+    let tree = tree.unwrap();
+    assert!(tree.to_string().contains("(sub '+' '0')"));
+    assert_eq!(tree.find_all(&NonTerminal::Elements).count(), 2);
+
+    // Without a comma left, recovery can only resume at the end of input.
+    let (tree, errors) = parser.parse_with_recovery_points("[ 0 + * 0 ]", &sync);
+    assert!(tree.is_some());
+    assert_eq!(errors.len(), 1);
+    let (_, errors) = parser.parse_with_recovery_points("[ 0 , 0 ]", &sync);
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn resumes_at_semicolons() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    // Sums each ended by a `;`.
+    let mut grammar = common::arithmetic_grammar();
+    grammar.rules.insert(
+        NonTerminal::List,
+        vec![
+            vec![
+                N(NonTerminal::List),
+                N(NonTerminal::Sum),
+                T(Terminal::Semicolon),
+            ],
+            vec![N(NonTerminal::Sum), T(Terminal::Semicolon)],
+        ],
+    );
+    grammar.starting_symbol = NonTerminal::List;
+    let parser = Parser::new(grammar);
+    let sync = HashSet::from([Terminal::Semicolon]);
+
+    let (tree, errors) = parser.parse_with_recovery_points("0 + + 0 ; 0 * 0 ; 0 ) ; 0 ;", &sync);
+    assert_eq!(
+        errors.iter().map(|error| error.span()).collect::<Vec<_>>(),
+        [
+            Some(Span { start: 4, end: 5 }),
+            Some(Span { start: 20, end: 21 })
+        ]
+    );
+    let tree = tree.expect("Should recover");
+    // The statements between the errors parse as usual.
+    assert!(tree.to_string().contains("'*' (atom (number '0'))"));
+    assert_eq!(
+        tree.leaves().filter(|t| **t == Terminal::Semicolon).count(),
+        4
+    );
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use std::collections::HashSet;
+
+use parser_macros::{ParseError, Parser, Span, Terminal};
+
+#[test]
+fn skipped_terminals_are_ignored() {
+    let parser = Parser::with_skipped(
+        common::arithmetic_grammar(),
+        HashSet::from([Terminal::Comma]),
+    );
+
+    let tree = parser
+        .parse(", 0 , + 0 ,")
+        .expect("Should be able to parse");
+    let plain = Parser::new(common::arithmetic_grammar())
+        .parse("0 + 0")
+        .expect("Should be able to parse");
+
+    assert_eq!(tree.to_string(), plain.to_string());
+}
+
+#[test]
+fn skipped_terminals_keep_positions() {
+    let parser = Parser::with_skipped(
+        common::arithmetic_grammar(),
+        HashSet::from([Terminal::Comma]),
+    );
+
+    let error = ParseError::UnexpectedToken {
+        found: Terminal::Zero,
+        position: 3,
+        span: Span { start: 6, end: 7 },
+        expected: vec![Terminal::Plus, Terminal::Minus, Terminal::Star],
+    };
+
+    assert_eq!(parser.parse("0 , , 0").err(), Some(error.clone()));
+    assert_eq!(parser.parse_recovering("0 , , 0").1.first(), Some(&error));
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use parser_macros::{Edit, Parser, Terminal};
+
+#[test]
+fn suggests_single_edits() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let edit = parser
+        .suggest_edit("( 0 + 0")
+        .expect("Should tokenize")
+        .expect("Should find an edit");
+    assert_eq!(
+        edit,
+        Edit::Insert {
+            terminal: Terminal::RightParen,
+            position: 4,
+        }
+    );
+    assert_eq!(edit.to_string(), "insert ')' at position 4");
+
+    assert_eq!(
+        parser.suggest_edit("0 + + 0"),
+        Ok(Some(Edit::Delete {
+            terminal: Terminal::Plus,
+            position: 2,
+        }))
+    );
+    assert_eq!(
+        parser.suggest_edit("0 ( 0 )"),
+        Ok(Some(Edit::Insert {
+            terminal: Terminal::Plus,
+            position: 1,
+        }))
+    );
+}
+
+#[test]
+fn nothing_to_suggest() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    assert_eq!(parser.suggest_edit("0 + 0"), Ok(None));
+    assert_eq!(parser.suggest_edit("( ( 0"), Ok(None), "Needs two edits");
+    assert!(parser.suggest_edit("0 + #").is_err());
+}
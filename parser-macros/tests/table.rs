@@ -0,0 +1,78 @@
+mod common;
+
+use parser_macros::{Action, Expression, Grammar, NonTerminal, ParseTable, Terminal};
+use std::collections::HashMap;
+
+#[test]
+fn renders_textbook_grid() {
+    let mut rules = HashMap::new();
+
+    rules.insert(
+        NonTerminal::Sum,
+        vec![
+            vec![
+                Expression::NonTerminal(NonTerminal::Sum),
+                Expression::Terminal(Terminal::Plus),
+                Expression::NonTerminal(NonTerminal::Number),
+            ],
+            vec![Expression::NonTerminal(NonTerminal::Number)],
+        ],
+    );
+    rules.insert(
+        NonTerminal::Number,
+        vec![vec![Expression::Terminal(Terminal::Zero)]],
+    );
+
+    let table = ParseTable::new(&Grammar::new(NonTerminal::Sum, rules));
+
+    assert_eq!(
+        table.to_table_string(),
+        "\
+state | +  | 0  | $   | sum | number
+0     |    | s3 |     | 1   | 2
+1     | s4 |    | acc |     |
+2     | r2 |    | r2  |     |
+3     | r3 |    | r3  |     |
+4     |    | s3 |     |     | 5
+5     | r1 |    | r1  |     |
+
+0: sum' -> sum
+1: sum -> sum '+' number
+2: sum -> number
+3: number -> '0'
+"
+    );
+}
+
+#[test]
+fn arithmetic_grammar_has_no_conflicts() {
+    let table = ParseTable::new(&common::arithmetic_grammar());
+
+    let terminals = [
+        Terminal::Plus,
+        Terminal::Minus,
+        Terminal::Star,
+        Terminal::LeftParen,
+        Terminal::RightParen,
+        Terminal::Zero,
+    ];
+
+    for state in 0..table.state_count() {
+        for lookahead in terminals.iter().map(Some).chain([None]) {
+            assert!(table.actions(state, lookahead).len() <= 1);
+        }
+    }
+
+    let accepting = (0..table.state_count())
+        .filter(|state| table.actions(*state, None) == [Action::Accept])
+        .count();
+    assert_eq!(accepting, 1);
+
+    assert_eq!(
+        table.production(0),
+        Some((
+            &NonTerminal::Sum,
+            [Expression::NonTerminal(NonTerminal::Sum)].as_slice()
+        ))
+    );
+}
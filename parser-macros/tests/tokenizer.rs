@@ -0,0 +1,700 @@
+mod common;
+
+use std::collections::HashMap;
+
+use parser_macros::{
+    ByteLexer, CharClass, CharLexer, Expression, FloatLiteral, Grammar, Lexer, NonTerminal,
+    ParseError, Parser, Span, StackValue, Terminal, Token, TokenizeError, Tokenizer, Trivia,
+};
+
+fn terminals(input: &str) -> Vec<Terminal> {
+    Tokenizer::new()
+        .tokenize(input)
+        .expect("Should be able to tokenize")
+        .into_iter()
+        .map(|token| token.terminal)
+        .collect()
+}
+
+#[test]
+fn operators() {
+    assert_eq!(
+        terminals("( 0 + 0 ) * 0 - [ , ] ;"),
+        vec![
+            Terminal::LeftParen,
+            Terminal::Zero,
+            Terminal::Plus,
+            Terminal::Zero,
+            Terminal::RightParen,
+            Terminal::Star,
+            Terminal::Zero,
+            Terminal::Minus,
+            Terminal::LeftBracket,
+            Terminal::Comma,
+            Terminal::RightBracket,
+            Terminal::Semicolon,
+        ]
+    );
+    assert_eq!(Terminal::Semicolon.to_string(), ";");
+    assert_eq!(terminals("0;0"), terminals("0 ; 0"));
+}
+
+#[test]
+fn string_literals() {
+    let tokens = Tokenizer::new()
+        .tokenize(r#"[ "a b" ]"#)
+        .expect("Should be able to tokenize");
+
+    assert_eq!(
+        tokens[1].terminal,
+        Terminal::StringLiteral("a b".to_string())
+    );
+    assert_eq!(tokens[1].span, Span { start: 2, end: 7 });
+
+    assert_eq!(
+        terminals(r#""say \"hi\"" "back\\slash" "two\nlines""#),
+        vec![
+            Terminal::StringLiteral(r#"say "hi""#.to_string()),
+            Terminal::StringLiteral(r"back\slash".to_string()),
+            Terminal::StringLiteral("two\nlines".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn errors() {
+    assert_eq!(
+        Tokenizer::new().tokenize("0 + #"),
+        Err(TokenizeError::InvalidToken {
+            token: "#".to_string(),
+            span: Span { start: 4, end: 5 },
+        })
+    );
+
+    assert_eq!(
+        Tokenizer::new().tokenize(r#"0 "open"#),
+        Err(TokenizeError::UnterminatedString {
+            span: Span { start: 2, end: 3 },
+        })
+    );
+
+    assert_eq!(
+        Tokenizer::new().tokenize(r#""bad \t escape""#),
+        Err(TokenizeError::InvalidEscape {
+            escape: 't',
+            span: Span { start: 5, end: 7 },
+        })
+    );
+}
+
+#[test]
+fn parses_string_list() {
+    let mut rules = HashMap::new();
+    rules.insert(
+        NonTerminal::List,
+        vec![vec![
+            Expression::Terminal(Terminal::LeftBracket),
+            Expression::NonTerminal(NonTerminal::Elements),
+            Expression::Terminal(Terminal::RightBracket),
+        ]],
+    );
+    rules.insert(
+        NonTerminal::Elements,
+        vec![
+            vec![
+                Expression::Terminal(Terminal::StringLiteral(String::new())),
+                Expression::Terminal(Terminal::Comma),
+                Expression::NonTerminal(NonTerminal::Elements),
+            ],
+            vec![Expression::Terminal(Terminal::StringLiteral(String::new()))],
+            vec![],
+        ],
+    );
+    let parser = Parser::new(Grammar::new(NonTerminal::List, rules));
+
+    let tree = parser
+        .parse(r#"[ "a b" , "c" ]"#)
+        .expect("Should be able to parse");
+
+    let StackValue::Tree { values, .. } = &tree else {
+        panic!("Expected a tree");
+    };
+    let StackValue::Tree { values, .. } = &values[1] else {
+        panic!("Expected the elements");
+    };
+    assert!(matches!(
+        &values[0],
+        StackValue::Terminal(Token {
+            terminal: Terminal::StringLiteral(s),
+            ..
+        }) if s == "a b"
+    ));
+}
+
+#[test]
+fn trivia() {
+    let input = "  0 + // first\n  ( 0 ) // last";
+    let tokens = Tokenizer::new()
+        .tokenize(input)
+        .expect("Should be able to tokenize");
+
+    assert_eq!(
+        tokens[0].leading_trivia,
+        vec![Trivia::Whitespace("  ".to_string())]
+    );
+    assert_eq!(
+        tokens[1].trailing_trivia,
+        vec![
+            Trivia::Whitespace(" ".to_string()),
+            Trivia::Comment("// first".to_string()),
+            Trivia::Whitespace("\n".to_string()),
+        ]
+    );
+    assert_eq!(
+        tokens[2].leading_trivia,
+        vec![Trivia::Whitespace("  ".to_string())]
+    );
+    assert_eq!(
+        tokens[4].trailing_trivia,
+        vec![
+            Trivia::Whitespace(" ".to_string()),
+            Trivia::Comment("// last".to_string()),
+        ]
+    );
+
+    let mut reproduced = String::new();
+    for token in &tokens {
+        for trivia in &token.leading_trivia {
+            reproduced.push_str(&trivia.to_string());
+        }
+        reproduced.push_str(&token.terminal.to_string());
+        for trivia in &token.trailing_trivia {
+            reproduced.push_str(&trivia.to_string());
+        }
+    }
+    assert_eq!(reproduced, input);
+}
+
+#[test]
+fn trees_carry_trivia() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let tree = parser
+        .parse("// sum\n0 + ( 0 ) // done")
+        .expect("Should be able to parse");
+
+    assert_eq!(
+        tree.leading_trivia(),
+        &[
+            Trivia::Comment("// sum".to_string()),
+            Trivia::Whitespace("\n".to_string()),
+        ]
+    );
+    assert_eq!(
+        tree.trailing_trivia(),
+        &[
+            Trivia::Whitespace(" ".to_string()),
+            Trivia::Comment("// done".to_string()),
+        ]
+    );
+
+    let StackValue::Tree { values, .. } = &tree else {
+        panic!("Expected a tree");
+    };
+    assert_eq!(
+        values[2].leading_trivia(),
+        &[] as &[Trivia],
+        "Trivia between tokens trails the token before"
+    );
+    assert_eq!(
+        values[1].trailing_trivia(),
+        &[Trivia::Whitespace(" ".to_string())]
+    );
+}
+
+#[test]
+fn longest_operator_wins() {
+    assert_eq!(
+        terminals("0 = 0 == 0"),
+        vec![
+            Terminal::Zero,
+            Terminal::Equal,
+            Terminal::Zero,
+            Terminal::DoubleEqual,
+            Terminal::Zero,
+        ]
+    );
+    assert_eq!(
+        terminals("0-0->0"),
+        vec![
+            Terminal::Zero,
+            Terminal::Minus,
+            Terminal::Zero,
+            Terminal::Arrow,
+            Terminal::Zero,
+        ]
+    );
+    assert_eq!(
+        terminals("<=< >=> - >"),
+        vec![
+            Terminal::LessEqual,
+            Terminal::Less,
+            Terminal::GreaterEqual,
+            Terminal::Greater,
+            Terminal::Minus,
+            Terminal::Greater,
+        ]
+    );
+    assert_eq!(
+        Tokenizer::new().tokenize("0+#$%*0"),
+        Err(TokenizeError::InvalidToken {
+            token: "#$%".to_string(),
+            span: Span { start: 2, end: 5 },
+        })
+    );
+}
+
+#[test]
+fn grammar_uses_multi_character_operators() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let parser = Parser::new(Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![vec![
+                    N(NonTerminal::Number),
+                    T(Terminal::DoubleEqual),
+                    N(NonTerminal::Number),
+                ]],
+            ),
+            (NonTerminal::Number, vec![vec![T(Terminal::Zero)]]),
+        ],
+    ));
+
+    assert!(parser.parse("0==0").is_ok());
+    assert!(parser.parse("0 = = 0").is_err());
+}
+
+#[test]
+fn identifiers() {
+    assert_eq!(
+        terminals("a + rate_2*_x 0b"),
+        vec![
+            Terminal::Identifier("a".to_string()),
+            Terminal::Plus,
+            Terminal::Identifier("rate_2".to_string()),
+            Terminal::Star,
+            Terminal::Identifier("_x".to_string()),
+            Terminal::Zero,
+            Terminal::Identifier("b".to_string()),
+        ]
+    );
+    assert_eq!(
+        Terminal::Identifier("a".to_string()).kind().to_string(),
+        "identifier"
+    );
+}
+
+#[test]
+fn numbers() {
+    assert_eq!(
+        terminals("42 + 2.75 * 3. - .5 0"),
+        vec![
+            Terminal::Int(Some(42)),
+            Terminal::Plus,
+            Terminal::Float(Some(FloatLiteral(2.75))),
+            Terminal::Star,
+            Terminal::Float(Some(FloatLiteral(3.0))),
+            Terminal::Minus,
+            Terminal::Float(Some(FloatLiteral(0.5))),
+            Terminal::Zero,
+        ]
+    );
+    assert_eq!(Terminal::Int(Some(42)).kind().to_string(), "integer");
+    assert_eq!(Terminal::Float(Some(FloatLiteral(0.5))).to_string(), "0.5");
+
+    // A minus is never part of the literal, so negation is up to the
+    // grammar and `1-1` is still a subtraction.
+    assert_eq!(terminals("-1"), [Terminal::Minus, Terminal::Int(Some(1))]);
+    assert_eq!(
+        terminals("1-1"),
+        [
+            Terminal::Int(Some(1)),
+            Terminal::Minus,
+            Terminal::Int(Some(1))
+        ]
+    );
+    assert_eq!(
+        terminals("-.5"),
+        [Terminal::Minus, Terminal::Float(Some(FloatLiteral(0.5)))]
+    );
+
+    assert_eq!(
+        Tokenizer::new().tokenize("0 + 2.75.15"),
+        Err(TokenizeError::MalformedNumber {
+            number: "2.75.15".to_string(),
+            span: Span { start: 4, end: 11 },
+        })
+    );
+    assert!(matches!(
+        Tokenizer::new().tokenize("99999999999999999999"),
+        Err(TokenizeError::MalformedNumber { .. })
+    ));
+}
+
+#[test]
+fn parses_numbers() {
+    use Expression::Terminal as T;
+
+    let grammar = Grammar::from_rules(
+        NonTerminal::Sum,
+        [(
+            NonTerminal::Sum,
+            vec![vec![T(Terminal::Int(None))], vec![T(Terminal::Float(None))]],
+        )],
+    );
+    let parser = Parser::new(grammar);
+
+    let tree = parser.parse("12").expect("Should be able to parse");
+    assert_eq!(tree.leaves().next(), Some(&Terminal::Int(Some(12))));
+    assert!(parser.parse("1.5").is_ok());
+    assert!(parser.parse("1 2").is_err());
+}
+
+#[test]
+fn parses_variables() {
+    let parser = Parser::new(common::variable_grammar());
+
+    let tree = parser.parse("a + b * c").expect("Should be able to parse");
+    assert_eq!(
+        tree.leaves().map(Terminal::to_string).collect::<Vec<_>>(),
+        ["a", "+", "b", "*", "c"]
+    );
+    assert!(parser.parse("( a - 0 ) * b").is_ok());
+    assert!(parser.parse("a b").is_err());
+}
+
+#[test]
+fn any_whitespace_separates_tokens() {
+    let spaced = terminals("0 + 0");
+
+    for input in [
+        "0\t+\n0",
+        "0 \t+\r\n  0",
+        "\n\n0+\t\t0\n",
+        "0\u{a0}+\u{2003}0",
+    ] {
+        assert_eq!(terminals(input), spaced, "{input:?}");
+    }
+
+    let parser = Parser::new(common::arithmetic_grammar());
+    assert_eq!(
+        parser.parse("0\t+\n0").unwrap().to_string(),
+        parser.parse("0 + 0").unwrap().to_string()
+    );
+}
+
+#[test]
+fn char_input_counts_chars() {
+    let input = "\"é\" + été // ü\n";
+    let chars: Vec<char> = input.chars().collect();
+    let spans = |tokens: Vec<Token>| -> Vec<(usize, usize)> {
+        tokens
+            .into_iter()
+            .map(|token| (token.span.start, token.span.end))
+            .collect()
+    };
+
+    let from_chars = Tokenizer::new()
+        .tokenize_chars(&chars)
+        .expect("Should be able to tokenize");
+    let from_str = Tokenizer::new()
+        .tokenize(input)
+        .expect("Should be able to tokenize");
+
+    assert_eq!(
+        from_chars
+            .iter()
+            .map(|token| (&token.terminal, &token.trailing_trivia))
+            .collect::<Vec<_>>(),
+        from_str
+            .iter()
+            .map(|token| (&token.terminal, &token.trailing_trivia))
+            .collect::<Vec<_>>(),
+    );
+    assert_eq!(spans(from_chars), [(0, 3), (4, 5), (6, 9)]);
+    assert_eq!(spans(from_str), [(0, 4), (5, 6), (7, 12)]);
+
+    assert_eq!(
+        Tokenizer::new().tokenize_chars(&"é # \"\\q\"".chars().collect::<Vec<_>>()),
+        Err(TokenizeError::InvalidToken {
+            token: "#".to_string(),
+            span: Span { start: 2, end: 3 },
+        })
+    );
+    assert_eq!(
+        Tokenizer::new().tokenize_chars(&"é \"\\q\"".chars().collect::<Vec<_>>()),
+        Err(TokenizeError::InvalidEscape {
+            escape: 'q',
+            span: Span { start: 3, end: 5 },
+        })
+    );
+
+    // Errors read in the unit their spans count.
+    let input = "é #";
+    let error = |result: Result<Vec<Token>, TokenizeError>| result.unwrap_err().to_string();
+    assert_eq!(
+        error(Tokenizer::new().tokenize_chars(&input.chars().collect::<Vec<_>>())),
+        "Invalid token \"#\" at offset 2"
+    );
+    assert_eq!(
+        error(Tokenizer::new().tokenize(input)),
+        "Invalid token \"#\" at offset 3"
+    );
+}
+
+#[test]
+fn whitespace_tokens() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    let whitespace = |text: &str| Terminal::Whitespace(text.to_string());
+    let tokens: Vec<Terminal> = Tokenizer::with_whitespace()
+        .tokenize("0 +\n 0 // done")
+        .expect("Should be able to tokenize")
+        .into_iter()
+        .map(|token| token.terminal)
+        .collect();
+    assert_eq!(
+        tokens,
+        [
+            Terminal::Zero,
+            whitespace(" "),
+            Terminal::Plus,
+            whitespace("\n "),
+            Terminal::Zero,
+            whitespace(" "),
+        ]
+    );
+
+    // Grammars that do not care about spacing can still skip it.
+    let parser = Parser::with_lexer_skipping(
+        common::arithmetic_grammar(),
+        Tokenizer::with_whitespace(),
+        [Terminal::Whitespace(String::new())].into(),
+    );
+    assert!(parser.parse("( 0 + 0 ) * 0").is_ok());
+
+    // Atoms have to be separated by spacing, and nothing else may have any.
+    let grammar = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![vec![
+                    N(NonTerminal::Atom),
+                    T(Terminal::Whitespace(String::new())),
+                    N(NonTerminal::Atom),
+                ]],
+            ),
+            (
+                NonTerminal::Atom,
+                vec![vec![
+                    T(Terminal::LeftParen),
+                    T(Terminal::Zero),
+                    T(Terminal::RightParen),
+                ]],
+            ),
+        ],
+    );
+    let parser = Parser::with_lexer(grammar, Tokenizer::with_whitespace());
+    assert!(parser.parse("(0) \t(0)").is_ok());
+    assert!(parser.parse("(0)(0)").is_err());
+    assert!(parser.parse("( 0) (0)").is_err());
+}
+
+#[test]
+fn char_classes() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    assert_eq!(Terminal::char('é').kind(), Terminal::any(CharClass::Letter));
+    assert_eq!(Terminal::char('٣').kind(), Terminal::any(CharClass::Digit));
+    assert_eq!(Terminal::char('+').kind(), Terminal::char('+'));
+
+    // Identifiers spelled out char by char: a letter, then letters and
+    // digits, joined by `+`.
+    let letter = || T(Terminal::any(CharClass::Letter));
+    let grammar = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![
+                    vec![
+                        N(NonTerminal::Sum),
+                        T(Terminal::char('+')),
+                        N(NonTerminal::Atom),
+                    ],
+                    vec![N(NonTerminal::Atom)],
+                ],
+            ),
+            (
+                NonTerminal::Atom,
+                vec![
+                    vec![N(NonTerminal::Atom), letter()],
+                    vec![N(NonTerminal::Atom), T(Terminal::any(CharClass::Digit))],
+                    vec![letter()],
+                ],
+            ),
+        ],
+    );
+    let parser = Parser::with_lexer(grammar, CharLexer);
+
+    let tree = parser.parse("rate2+été").expect("Should be able to parse");
+    assert_eq!(
+        tree.leaves().map(Terminal::to_string).collect::<String>(),
+        "rate2+été"
+    );
+    assert_eq!(
+        CharLexer.lex("+é").unwrap().last().map(|token| token.span),
+        Some(Span { start: 1, end: 3 })
+    );
+    assert!(parser.parse("жук+x").is_ok());
+    assert!(parser.parse("2rate").is_err());
+    assert!(parser.parse("a + b").is_err());
+    assert!(parser.parse("a-b").is_err());
+}
+
+#[test]
+fn byte_grammars() {
+    use Expression::{NonTerminal as N, Terminal as T};
+
+    // A frame: 0xaa, one or more ASCII bytes, then 0xff.
+    let ascii = || T(Terminal::byte_range(0x00..=0x7f));
+    let grammar = Grammar::from_rules(
+        NonTerminal::Sum,
+        [
+            (
+                NonTerminal::Sum,
+                vec![vec![
+                    T(Terminal::byte(0xaa)),
+                    N(NonTerminal::Atom),
+                    T(Terminal::byte(0xff)),
+                ]],
+            ),
+            (
+                NonTerminal::Atom,
+                vec![vec![N(NonTerminal::Atom), ascii()], vec![ascii()]],
+            ),
+        ],
+    );
+    let parser = Parser::new(grammar);
+    let lexer = ByteLexer::new().with_range(0x00..=0x7f);
+
+    let tokens = lexer.tokenize_bytes(&[0xaa, 0x00, b'A', 0xff]);
+    assert_eq!(
+        tokens
+            .iter()
+            .map(|(_, span)| span.start)
+            .collect::<Vec<_>>(),
+        [0, 1, 2, 3]
+    );
+    assert_eq!(tokens[2].0.kind(), Terminal::byte_range(0x00..=0x7f));
+    assert_eq!(tokens[2].0.to_string(), "0x41");
+    assert_eq!(tokens[3].0, Terminal::byte(0xff));
+
+    let tree = parser
+        .parse_spanned(tokens)
+        .expect("Should be able to parse");
+    assert_eq!(tree.leaves().count(), 4);
+
+    for frame in [&[0xaa, 0x80, 0xff][..], &[0xaa, 0xff], &[0xaa, 0x01]] {
+        assert!(parser.parse_spanned(lexer.tokenize_bytes(frame)).is_err());
+    }
+}
+
+#[test]
+fn span_helpers() {
+    let a = Span { start: 2, end: 5 };
+    let b = Span { start: 8, end: 9 };
+    let empty = Span { start: 4, end: 4 };
+
+    assert_eq!(a.merge(b), Span { start: 2, end: 9 });
+    assert_eq!(Span::merge(b, a), a.merge(b));
+    assert_eq!(a.merge(empty), a);
+
+    assert!(a.contains(2) && a.contains(4));
+    assert!(!a.contains(5) && !a.contains(1));
+    assert!(!empty.contains(4));
+
+    assert_eq!((a.len(), b.len(), empty.len()), (3, 1, 0));
+    assert!(empty.is_empty() && !a.is_empty());
+
+    assert_eq!(a.to_string(), "2..5");
+}
+
+#[test]
+fn lenient_tokens() {
+    let lenient = Tokenizer::new().lenient();
+
+    let tokens = lenient
+        .tokenize("0 + # * 0")
+        .expect("Lenient tokenizing never fails");
+    assert_eq!(
+        tokens
+            .iter()
+            .map(|token| token.terminal.clone())
+            .collect::<Vec<_>>(),
+        vec![
+            Terminal::Zero,
+            Terminal::Plus,
+            Terminal::Error("#".to_string()),
+            Terminal::Star,
+            Terminal::Zero,
+        ]
+    );
+    assert_eq!(tokens[2].span, Span { start: 4, end: 5 });
+    assert!(Tokenizer::new().tokenize("0 + # * 0").is_err());
+
+    // Bad string literals span up to where tokenizing resumes.
+    let tokens = lenient
+        .tokenize("\"a\\q\" 0 \"b")
+        .expect("Lenient tokenizing never fails");
+    assert_eq!(
+        tokens
+            .iter()
+            .map(|token| (token.terminal.clone(), token.span))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                Terminal::Error("\"a\\q\"".to_string()),
+                Span { start: 0, end: 5 }
+            ),
+            (Terminal::Zero, Span { start: 6, end: 7 }),
+            (
+                Terminal::Error("\"b".to_string()),
+                Span { start: 8, end: 10 }
+            ),
+        ]
+    );
+
+    let parser = Parser::with_lexer(common::arithmetic_grammar(), lenient);
+    assert_eq!(
+        parser.parse("0 + § * 0"),
+        Err(ParseError::UnexpectedToken {
+            expected: vec![Terminal::LeftParen, Terminal::Zero],
+            found: Terminal::Error("§".to_string()),
+            position: 2,
+            span: Span { start: 4, end: 6 },
+        })
+    );
+
+    let (tree, errors) = parser.parse_recovering("0 + # + 0 * 0");
+    assert!(tree.is_some());
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(
+        &errors[0],
+        ParseError::UnexpectedToken { found: Terminal::Error(text), .. } if text == "#"
+    ));
+}
@@ -0,0 +1,235 @@
+mod common;
+
+use parser_macros::{NonTerminal, Parser, Spacing, StackValue, Terminal};
+
+/// `levels` atoms each wrapping the next in parentheses, around a `0`.
+fn nested_parens(levels: usize) -> StackValue {
+    let mut tree = StackValue::Tree {
+        head: NonTerminal::Number,
+        values: vec![StackValue::Terminal(Terminal::Zero.into())],
+    };
+
+    for _ in 0..levels {
+        tree = StackValue::Tree {
+            head: NonTerminal::Atom,
+            values: vec![
+                StackValue::Terminal(Terminal::LeftParen.into()),
+                tree,
+                StackValue::Terminal(Terminal::RightParen.into()),
+            ],
+        };
+    }
+
+    tree
+}
+
+#[test]
+fn display_as_sexpr() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let tree = parser.parse("0 * 0").expect("Should be able to parse");
+
+    assert_eq!(
+        tree.to_string(),
+        "(sum (sub (mult (mult (atom (number '0'))) '*' (atom (number '0')))))"
+    );
+    assert_eq!(tree.depth(), 6);
+}
+
+#[test]
+fn node_count() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    // (sum (sub (mult (atom '(' (sum (sub (mult (mult (atom (number '0')))
+    //   '*' (atom (number '0'))))) ')'))))
+    let tree = parser.parse("( 0 * 0 )").expect("Should be able to parse");
+    assert_eq!(tree.node_count(), 17);
+    assert_eq!(tree.depth(), 10);
+
+    assert_eq!(StackValue::Terminal(Terminal::Zero.into()).node_count(), 1);
+}
+
+#[test]
+fn deep_trees_do_not_overflow() {
+    let levels = 100_000;
+    let tree = nested_parens(levels);
+
+    assert_eq!(tree.depth(), levels + 1);
+    assert_eq!(tree.node_count(), 3 * levels + 2);
+
+    let sexpr = tree.to_string();
+    assert!(sexpr.starts_with("(atom '(' (atom '('"));
+    assert!(sexpr.contains("(atom '(' (number '0') ')')"));
+    assert!(sexpr.ends_with("')') ')')"));
+
+    drop(tree);
+
+    let input = format!("{}0{}", "( ".repeat(levels), " )".repeat(levels));
+
+    let parser = Parser::new(common::arithmetic_grammar());
+    let tree = parser.parse(&input).expect("Should be able to parse");
+
+    // Each parenthesis wraps the sum in sum, sub, mult and atom.
+    assert_eq!(tree.depth(), 4 * levels + 5);
+
+    let copy = tree.clone();
+    assert_eq!(copy, tree);
+    assert!(format!("{copy:?}").starts_with("Tree { head: Sum, values: [Tree { head: Sub"));
+
+    drop(copy);
+    drop(tree);
+}
+
+#[test]
+fn leaves_reproduce_the_input() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    for input in ["0 + 0 * 0", "( 0 - 0 ) * ( 0 + 0 )", "0"] {
+        let tree = parser.parse(input).expect("Should be able to parse");
+
+        let leaves: Vec<String> = tree.leaves().map(Terminal::to_string).collect();
+        assert_eq!(leaves.join(" "), input);
+    }
+
+    let tree = Parser::new(common::list_grammar())
+        .parse("[ ]")
+        .expect("Should be able to parse");
+    assert_eq!(
+        tree.leaves().collect::<Vec<_>>(),
+        [&Terminal::LeftBracket, &Terminal::RightBracket]
+    );
+}
+
+#[test]
+fn map_terminals_keeps_structure() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let tree = parser
+        .parse("0 + ( 0 * 0 )")
+        .expect("Should be able to parse");
+
+    let mut count = 0;
+    let numbered = tree.map_terminals(|terminal| match terminal {
+        Terminal::Zero => {
+            count += 1;
+            Terminal::StringLiteral(count.to_string())
+        }
+        other => other.clone(),
+    });
+
+    assert_eq!(
+        numbered
+            .leaves()
+            .map(Terminal::to_string)
+            .collect::<Vec<_>>(),
+        ["\"1\"", "+", "(", "\"2\"", "*", "\"3\"", ")"]
+    );
+    assert_eq!(numbered.depth(), tree.depth());
+    assert_eq!(
+        numbered
+            .to_string()
+            .replace("'\"1\"'", "'0'")
+            .replace("'\"2\"'", "'0'")
+            .replace("'\"3\"'", "'0'"),
+        tree.to_string()
+    );
+
+    let deep = nested_parens(100_000).map_terminals(Terminal::kind);
+    assert_eq!(deep.depth(), 100_001);
+}
+
+#[test]
+fn structural_eq_ignores_positions_and_text() {
+    let parser = Parser::new(common::variable_grammar());
+    let parse = |input| parser.parse(input).expect("Should be able to parse");
+
+    let tree = parse("a + ( 0 * b )");
+    let spaced = parse("  a+(0\t*   b)");
+    assert_ne!(tree, spaced);
+    assert!(tree.structural_eq(&spaced));
+    assert!(tree.structural_eq(&parse("rate + ( 0 * rate )")));
+
+    assert!(!tree.structural_eq(&parse("a + ( 0 - b )")));
+    assert!(!tree.structural_eq(&parse("a + ( 0 * 0 )")));
+    assert!(!tree.structural_eq(&parse("a + 0 * b")));
+
+    assert!(nested_parens(100_000).structural_eq(&nested_parens(100_000)));
+    assert!(!nested_parens(100_000).structural_eq(&nested_parens(99_999)));
+}
+
+#[test]
+fn find_subtrees_by_head() {
+    let parser = Parser::new(common::arithmetic_grammar());
+    let tree = parser
+        .parse("( 0 + 0 ) * 0")
+        .expect("Should be able to parse");
+
+    assert_eq!(tree.find(&NonTerminal::Sum), Some(&tree));
+    assert_eq!(tree.find_all(&NonTerminal::Number).count(), 3);
+    assert_eq!(tree.find(&NonTerminal::List), None);
+
+    // Pre-order, so the parenthesized atom comes before those inside it.
+    let atoms: Vec<String> = tree
+        .find_all(&NonTerminal::Atom)
+        .map(|atom| atom.leaves().map(Terminal::to_string).collect())
+        .collect();
+    assert_eq!(atoms, ["(0+0)", "0", "0", "0"]);
+
+    let nested = nested_parens(5);
+    assert_eq!(nested.find_all_within(&NonTerminal::Atom, 2).count(), 3);
+    assert_eq!(nested.find_within(&NonTerminal::Number, 4), None);
+    assert!(nested.find_within(&NonTerminal::Number, 5).is_some());
+
+    assert!(nested_parens(100_000).find(&NonTerminal::Number).is_some());
+}
+
+#[test]
+fn collapse_unit_productions() {
+    let mut parser = Parser::new(common::arithmetic_grammar());
+
+    let full = parser.parse("0").expect("Should be able to parse");
+    assert_eq!(full.to_string(), "(sum (sub (mult (atom (number '0')))))");
+
+    parser.collapse_unit_productions(true);
+    let flat = parser.parse("0").expect("Should be able to parse");
+    assert_eq!(flat.to_string(), "(sum '0')");
+
+    let flat = parser.parse("( 0 ) * 0").expect("Should be able to parse");
+    assert_eq!(
+        flat.to_string(),
+        "(sum (mult '(' (sum '0') ')') '*' (atom '0'))"
+    );
+    let full = Parser::new(common::arithmetic_grammar())
+        .parse("( 0 ) * 0")
+        .expect("Should be able to parse");
+    assert!(flat.leaves().eq(full.leaves()));
+}
+
+#[test]
+fn to_source_round_trips() {
+    let parser = Parser::new(common::arithmetic_grammar());
+
+    let tree = parser.parse("( 0 * 0 )").unwrap();
+    assert_eq!(tree.to_source(), "(0 * 0)");
+    assert_eq!(tree.to_source_with(&Spacing::everywhere()), "( 0 * 0 )");
+
+    for input in ["0", "(0*0)", "0 - ( 0 + 0 ) * ( ( 0 ) )", "0+0-0*0"] {
+        let tree = parser.parse(input).unwrap();
+        let source = tree.to_source();
+        let reparsed = parser.parse(&source).unwrap();
+        assert!(tree.structural_eq(&reparsed), "{input} printed as {source}");
+    }
+
+    let lists = Parser::new(common::list_grammar());
+    let tree = lists.parse("[ 0 , 0,0 ]").unwrap();
+    assert_eq!(tree.to_source(), "[0, 0, 0]");
+    assert!(tree.structural_eq(&lists.parse(&tree.to_source()).unwrap()));
+
+    let tight = Spacing {
+        open: [Terminal::Star].into(),
+        close: [Terminal::Star].into(),
+    };
+    assert_eq!(
+        parser.parse("0 * 0 + 0").unwrap().to_source_with(&tight),
+        "0*0 + 0"
+    );
+}